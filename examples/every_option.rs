@@ -43,6 +43,7 @@ fn setup(mut commands: Commands, mut font_system: ResMut<CosmicFontSystem>) {
             // Computed fields
             padding: Default::default(),
             widget_size: Default::default(),
+            history: EditHistory::new(50),
         })
         .id();
 
@@ -0,0 +1,593 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use crate::*;
+use bevy::{prelude::*, render::render_resource::Extent3d, window::PrimaryWindow};
+use cosmic_text::{Selection, Shaping};
+
+pub(crate) struct AutocompletePlugin;
+
+impl Plugin for AutocompletePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            kb_autocomplete.in_set(InputSet).before(kb_move_cursor),
+        )
+        .add_systems(
+            Update,
+            (update_completions, show_completions)
+                .chain()
+                .after(InputSet),
+        );
+    }
+}
+
+/// Supplies the candidates of an [`Autocomplete`] popup
+pub trait CompletionProvider: Send + Sync + 'static {
+    /// Candidates for the word at `cursor`, a byte offset into `text`. Accepting a candidate
+    /// replaces the whole word, see [`word_at`].
+    fn complete(&self, text: &str, cursor: usize) -> Vec<String>;
+}
+
+impl<F> CompletionProvider for F
+where
+    F: Fn(&str, usize) -> Vec<String> + Send + Sync + 'static,
+{
+    fn complete(&self, text: &str, cursor: usize) -> Vec<String> {
+        self(text, cursor)
+    }
+}
+
+/// Completes the start of the word under the cursor from a fixed list, ignoring case
+#[derive(Clone, Debug, Default)]
+pub struct WordList(pub Vec<String>);
+
+impl CompletionProvider for WordList {
+    fn complete(&self, text: &str, cursor: usize) -> Vec<String> {
+        let (start, _) = word_at(text, cursor);
+        let prefix = text[start..cursor].to_lowercase();
+        self.0
+            .iter()
+            .filter(|word| {
+                word.to_lowercase().starts_with(&prefix) && **word != text[start..cursor]
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Byte range of the word around `cursor`, made of letters, digits and underscores
+pub fn word_at(text: &str, cursor: usize) -> (usize, usize) {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let start = text[..cursor]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word(*c))
+        .last()
+        .map_or(cursor, |(i, _)| i);
+    let end = text[cursor..]
+        .char_indices()
+        .find(|(_, c)| !is_word(*c))
+        .map_or(text.len(), |(i, _)| cursor + i);
+    (start, end)
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] to suggest completions while
+/// typing.
+///
+/// Candidates from the [`CompletionProvider`] are shown in a popup below the caret. Up/Down
+/// select a candidate, Tab or Enter accepts it in place of the word under the cursor and Escape
+/// dismisses the popup until the text changes again.
+///
+/// ```
+/// # use bevy_cosmic_edit::*;
+/// let autocomplete = Autocomplete::new(WordList(vec!["spawn".into(), "speed".into()]));
+/// ```
+#[derive(Component)]
+pub struct Autocomplete {
+    provider: Box<dyn CompletionProvider>,
+    /// Most candidates shown at once
+    pub max_items: usize,
+    /// Characters of the word to type before candidates are shown
+    pub min_chars: usize,
+    pub background_color: Color,
+    /// Background of the selected candidate
+    pub selected_color: Color,
+    candidates: Vec<String>,
+    selected: usize,
+    /// Escape was pressed, stays closed until the text changes
+    dismissed: bool,
+    /// Text and cursor offset the candidates were requested for
+    last_input: Option<(String, usize)>,
+    /// Keys were used by the popup this frame
+    captured: bool,
+    popup: Option<(Entity, Handle<Image>)>,
+    dirty: bool,
+}
+
+impl Autocomplete {
+    pub fn new(provider: impl CompletionProvider) -> Self {
+        Self {
+            provider: Box::new(provider),
+            max_items: 8,
+            min_chars: 1,
+            background_color: Color::rgb(0.97, 0.97, 0.97),
+            selected_color: Color::rgb(0.78, 0.86, 1.),
+            candidates: Vec::new(),
+            selected: 0,
+            dismissed: false,
+            last_input: None,
+            captured: false,
+            popup: None,
+            dirty: false,
+        }
+    }
+
+    /// Whether the popup is shown
+    pub fn is_open(&self) -> bool {
+        !self.candidates.is_empty()
+    }
+
+    /// Candidates currently shown
+    pub fn candidates(&self) -> &[String] {
+        &self.candidates
+    }
+
+    /// Index of the selected candidate
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Whether navigation keys go to the popup instead of the editor this frame
+    pub(crate) fn captures_keys(&self) -> bool {
+        self.is_open() || self.captured
+    }
+
+    fn close(&mut self) {
+        if self.is_open() {
+            self.candidates.clear();
+            self.selected = 0;
+            self.dirty = true;
+        }
+    }
+}
+
+/// Marks the popup of the [`Autocomplete`] on the contained entity
+#[derive(Component)]
+pub struct AutocompletePopup(pub Entity);
+
+fn kb_autocomplete(
+    active_editor: Res<FocusedWidget>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut q: Query<(
+        &mut Autocomplete,
+        &mut CosmicEditor,
+        Option<&mut EditHistory>,
+        Option<&CosmicKeymap>,
+    )>,
+    keymap: Res<CosmicKeymap>,
+    mut evw_changed: EventWriter<CosmicTextChanged>,
+    mut font_system: ResMut<CosmicFontSystem>,
+) {
+    let Some(entity) = active_editor.0 else {
+        return;
+    };
+    let Ok((mut autocomplete, mut editor, history_opt, keymap_opt)) = q.get_mut(entity) else {
+        return;
+    };
+
+    if autocomplete.captured {
+        autocomplete.captured = false;
+    }
+    if !autocomplete.is_open() {
+        return;
+    }
+
+    let len = autocomplete.candidates.len();
+    let mut accept = false;
+    for command in keymap_opt.unwrap_or(&*keymap).just_pressed(&keys) {
+        match command {
+            EditorCommand::Up => autocomplete.selected = (autocomplete.selected + len - 1) % len,
+            EditorCommand::Down => autocomplete.selected = (autocomplete.selected + 1) % len,
+            EditorCommand::Newline | EditorCommand::Indent => accept = true,
+            EditorCommand::Escape => {
+                autocomplete.dismissed = true;
+                autocomplete.close();
+            }
+            _ => continue,
+        }
+        autocomplete.captured = true;
+        autocomplete.dirty = true;
+    }
+
+    if !accept {
+        return;
+    }
+
+    let candidate = autocomplete.candidates[autocomplete.selected].clone();
+    let before = (editor.cursor(), editor.selection());
+    let cursor = editor.cursor();
+    let (start, end) = editor.with_buffer(|b| word_at(b.lines[cursor.line].text(), cursor.index));
+
+    editor.start_change();
+    editor.delete_range(
+        Cursor::new(cursor.line, start),
+        Cursor::new(cursor.line, end),
+    );
+    let after = editor.insert_at(Cursor::new(cursor.line, start), &candidate, None);
+    editor.set_selection(Selection::None);
+    editor.set_cursor(after);
+    let change = editor.finish_change();
+    editor.shape_as_needed(&mut font_system.0, false);
+    editor.set_redraw(true);
+
+    if let (Some(change), Some(mut history)) = (change, history_opt) {
+        history.seal();
+        history.record(change, before, &editor);
+        history.seal();
+    }
+
+    let text = editor.with_buffer(|b| b.get_text());
+    let offset = editor.with_buffer(|b| cursor_to_offset(b, after));
+    // Not completed again until something else is typed
+    autocomplete.last_input = Some((text.clone(), offset));
+    autocomplete.close();
+    evw_changed.send(CosmicTextChanged((entity, text)));
+}
+
+/// Asks the provider for candidates when the text or cursor of a focused widget changes
+fn update_completions(
+    mut q: Query<(
+        &mut Autocomplete,
+        Option<&CosmicEditor>,
+        Option<&ReadOnly>,
+        Option<&Placeholder>,
+    )>,
+) {
+    for (mut autocomplete, editor_opt, readonly_opt, placeholder_opt) in q.iter_mut() {
+        let placeholder_active = placeholder_opt.is_some_and(|p| p.is_active());
+        let editor = editor_opt.filter(|_| readonly_opt.is_none() && !placeholder_active);
+        let Some(editor) = editor else {
+            autocomplete.close();
+            if autocomplete.last_input.is_some() {
+                autocomplete.last_input = None;
+            }
+            continue;
+        };
+
+        let input = editor.with_buffer(|b| (b.get_text(), cursor_to_offset(b, editor.cursor())));
+        if autocomplete.last_input.as_ref() == Some(&input) {
+            continue;
+        }
+
+        let text_changed = autocomplete.last_input.as_ref().map(|(text, _)| text) != Some(&input.0);
+        if text_changed {
+            autocomplete.dismissed = false;
+        }
+        let (text, offset) = input.clone();
+        autocomplete.last_input = Some(input);
+
+        // Opened by typing, moving the cursor only updates an open popup
+        if autocomplete.dismissed || (!text_changed && !autocomplete.is_open()) {
+            autocomplete.close();
+            continue;
+        }
+
+        let (start, _) = word_at(&text, offset);
+        if text[start..offset].chars().count() < autocomplete.min_chars.max(1) {
+            autocomplete.close();
+            continue;
+        }
+
+        let mut candidates = autocomplete.provider.complete(&text, offset);
+        candidates.truncate(autocomplete.max_items);
+        if candidates != autocomplete.candidates {
+            autocomplete.candidates = candidates;
+            autocomplete.selected = 0;
+            autocomplete.dirty = true;
+        }
+    }
+}
+
+/// Spawns, positions and draws the popups, and removes them once closed
+fn show_completions(
+    mut commands: Commands,
+    mut q: Query<(
+        Entity,
+        &mut Autocomplete,
+        Option<&CosmicEditor>,
+        &DefaultAttrs,
+        &GlobalTransform,
+        &Sprite,
+        &CosmicPadding,
+        &XOffset,
+    )>,
+    mut popup_q: Query<(Entity, &AutocompletePopup, &mut Style)>,
+    node_q: Query<(&Node, &GlobalTransform, &CosmicSource)>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut images: ResMut<Assets<Image>>,
+    mut font_system: ResMut<CosmicFontSystem>,
+    mut swash_cache_state: ResMut<SwashCacheState>,
+) {
+    for (popup, owner, _) in popup_q.iter() {
+        if !q.contains(owner.0) {
+            commands.entity(popup).despawn_recursive();
+        }
+    }
+
+    let scale_factor = windows.get_single().map_or(1., |w| w.scale_factor());
+    let camera = camera_q.iter().find(|(c, _)| c.is_active);
+
+    for (
+        entity,
+        mut autocomplete,
+        editor_opt,
+        attrs,
+        sprite_transform,
+        sprite,
+        padding,
+        x_offset,
+    ) in q.iter_mut()
+    {
+        let (Some(editor), Some(camera)) = (editor_opt, camera) else {
+            if let Some((popup, _)) = autocomplete.popup.take() {
+                commands.entity(popup).despawn_recursive();
+            }
+            continue;
+        };
+        if !autocomplete.is_open() {
+            if let Some((popup, _)) = autocomplete.popup.take() {
+                commands.entity(popup).despawn_recursive();
+            }
+            continue;
+        }
+
+        let Some(position) = get_caret_window_pos(
+            editor,
+            widget_node(entity, (sprite_transform, sprite), &node_q),
+            padding,
+            x_offset,
+            camera,
+            scale_factor,
+        ) else {
+            continue;
+        };
+
+        let mut popup_size = None;
+        if autocomplete.dirty || autocomplete.popup.is_none() {
+            autocomplete.dirty = false;
+            let metrics = editor.with_buffer(|b| b.metrics());
+            let (pixels, width, height) = draw_completions(
+                &autocomplete,
+                &attrs.0,
+                metrics,
+                &mut font_system.0,
+                &mut swash_cache_state.swash_cache,
+            );
+
+            let handle = match &autocomplete.popup {
+                Some((_, handle)) => handle.clone(),
+                None => images.add(Image::default()),
+            };
+            if let Some(image) = images.get_mut(&handle) {
+                image.data.clear();
+                image.data.extend_from_slice(&pixels);
+                image.resize(Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                });
+            }
+            popup_size = Some(Vec2::new(width as f32, height as f32) / scale_factor);
+
+            if autocomplete.popup.is_none() {
+                let popup = commands
+                    .spawn((
+                        ImageBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                left: Val::Px(position.x),
+                                top: Val::Px(position.y),
+                                width: Val::Px(width as f32 / scale_factor),
+                                height: Val::Px(height as f32 / scale_factor),
+                                ..default()
+                            },
+                            image: UiImage::new(handle.clone()),
+                            z_index: ZIndex::Global(i32::MAX),
+                            ..default()
+                        },
+                        AutocompletePopup(entity),
+                    ))
+                    .id();
+                autocomplete.popup = Some((popup, handle));
+                continue;
+            }
+        }
+
+        let Some((popup, _)) = autocomplete.popup.as_ref() else {
+            continue;
+        };
+        if let Ok((_, _, mut style)) = popup_q.get_mut(*popup) {
+            if style.left != Val::Px(position.x) || style.top != Val::Px(position.y) {
+                style.left = Val::Px(position.x);
+                style.top = Val::Px(position.y);
+            }
+            if let Some(popup_size) = popup_size {
+                style.width = Val::Px(popup_size.x);
+                style.height = Val::Px(popup_size.y);
+            }
+        }
+    }
+}
+
+/// Draws the candidates into an image, returning its pixels and physical size
+fn draw_completions(
+    autocomplete: &Autocomplete,
+    attrs: &AttrsOwned,
+    metrics: Metrics,
+    font_system: &mut FontSystem,
+    swash_cache: &mut cosmic_text::SwashCache,
+) -> (Vec<u8>, u32, u32) {
+    let mut buffer = Buffer::new(font_system, metrics);
+    buffer.set_size(font_system, 10000., 10000.);
+    buffer.set_text(
+        font_system,
+        &autocomplete.candidates.join("\n"),
+        attrs.as_attrs(),
+        Shaping::Advanced,
+    );
+    buffer.shape_until_scroll(font_system, false);
+
+    let pad = (metrics.font_size / 2.).ceil();
+    let text_width = buffer
+        .layout_runs()
+        .fold(0., |w: f32, run| w.max(run.line_w));
+    let width = (text_width + pad * 2.).ceil() as u32;
+    let height =
+        (autocomplete.candidates.len() as f32 * metrics.line_height + pad * 2.).ceil() as u32;
+
+    let mut pixels = vec![0; width as usize * height as usize * 4];
+    let bg = autocomplete.background_color;
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel[0] = (bg.r() * 255.) as u8;
+        pixel[1] = (bg.g() * 255.) as u8;
+        pixel[2] = (bg.b() * 255.) as u8;
+        pixel[3] = (bg.a() * 255.) as u8;
+    }
+
+    let selected_top = pad + autocomplete.selected as f32 * metrics.line_height;
+    for y in selected_top as i32..(selected_top + metrics.line_height) as i32 {
+        for x in 0..width as i32 {
+            draw_pixel(
+                &mut pixels,
+                width as i32,
+                height as i32,
+                x,
+                y,
+                autocomplete.selected_color.to_cosmic(),
+            );
+        }
+    }
+
+    let font_color = attrs.color_opt.unwrap_or(cosmic_text::Color::rgb(0, 0, 0));
+    buffer.draw(font_system, swash_cache, font_color, |x, y, w, h, color| {
+        for row in 0..h as i32 {
+            for col in 0..w as i32 {
+                draw_pixel(
+                    &mut pixels,
+                    width as i32,
+                    height as i32,
+                    x + col + pad as i32,
+                    y + row + pad as i32,
+                    color,
+                );
+            }
+        }
+    });
+
+    (pixels, width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmic_text::{Attrs, Buffer, Editor, Metrics};
+
+    #[test]
+    fn word_list_completes_word_at_cursor() {
+        assert_eq!(word_at("give sword_2 now", 8), (5, 12));
+        assert_eq!(word_at("give ", 5), (5, 5));
+
+        let words = WordList(vec!["Spawn".into(), "speed".into(), "give".into()]);
+        assert_eq!(words.complete("sp", 2), vec!["Spawn", "speed"]);
+        assert_eq!(words.complete("x give", 6), Vec::<String>::new());
+    }
+
+    fn app_with_completions(text: &str) -> (App, Entity) {
+        let mut font_system = create_cosmic_font_system(CosmicFontConfig {
+            load_system_fonts: false,
+            ..Default::default()
+        });
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(20., 20.));
+        buffer.set_text(&mut font_system, text, Attrs::new(), Shaping::Advanced);
+        let mut editor = CosmicEditor::new(Editor::new(buffer));
+        editor.set_cursor(Cursor::new(0, text.len()));
+
+        let words = WordList(vec!["spawn".into(), "speed".into(), "spin".into()]);
+        let mut app = App::new();
+        app.add_event::<CosmicTextChanged>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<CosmicKeymap>()
+            .insert_resource(CosmicFontSystem(font_system))
+            .add_systems(Update, (kb_autocomplete, update_completions).chain());
+        let entity = app.world.spawn((editor, Autocomplete::new(words))).id();
+        app.insert_resource(FocusedWidget(Some(entity)));
+
+        // Typing opened the popup
+        app.update();
+        (app, entity)
+    }
+
+    fn press(app: &mut App, key: KeyCode) {
+        let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+        keys.release_all();
+        keys.clear();
+        keys.press(key);
+        app.update();
+    }
+
+    fn autocomplete(app: &App, entity: Entity) -> &Autocomplete {
+        app.world.get::<Autocomplete>(entity).unwrap()
+    }
+
+    #[test]
+    fn selection_wraps_around() {
+        let (mut app, entity) = app_with_completions("sp");
+        assert_eq!(autocomplete(&app, entity).candidates().len(), 3);
+        assert_eq!(autocomplete(&app, entity).selected(), 0);
+
+        press(&mut app, KeyCode::ArrowUp);
+        assert_eq!(autocomplete(&app, entity).selected(), 2);
+        press(&mut app, KeyCode::ArrowDown);
+        assert_eq!(autocomplete(&app, entity).selected(), 0);
+        press(&mut app, KeyCode::ArrowDown);
+        assert_eq!(autocomplete(&app, entity).selected(), 1);
+    }
+
+    #[test]
+    fn accepting_replaces_the_word() {
+        let (mut app, entity) = app_with_completions("go sp");
+        press(&mut app, KeyCode::ArrowDown);
+        press(&mut app, KeyCode::Enter);
+
+        let editor = app.world.get::<CosmicEditor>(entity).unwrap();
+        assert_eq!(editor.with_buffer(|b| b.get_text()), "go speed");
+        assert_eq!(editor.cursor(), Cursor::new(0, 8));
+        assert!(!autocomplete(&app, entity).is_open());
+
+        // Not opened again until something else is typed
+        app.update();
+        assert!(!autocomplete(&app, entity).is_open());
+    }
+
+    #[test]
+    fn escape_dismisses_until_the_text_changes() {
+        let (mut app, entity) = app_with_completions("sp");
+        press(&mut app, KeyCode::Escape);
+        assert!(!autocomplete(&app, entity).is_open());
+
+        // Moving the cursor keeps it closed, typing opens it again
+        let mut editor = app.world.get_mut::<CosmicEditor>(entity).unwrap();
+        editor.set_cursor(Cursor::new(0, 1));
+        press(&mut app, KeyCode::KeyA);
+        assert!(!autocomplete(&app, entity).is_open());
+
+        let mut editor = app.world.get_mut::<CosmicEditor>(entity).unwrap();
+        editor.set_cursor(Cursor::new(0, 2));
+        editor.insert_string("e", None);
+        app.update();
+        assert_eq!(autocomplete(&app, entity).candidates(), ["speed"]);
+        let editor = app.world.get::<CosmicEditor>(entity).unwrap();
+        assert_eq!(editor.with_buffer(|b| b.get_text()), "spe");
+    }
+}
@@ -1,5 +1,7 @@
 use crate::*;
 use bevy::{prelude::*, window::PrimaryWindow};
+use cosmic_text::LayoutRun;
+use unicode_segmentation::UnicodeSegmentation;
 
 /// Set of all buffer setup functions. Runs in [`First`]
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
@@ -260,3 +262,78 @@ pub fn get_x_offset_center(widget_width: f32, buffer: &Buffer) -> i32 {
     let (text_width, _) = get_text_size(buffer);
     ((widget_width - text_width) / 2.0) as i32
 }
+
+/// Returns the position of `cursor` in buffer pixels, as the x position of the caret and the top
+/// of its layout line, or [`None`] if the cursor's line is not laid out
+pub fn get_cursor_position(buffer: &Buffer, cursor: Cursor) -> Option<(f32, f32)> {
+    buffer.layout_runs().find_map(|run| {
+        let x = cursor_x_in_run(&cursor, &run)?;
+        Some((x, run.line_top))
+    })
+}
+
+/// Caret x position of `cursor` inside a [`LayoutRun`], handling right-to-left glyphs
+pub(crate) fn cursor_x_in_run(cursor: &Cursor, run: &LayoutRun) -> Option<f32> {
+    if cursor.line != run.line_i {
+        return None;
+    }
+
+    // The cursor sits at the start of a glyph, or part way through a ligature cluster
+    for glyph in run.glyphs.iter() {
+        let offset = if cursor.index == glyph.start {
+            0.
+        } else if cursor.index > glyph.start && cursor.index < glyph.end {
+            let cluster = &run.text[glyph.start..glyph.end];
+            let total = cluster.graphemes(true).count();
+            let before = cluster
+                .grapheme_indices(true)
+                .filter(|(i, _)| glyph.start + i < cursor.index)
+                .count();
+            glyph.w * before as f32 / total as f32
+        } else {
+            continue;
+        };
+
+        return Some(if glyph.level.is_rtl() {
+            glyph.x + glyph.w - offset
+        } else {
+            glyph.x + offset
+        });
+    }
+
+    match run.glyphs.last() {
+        Some(glyph) if cursor.index == glyph.end => Some(if glyph.level.is_rtl() {
+            glyph.x
+        } else {
+            glyph.x + glyph.w
+        }),
+        Some(_) => None,
+        None => Some(0.),
+    }
+}
+
+/// Byte offset of `cursor` in the text returned by [`BufferExtras::get_text`]
+pub fn cursor_to_offset(buffer: &Buffer, cursor: Cursor) -> usize {
+    buffer
+        .lines
+        .iter()
+        .take(cursor.line)
+        .map(|line| line.text().len() + 1)
+        .sum::<usize>()
+        + cursor.index
+}
+
+/// [`Cursor`] at a byte offset in the text returned by [`BufferExtras::get_text`]
+pub fn offset_to_cursor(buffer: &Buffer, offset: usize) -> Cursor {
+    let mut remaining = offset;
+    for (i, line) in buffer.lines.iter().enumerate() {
+        let len = line.text().len();
+        if remaining <= len {
+            return Cursor::new(i, remaining);
+        }
+        remaining -= len + 1;
+    }
+
+    let last = buffer.lines.len().saturating_sub(1);
+    Cursor::new(last, buffer.lines.last().map_or(0, |l| l.text().len()))
+}
@@ -0,0 +1,572 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use crate::*;
+use bevy::{prelude::*, render::render_resource::Extent3d, window::PrimaryWindow};
+use cosmic_text::{AttrsList, Shaping};
+
+pub(crate) struct ContextMenuPlugin;
+
+impl Plugin for ContextMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MenuCommands>()
+            .add_systems(
+                PreUpdate,
+                context_menu_input.in_set(InputSet).before(input_mouse),
+            )
+            .add_systems(
+                Update,
+                (show_context_menu, clear_menu_commands).after(InputSet),
+            );
+    }
+}
+
+/// Commands chosen from a [`ContextMenu`], run by the input systems as if their keys were
+/// pressed
+#[derive(Resource, Default)]
+pub struct MenuCommands(pub(crate) Vec<(Entity, EditorCommand)>);
+
+impl MenuCommands {
+    /// Commands chosen from the menu of `entity`
+    pub(crate) fn of(&self, entity: Entity) -> impl Iterator<Item = EditorCommand> + '_ {
+        self.0
+            .iter()
+            .filter(move |(e, _)| *e == entity)
+            .map(|(_, command)| *command)
+    }
+}
+
+/// Entry of a [`ContextMenu`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MenuItem {
+    Cut,
+    Copy,
+    Paste,
+    Delete,
+    SelectAll,
+    /// Added with [`ContextMenu::with_entry`], sends [`CosmicContextMenuChosen`] with its `id`
+    Custom {
+        id: String,
+        label: String,
+    },
+}
+
+impl MenuItem {
+    pub fn label(&self) -> &str {
+        match self {
+            MenuItem::Cut => "Cut",
+            MenuItem::Copy => "Copy",
+            MenuItem::Paste => "Paste",
+            MenuItem::Delete => "Delete",
+            MenuItem::SelectAll => "Select All",
+            MenuItem::Custom { label, .. } => label,
+        }
+    }
+}
+
+/// Menu shown at the pointer
+struct OpenMenu {
+    /// Top left in logical window pixels
+    position: Vec2,
+    /// Items with whether they can be chosen, filled in once the widget is focused
+    items: Vec<(MenuItem, bool)>,
+    hovered: Option<usize>,
+    /// Logical size, padding and physical row height of the drawn menu
+    layout: Option<(Vec2, f32, f32)>,
+    popup: Option<(Entity, Handle<Image>)>,
+    dirty: bool,
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] to open a menu of edit
+/// commands on right click.
+///
+/// Cut, Copy, Paste and Delete are greyed out when they would do nothing, such as for
+/// [`ReadOnly`] widgets or without a selection. Entries added with [`ContextMenu::with_entry`]
+/// come after them and send [`CosmicContextMenuChosen`] when chosen.
+///
+/// ```
+/// # use bevy_cosmic_edit::*;
+/// let menu = ContextMenu::default().with_entry("upper", "Uppercase");
+/// ```
+#[derive(Component)]
+pub struct ContextMenu {
+    pub background_color: Color,
+    /// Background of the entry under the pointer
+    pub hover_color: Color,
+    /// Text color of entries that cannot be chosen
+    pub disabled_color: Color,
+    entries: Vec<MenuItem>,
+    open: Option<OpenMenu>,
+    /// The pointer press of this frame belongs to the menu
+    captured: bool,
+}
+
+impl Default for ContextMenu {
+    fn default() -> Self {
+        Self {
+            background_color: Color::rgb(0.97, 0.97, 0.97),
+            hover_color: Color::rgb(0.78, 0.86, 1.),
+            disabled_color: Color::rgb(0.6, 0.6, 0.6),
+            entries: Vec::new(),
+            open: None,
+            captured: false,
+        }
+    }
+}
+
+impl ContextMenu {
+    /// Adds an entry after the edit commands, chosen entries send [`CosmicContextMenuChosen`]
+    /// with `id`
+    pub fn with_entry(mut self, id: impl Into<String>, label: impl Into<String>) -> Self {
+        self.add_entry(id, label);
+        self
+    }
+
+    pub fn add_entry(&mut self, id: impl Into<String>, label: impl Into<String>) {
+        self.entries.push(MenuItem::Custom {
+            id: id.into(),
+            label: label.into(),
+        });
+    }
+
+    /// Removes the entries added with `id`
+    pub fn remove_entry(&mut self, id: &str) {
+        self.entries.retain(
+            |entry| !matches!(entry, MenuItem::Custom { id: entry_id, .. } if entry_id == id),
+        );
+    }
+
+    /// Whether the menu is shown
+    pub fn is_open(&self) -> bool {
+        self.open.is_some()
+    }
+
+    /// Whether the pointer press of this frame was used by the menu
+    pub(crate) fn captures_pointer(&self) -> bool {
+        self.captured
+    }
+}
+
+/// Entries with whether they can be chosen
+fn menu_items(
+    entries: &[MenuItem],
+    has_selection: bool,
+    readonly: bool,
+    masked: bool,
+    can_paste: bool,
+) -> Vec<(MenuItem, bool)> {
+    let editable = !readonly && !masked;
+    [
+        (MenuItem::Cut, editable && has_selection),
+        (MenuItem::Copy, has_selection),
+        (MenuItem::Paste, editable && can_paste),
+        (MenuItem::Delete, !readonly && has_selection),
+        (MenuItem::SelectAll, true),
+    ]
+    .into_iter()
+    .chain(entries.iter().map(|entry| (entry.clone(), true)))
+    .collect()
+}
+
+/// Whether there is text to paste
+fn clipboard_has_text() -> bool {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.get_text())
+            .is_ok_and(|text| !text.is_empty())
+    }
+
+    // Reading the clipboard asks for permission on the web
+    #[cfg(target_arch = "wasm32")]
+    true
+}
+
+/// Opens menus on right click and runs the chosen entries
+fn context_menu_input(
+    mut commands: Commands,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mut q: Query<(
+        Entity,
+        &mut ContextMenu,
+        Has<CosmicEditor>,
+        &GlobalTransform,
+        &Sprite,
+        &Visibility,
+    )>,
+    node_q: Query<(&Node, &GlobalTransform, &CosmicSource)>,
+    mut menu_commands: ResMut<MenuCommands>,
+    mut evw_chosen: EventWriter<CosmicContextMenuChosen>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Some((camera, camera_transform)) = camera_q.iter().find(|(c, _)| c.is_active) else {
+        return;
+    };
+    let pointer = window.cursor_position();
+    let scale_factor = window.scale_factor();
+
+    for (entity, mut menu, has_editor, sprite_transform, sprite, visibility) in q.iter_mut() {
+        menu.captured = false;
+
+        if let Some(open) = menu.open.as_mut() {
+            // Entry under the pointer
+            let hovered = match (pointer, open.layout) {
+                (Some(pointer), Some((size, pad, row_height))) => {
+                    let local = pointer - open.position;
+                    let inside =
+                        local.x >= 0. && local.y >= 0. && local.x < size.x && local.y < size.y;
+                    let row = ((local.y * scale_factor - pad) / row_height).floor();
+                    (inside && row >= 0.)
+                        .then_some(row as usize)
+                        .filter(|row| *row < open.items.len())
+                }
+                _ => None,
+            };
+            if open.hovered != hovered {
+                open.hovered = hovered;
+                open.dirty = true;
+            }
+
+            let chosen = hovered.and_then(|i| open.items.get(i)).cloned();
+            let pressed = buttons.any_just_pressed([MouseButton::Left, MouseButton::Right]);
+            if keys.just_pressed(KeyCode::Escape) || (pressed && hovered.is_none()) {
+                menu.open = None;
+                continue;
+            }
+            if !buttons.just_pressed(MouseButton::Left) {
+                continue;
+            }
+
+            menu.captured = true;
+            // Entries that are greyed out keep the menu open
+            let Some((item, true)) = chosen else {
+                continue;
+            };
+            menu.open = None;
+
+            if !has_editor {
+                continue;
+            }
+            let command = match item {
+                MenuItem::Cut => EditorCommand::Cut,
+                MenuItem::Copy => EditorCommand::Copy,
+                MenuItem::Paste => EditorCommand::Paste,
+                MenuItem::Delete => EditorCommand::Delete,
+                MenuItem::SelectAll => EditorCommand::SelectAll,
+                MenuItem::Custom { id, .. } => {
+                    evw_chosen.send(CosmicContextMenuChosen { entity, id });
+                    continue;
+                }
+            };
+            menu_commands.0.push((entity, command));
+            continue;
+        }
+
+        if !buttons.just_pressed(MouseButton::Right) || visibility == Visibility::Hidden {
+            continue;
+        }
+        let Some(pointer) = pointer else {
+            continue;
+        };
+
+        let mut is_ui_node = false;
+        let mut transform = sprite_transform;
+        let mut size = sprite.custom_size.unwrap_or(Vec2::ONE);
+        for (node, node_transform, source) in node_q.iter() {
+            if source.0 == entity {
+                is_ui_node = true;
+                transform = node_transform;
+                size = node.size();
+            }
+        }
+        let hit = get_node_cursor_pos(
+            window,
+            transform,
+            (size.x, size.y),
+            is_ui_node,
+            camera,
+            camera_transform,
+        );
+        if hit.is_none() {
+            continue;
+        }
+
+        // The menu acts on the focused editor
+        commands.insert_resource(FocusedWidget(Some(entity)));
+        menu.captured = true;
+        menu.open = Some(OpenMenu {
+            position: pointer,
+            items: Vec::new(),
+            hovered: None,
+            layout: None,
+            popup: None,
+            dirty: true,
+        });
+    }
+}
+
+/// Forgets the commands chosen for the focused widget once the input systems have run them
+fn clear_menu_commands(
+    active_editor: Res<FocusedWidget>,
+    editor_q: Query<(), With<CosmicEditor>>,
+    mut menu_commands: ResMut<MenuCommands>,
+) {
+    if let Some(entity) = active_editor.0.filter(|e| editor_q.contains(*e)) {
+        menu_commands.0.retain(|(e, _)| *e != entity);
+    }
+}
+
+/// Spawns, positions and draws the menus, and removes them once closed
+fn show_context_menu(
+    mut commands: Commands,
+    mut q: Query<(
+        Entity,
+        &mut ContextMenu,
+        Option<&CosmicEditor>,
+        &CosmicBuffer,
+        &DefaultAttrs,
+        Option<&ReadOnly>,
+        Option<&InputMask>,
+    )>,
+    mut popup_q: Query<(Entity, &ContextMenuPopup, &mut Style)>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut images: ResMut<Assets<Image>>,
+    mut font_system: ResMut<CosmicFontSystem>,
+    mut swash_cache_state: ResMut<SwashCacheState>,
+) {
+    // Popups of closed menus, or of widgets that are gone
+    for (popup, owner, _) in popup_q.iter() {
+        let open = q
+            .get(owner.0)
+            .ok()
+            .and_then(|(_, menu, ..)| menu.open.as_ref())
+            .and_then(|open| open.popup.as_ref())
+            .is_some_and(|(entity, _)| *entity == popup);
+        if !open {
+            commands.entity(popup).despawn_recursive();
+        }
+    }
+
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let scale_factor = window.scale_factor();
+
+    for (entity, mut menu, editor_opt, buffer, attrs, readonly_opt, mask_opt) in q.iter_mut() {
+        let entries = menu.entries.clone();
+        let disabled_color = menu.disabled_color;
+        let background_color = menu.background_color;
+        let hover_color = menu.hover_color;
+        let Some(open) = menu.open.as_mut() else {
+            continue;
+        };
+
+        if open.items.is_empty() {
+            // Waits for the editor of a widget focused by the right click
+            let Some(editor) = editor_opt else {
+                continue;
+            };
+            let has_selection = editor
+                .selection_bounds()
+                .is_some_and(|(start, end)| start != end);
+            let can_paste = readonly_opt.is_none() && clipboard_has_text();
+            open.items = menu_items(
+                &entries,
+                has_selection,
+                readonly_opt.is_some(),
+                mask_opt.is_some(),
+                can_paste,
+            );
+        }
+
+        if open.dirty || open.popup.is_none() {
+            open.dirty = false;
+            let (pixels, width, height, pad, row_height) = draw_context_menu(
+                open,
+                &attrs.0,
+                buffer.metrics(),
+                (background_color, hover_color, disabled_color),
+                &mut font_system.0,
+                &mut swash_cache_state.swash_cache,
+            );
+            let size = Vec2::new(width as f32, height as f32) / scale_factor;
+            open.layout = Some((size, pad, row_height));
+            // Kept inside the window
+            open.position = open
+                .position
+                .min(Vec2::new(window.width(), window.height()) - size)
+                .max(Vec2::ZERO);
+
+            let handle = match &open.popup {
+                Some((_, handle)) => handle.clone(),
+                None => images.add(Image::default()),
+            };
+            if let Some(image) = images.get_mut(&handle) {
+                image.data.clear();
+                image.data.extend_from_slice(&pixels);
+                image.resize(Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                });
+            }
+
+            match &open.popup {
+                Some((popup, _)) => {
+                    if let Ok((_, _, mut style)) = popup_q.get_mut(*popup) {
+                        style.left = Val::Px(open.position.x);
+                        style.top = Val::Px(open.position.y);
+                        style.width = Val::Px(size.x);
+                        style.height = Val::Px(size.y);
+                    }
+                }
+                None => {
+                    let popup = commands
+                        .spawn((
+                            ImageBundle {
+                                style: Style {
+                                    position_type: PositionType::Absolute,
+                                    left: Val::Px(open.position.x),
+                                    top: Val::Px(open.position.y),
+                                    width: Val::Px(size.x),
+                                    height: Val::Px(size.y),
+                                    ..default()
+                                },
+                                image: UiImage::new(handle.clone()),
+                                z_index: ZIndex::Global(i32::MAX),
+                                ..default()
+                            },
+                            ContextMenuPopup(entity),
+                        ))
+                        .id();
+                    open.popup = Some((popup, handle));
+                }
+            }
+        }
+    }
+}
+
+/// Marks the popup of the [`ContextMenu`] on the contained entity
+#[derive(Component)]
+pub struct ContextMenuPopup(pub Entity);
+
+/// Draws the entries into an image, returning its pixels, physical size, padding and row height
+fn draw_context_menu(
+    open: &OpenMenu,
+    attrs: &AttrsOwned,
+    metrics: Metrics,
+    (background, hover, disabled): (Color, Color, Color),
+    font_system: &mut FontSystem,
+    swash_cache: &mut cosmic_text::SwashCache,
+) -> (Vec<u8>, u32, u32, f32, f32) {
+    let labels: Vec<&str> = open.items.iter().map(|(item, _)| item.label()).collect();
+    let mut buffer = Buffer::new(font_system, metrics);
+    buffer.set_size(font_system, 10000., 10000.);
+    buffer.set_text(
+        font_system,
+        &labels.join("\n"),
+        attrs.as_attrs(),
+        Shaping::Advanced,
+    );
+    let disabled_attrs = attrs.as_attrs().color(disabled.to_cosmic());
+    for (line, (_, enabled)) in buffer.lines.iter_mut().zip(open.items.iter()) {
+        if !enabled {
+            line.set_attrs_list(AttrsList::new(disabled_attrs));
+        }
+    }
+    buffer.shape_until_scroll(font_system, false);
+
+    let pad = (metrics.font_size / 2.).ceil();
+    let text_width = buffer
+        .layout_runs()
+        .fold(0., |w: f32, run| w.max(run.line_w));
+    let width = (text_width + pad * 4.).ceil() as u32;
+    let height = (open.items.len() as f32 * metrics.line_height + pad * 2.).ceil() as u32;
+
+    let mut pixels = vec![0; width as usize * height as usize * 4];
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel[0] = (background.r() * 255.) as u8;
+        pixel[1] = (background.g() * 255.) as u8;
+        pixel[2] = (background.b() * 255.) as u8;
+        pixel[3] = (background.a() * 255.) as u8;
+    }
+
+    let hovered = open
+        .hovered
+        .filter(|i| open.items.get(*i).is_some_and(|(_, enabled)| *enabled));
+    if let Some(hovered) = hovered {
+        let top = pad + hovered as f32 * metrics.line_height;
+        for y in top as i32..(top + metrics.line_height) as i32 {
+            for x in 0..width as i32 {
+                draw_pixel(
+                    &mut pixels,
+                    width as i32,
+                    height as i32,
+                    x,
+                    y,
+                    hover.to_cosmic(),
+                );
+            }
+        }
+    }
+
+    let font_color = attrs.color_opt.unwrap_or(cosmic_text::Color::rgb(0, 0, 0));
+    buffer.draw(font_system, swash_cache, font_color, |x, y, w, h, color| {
+        for row in 0..h as i32 {
+            for col in 0..w as i32 {
+                draw_pixel(
+                    &mut pixels,
+                    width as i32,
+                    height as i32,
+                    x + col + (pad * 2.) as i32,
+                    y + row + pad as i32,
+                    color,
+                );
+            }
+        }
+    });
+
+    (pixels, width, height, pad, metrics.line_height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn greyed_out_items() {
+        let entries = vec![MenuItem::Custom {
+            id: "upper".into(),
+            label: "Uppercase".into(),
+        }];
+        let enabled = |items: Vec<(MenuItem, bool)>| -> Vec<bool> {
+            items.into_iter().map(|(_, enabled)| enabled).collect()
+        };
+
+        assert_eq!(
+            enabled(menu_items(&entries, true, false, false, true)),
+            vec![true, true, true, true, true, true]
+        );
+        // Read only, nothing selected
+        assert_eq!(
+            enabled(menu_items(&entries, false, true, false, true)),
+            vec![false, false, false, false, true, true]
+        );
+        // Masked fields copy but do not cut or paste from here
+        assert_eq!(
+            enabled(menu_items(&[], true, false, true, true)),
+            vec![false, true, false, true, true]
+        );
+
+        let mut menu = ContextMenu::default()
+            .with_entry("upper", "Uppercase")
+            .with_entry("lower", "Lowercase");
+        menu.remove_entry("upper");
+        assert_eq!(menu.entries.len(), 1);
+        assert_eq!(menu.entries[0].label(), "Lowercase");
+    }
+}
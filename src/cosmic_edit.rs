@@ -10,11 +10,53 @@ pub enum CosmicWrap {
 }
 
 /// Enum representing the text alignment in a cosmic [`Buffer`]
+///
+/// Variants without a vertical position center the text vertically. `padding` is kept between
+/// the text and the edges of the widget, use [`CosmicTextAlign::Aligned`] for different
+/// horizontal and vertical padding.
 #[derive(Clone, Component)]
 pub enum CosmicTextAlign {
-    Center { padding: i32 },
-    TopLeft { padding: i32 },
-    Left { padding: i32 },
+    Center {
+        padding: i32,
+    },
+    TopLeft {
+        padding: i32,
+    },
+    Left {
+        padding: i32,
+    },
+    Right {
+        padding: i32,
+    },
+    TopCenter {
+        padding: i32,
+    },
+    TopRight {
+        padding: i32,
+    },
+    BottomLeft {
+        padding: i32,
+    },
+    BottomCenter {
+        padding: i32,
+    },
+    BottomRight {
+        padding: i32,
+    },
+    /// Left for left-to-right paragraphs, right for right-to-left ones
+    Start {
+        padding: i32,
+    },
+    /// Right for left-to-right paragraphs, left for right-to-left ones
+    End {
+        padding: i32,
+    },
+    Aligned {
+        horizontal: HorizontalAlign,
+        vertical: VerticalAlign,
+        padding_x: i32,
+        padding_y: i32,
+    },
 }
 
 impl Default for CosmicTextAlign {
@@ -23,6 +65,68 @@ impl Default for CosmicTextAlign {
     }
 }
 
+impl CosmicTextAlign {
+    /// Horizontal and vertical alignment, and the padding kept to the widget edges
+    pub fn parts(&self) -> (HorizontalAlign, VerticalAlign, Vec2) {
+        use HorizontalAlign as H;
+        use VerticalAlign as V;
+
+        let (horizontal, vertical, padding) = match *self {
+            CosmicTextAlign::Center { padding } => (H::Center, V::Center, padding),
+            CosmicTextAlign::TopLeft { padding } => (H::Left, V::Top, padding),
+            CosmicTextAlign::Left { padding } => (H::Left, V::Center, padding),
+            CosmicTextAlign::Right { padding } => (H::Right, V::Center, padding),
+            CosmicTextAlign::TopCenter { padding } => (H::Center, V::Top, padding),
+            CosmicTextAlign::TopRight { padding } => (H::Right, V::Top, padding),
+            CosmicTextAlign::BottomLeft { padding } => (H::Left, V::Bottom, padding),
+            CosmicTextAlign::BottomCenter { padding } => (H::Center, V::Bottom, padding),
+            CosmicTextAlign::BottomRight { padding } => (H::Right, V::Bottom, padding),
+            CosmicTextAlign::Start { padding } => (H::Start, V::Center, padding),
+            CosmicTextAlign::End { padding } => (H::End, V::Center, padding),
+            CosmicTextAlign::Aligned {
+                horizontal,
+                vertical,
+                padding_x,
+                padding_y,
+            } => {
+                return (
+                    horizontal,
+                    vertical,
+                    Vec2::new(padding_x as f32, padding_y as f32),
+                )
+            }
+        };
+        (horizontal, vertical, Vec2::splat(padding as f32))
+    }
+
+    /// Padding kept between the text and the widget edges
+    pub fn padding(&self) -> Vec2 {
+        self.parts().2
+    }
+}
+
+/// Horizontal part of a [`CosmicTextAlign`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HorizontalAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+    /// Follows the direction of the paragraph
+    Start,
+    /// Opposite to the direction of the paragraph
+    End,
+}
+
+/// Vertical part of a [`CosmicTextAlign`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VerticalAlign {
+    #[default]
+    Top,
+    Center,
+    Bottom,
+}
+
 /// Tag component to disable writing to a [`CosmicBuffer`]
 // TODO: Code example
 #[derive(Component, Default)]
@@ -33,8 +137,15 @@ pub struct ReadOnly; // tag component
 pub struct XOffset {
     pub left: f32,
     pub width: f32,
+    /// Caret the offset last scrolled to, so scrolling by other means sticks until it moves
+    pub(crate) caret: Option<Cursor>,
 }
 
+/// Space kept between the caret and the edges of a [`CosmicWrap::InfiniteLine`] widget when it
+/// scrolls horizontally, in buffer pixels
+#[derive(Component, Clone, Copy, Debug, Default, Deref)]
+pub struct ScrollMargin(pub f32);
+
 /// Default text attributes to be used on a [`CosmicBuffer`]
 #[derive(Component, Deref, DerefMut)]
 pub struct DefaultAttrs(pub AttrsOwned);
@@ -192,6 +303,8 @@ pub struct CosmicEditBundle {
     pub padding: CosmicPadding,
     pub widget_size: CosmicWidgetSize,
     pub hover_cursor: HoverCursor,
+    // editing bits
+    pub history: EditHistory,
 }
 
 impl Default for CosmicEditBundle {
@@ -219,6 +332,7 @@ impl Default for CosmicEditBundle {
             padding: Default::default(),
             widget_size: Default::default(),
             hover_cursor: Default::default(),
+            history: Default::default(),
         }
     }
 }
@@ -234,6 +348,10 @@ pub struct CosmicEditor {
     pub editor: Editor<'static>,
     pub cursor_visible: bool,
     pub cursor_timer: Timer,
+    /// Extra cursors edited alongside the primary one
+    pub secondary_cursors: Vec<SecondaryCursor>,
+    /// Set by [`EditorCommand::SetMark`], motions extend the selection while active
+    pub mark_active: bool,
 }
 
 impl CosmicEditor {
@@ -242,6 +360,8 @@ impl CosmicEditor {
             editor,
             cursor_visible: true,
             cursor_timer: Timer::new(Duration::from_millis(530), TimerMode::Repeating),
+            secondary_cursors: Vec::new(),
+            mark_active: false,
         }
     }
 }
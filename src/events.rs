@@ -1,5 +1,8 @@
 // File for all events, meant for easy documentation
 
+use std::path::PathBuf;
+
+use crate::{FileRejection, SearchAction};
 use bevy::prelude::*;
 
 /// Registers internal events
@@ -7,7 +10,15 @@ pub(crate) struct EventsPlugin;
 
 impl Plugin for EventsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<CosmicTextChanged>();
+        app.add_event::<CosmicTextChanged>()
+            .add_event::<CosmicSearchAction>()
+            .add_event::<CosmicSearchOpened>()
+            .add_event::<CosmicSearchMatches>()
+            .add_event::<CosmicInputRejected>()
+            .add_event::<CosmicNumberChanged>()
+            .add_event::<CosmicContextMenuChosen>()
+            .add_event::<CosmicTextDropped>()
+            .add_event::<CosmicFileRejected>();
     }
 }
 
@@ -16,3 +27,73 @@ impl Plugin for EventsPlugin {
 /// Contains the entity on which the text was changed, and the new text as a [`String`]
 #[derive(Event, Debug)]
 pub struct CosmicTextChanged(pub (Entity, String));
+
+/// Send to act on the matches of the [`CosmicSearch`](crate::CosmicSearch) on `entity`
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CosmicSearchAction {
+    pub entity: Entity,
+    pub action: SearchAction,
+}
+
+/// Sent when Find or Replace is pressed in a focused widget, to open a search bar for `entity`
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CosmicSearchOpened {
+    pub entity: Entity,
+    /// Whether the replace field was asked for
+    pub replace: bool,
+}
+
+/// Sent when the matches of a [`CosmicSearch`](crate::CosmicSearch) change
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CosmicSearchMatches {
+    pub entity: Entity,
+    /// Number of matches
+    pub count: usize,
+    /// Index of the selected match
+    pub current: Option<usize>,
+}
+
+/// Sent when an [`InputFilter`](crate::InputFilter) rejects typed or pasted text
+#[derive(Event, Debug, Clone)]
+pub struct CosmicInputRejected {
+    pub entity: Entity,
+    /// The rejected characters
+    pub text: String,
+}
+
+/// Sent when the parsed value of a [`NumericField`](crate::NumericField) changes
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CosmicNumberChanged {
+    pub entity: Entity,
+    /// Whole for [`NumberKind::Integer`](crate::NumberKind::Integer) fields
+    pub value: f64,
+}
+
+/// Sent when an entry added with [`ContextMenu::with_entry`](crate::ContextMenu::with_entry) is
+/// chosen
+#[derive(Event, Debug, Clone)]
+pub struct CosmicContextMenuChosen {
+    pub entity: Entity,
+    /// Id the entry was added with
+    pub id: String,
+}
+
+/// Sent when text dragged with [`TextDrag`](crate::TextDrag) is dropped
+#[derive(Event, Debug, Clone)]
+pub struct CosmicTextDropped {
+    /// Widget the text was dragged from
+    pub source: Entity,
+    /// Widget the text was dropped into, the same as `source` for moves within a widget
+    pub target: Entity,
+    pub text: String,
+    /// Whether the text was removed from `source`, false when copied
+    pub moved: bool,
+}
+
+/// Sent when a file dropped onto a [`FileDrop`](crate::FileDrop) widget is not inserted
+#[derive(Event, Debug, Clone)]
+pub struct CosmicFileRejected {
+    pub entity: Entity,
+    pub path: PathBuf,
+    pub reason: FileRejection,
+}
@@ -0,0 +1,290 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use std::path::Path;
+
+use crate::*;
+use bevy::{prelude::*, window::PrimaryWindow};
+
+pub(crate) struct FileDropPlugin;
+
+impl Plugin for FileDropPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, drop_files.in_set(InputSet));
+    }
+}
+
+/// What is inserted for a file dropped into a [`FileDrop`] widget
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FileDropMode {
+    /// The text in the file
+    #[default]
+    Contents,
+    /// The path of the file
+    Path,
+}
+
+/// Why a dropped file was not inserted, see [`CosmicFileRejected`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileRejection {
+    /// The file is not UTF-8 text
+    NotText,
+    /// The file is bigger than [`FileDrop::max_size`]
+    TooLarge,
+    /// The file could not be read
+    Unreadable,
+    /// The text would go over the widget's [`MaxChars`] or [`MaxLines`]
+    ExceedsLimits,
+    /// The widget is [`ReadOnly`]
+    ReadOnly,
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] to insert files dropped onto
+/// the window into it.
+///
+/// Files go to the widget under the pointer, where they are dropped, or else to the focused
+/// widget at its cursor. Files that cannot be inserted are reported with
+/// [`CosmicFileRejected`].
+#[derive(Component, Clone, Debug)]
+pub struct FileDrop {
+    pub mode: FileDropMode,
+    /// Largest file in bytes whose contents are inserted, [`None`] for no limit
+    pub max_size: Option<u64>,
+}
+
+impl Default for FileDrop {
+    fn default() -> Self {
+        Self {
+            mode: FileDropMode::Contents,
+            max_size: Some(1024 * 1024),
+        }
+    }
+}
+
+impl FileDrop {
+    /// Inserts the paths of dropped files instead of their contents
+    pub fn paths() -> Self {
+        Self {
+            mode: FileDropMode::Path,
+            ..default()
+        }
+    }
+}
+
+/// Text of a file's bytes, with Windows line endings and a byte order mark removed
+fn text_from_bytes(bytes: Vec<u8>) -> Option<String> {
+    let text = String::from_utf8(bytes).ok()?;
+    // Valid UTF-8 can still be binary
+    if text.contains('\0') {
+        return None;
+    }
+    Some(text.trim_start_matches('\u{feff}').replace("\r\n", "\n"))
+}
+
+/// Text to insert for a dropped file
+fn dropped_text(path: &Path, file_drop: &FileDrop) -> Result<String, FileRejection> {
+    if file_drop.mode == FileDropMode::Path {
+        return Ok(path.display().to_string());
+    }
+    let len = std::fs::metadata(path)
+        .map_err(|_| FileRejection::Unreadable)?
+        .len();
+    if file_drop.max_size.is_some_and(|max| len > max) {
+        return Err(FileRejection::TooLarge);
+    }
+    let bytes = std::fs::read(path).map_err(|_| FileRejection::Unreadable)?;
+    text_from_bytes(bytes).ok_or(FileRejection::NotText)
+}
+
+/// Inserts dropped files into the widget under the pointer or the focused widget
+fn drop_files(
+    mut evr_drop: EventReader<FileDragAndDrop>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    active_editor: Res<FocusedWidget>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mut q: Query<(
+        Entity,
+        &FileDrop,
+        &mut CosmicBuffer,
+        Option<&mut CosmicEditor>,
+        (&GlobalTransform, &Sprite, &Visibility),
+        (&CosmicTextAlign, &CosmicWrap),
+        &XOffset,
+        Option<&LineNumbers>,
+        Option<&SmoothScroll>,
+        Option<&ReadOnly>,
+        (&MaxChars, &MaxLines),
+        Option<&mut EditHistory>,
+        (
+            Option<&InputFilter>,
+            Option<&mut Placeholder>,
+            &DefaultAttrs,
+        ),
+    )>,
+    node_q: Query<(&Node, &GlobalTransform, &CosmicSource)>,
+    mut font_system: ResMut<CosmicFontSystem>,
+    mut evw_rejected: EventWriter<CosmicFileRejected>,
+    mut evw_input_rejected: EventWriter<CosmicInputRejected>,
+    mut evw_changed: EventWriter<CosmicTextChanged>,
+) {
+    let paths: Vec<_> = evr_drop
+        .read()
+        .filter_map(|ev| match ev {
+            FileDragAndDrop::DroppedFile { path_buf, .. } => Some(path_buf.clone()),
+            _ => None,
+        })
+        .collect();
+    if paths.is_empty() {
+        return;
+    }
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let scale_factor = window.scale_factor();
+
+    // The widget under the pointer and the cursor there
+    let mut hovered = None;
+    let camera = camera_q.iter().find(|(c, _)| c.is_active);
+    if let (Some(pointer), Some(camera)) = (window.cursor_position(), camera) {
+        for (
+            entity,
+            _,
+            buffer,
+            editor_opt,
+            (sprite_transform, sprite, visibility),
+            (position, mode),
+            x_offset,
+            numbers_opt,
+            smooth_opt,
+            ..,
+        ) in q.iter()
+        {
+            if visibility == Visibility::Hidden {
+                continue;
+            }
+            let node = widget_node(entity, (sprite_transform, sprite), &node_q);
+            let hit = |b: &Buffer| {
+                pointer_hit(
+                    pointer,
+                    b,
+                    node,
+                    camera,
+                    scale_factor,
+                    position,
+                    mode,
+                    x_offset,
+                    numbers_opt,
+                    smooth_opt,
+                )
+                .map(|(_, cursor)| cursor)
+            };
+            let found = match editor_opt {
+                Some(editor) => editor.with_buffer(hit),
+                None => hit(buffer),
+            };
+            if let Some(cursor) = found {
+                hovered = Some((entity, Some(cursor)));
+                break;
+            }
+        }
+    }
+
+    // Without a widget under the pointer, files go to the focused one at its cursor
+    let focused = active_editor
+        .0
+        .filter(|entity| q.contains(*entity))
+        .map(|entity| (entity, None));
+    let Some((entity, mut drop_at)) = hovered.or(focused) else {
+        return;
+    };
+    let Ok((
+        _,
+        file_drop,
+        mut buffer,
+        mut editor_opt,
+        _,
+        _,
+        _,
+        _,
+        _,
+        readonly_opt,
+        (max_chars, max_lines),
+        mut history_opt,
+        (filter_opt, mut placeholder_opt, attrs),
+    )) = q.get_mut(entity)
+    else {
+        return;
+    };
+
+    for path in paths {
+        let inserted = match readonly_opt {
+            Some(_) => Err(FileRejection::ReadOnly),
+            None => dropped_text(&path, file_drop),
+        };
+        let text = match inserted {
+            Ok(text) => text,
+            Err(reason) => {
+                evw_rejected.send(CosmicFileRejected {
+                    entity,
+                    path,
+                    reason,
+                });
+                continue;
+            }
+        };
+
+        // Several files are inserted one after the other
+        let end = match insert_text(
+            entity,
+            &text,
+            drop_at,
+            &mut buffer,
+            editor_opt.as_deref_mut(),
+            history_opt.as_deref_mut(),
+            filter_opt,
+            placeholder_opt.as_deref_mut(),
+            attrs,
+            max_chars,
+            max_lines,
+            &mut font_system.0,
+            &mut evw_input_rejected,
+            &mut evw_changed,
+        ) {
+            Ok(end) => end,
+            Err(NotInserted::Empty) => continue,
+            Err(NotInserted::ExceedsLimits) => {
+                evw_rejected.send(CosmicFileRejected {
+                    entity,
+                    path,
+                    reason: FileRejection::ExceedsLimits,
+                });
+                continue;
+            }
+        };
+        drop_at = Some(end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropped_bytes() {
+        assert_eq!(
+            text_from_bytes(b"\xef\xbb\xbfone\r\ntwo".to_vec()),
+            Some("one\ntwo".into())
+        );
+        assert_eq!(text_from_bytes(vec![0xff, 0xfe, 0x41]), None);
+        assert_eq!(text_from_bytes(b"a\0b".to_vec()), None);
+
+        let path = Path::new("notes.txt");
+        assert_eq!(
+            dropped_text(path, &FileDrop::paths()),
+            Ok("notes.txt".into())
+        );
+        assert_eq!(
+            dropped_text(Path::new("missing/notes.txt"), &FileDrop::default()),
+            Err(FileRejection::Unreadable)
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use crate::*;
+use bevy::prelude::*;
+use regex::Regex;
+use std::sync::Arc;
+
+/// Characters accepted by an [`InputFilter`]
+#[derive(Clone)]
+pub enum CharFilter {
+    /// `0` to `9`
+    Digits,
+    /// `0` to `9`, `a` to `f` and `A` to `F`
+    Hex,
+    Ascii,
+    /// Characters the function returns `true` for
+    Predicate(Arc<dyn Fn(char) -> bool + Send + Sync>),
+    /// Characters matched as a whole by the expression, such as `[a-z_]`
+    Regex(Regex),
+}
+
+impl CharFilter {
+    pub fn accepts(&self, c: char) -> bool {
+        match self {
+            CharFilter::Digits => c.is_ascii_digit(),
+            CharFilter::Hex => c.is_ascii_hexdigit(),
+            CharFilter::Ascii => c.is_ascii(),
+            CharFilter::Predicate(f) => f(c),
+            CharFilter::Regex(regex) => {
+                let mut buf = [0; 4];
+                let s = c.encode_utf8(&mut buf);
+                regex
+                    .find(s)
+                    .is_some_and(|m| m.start() == 0 && m.end() == s.len())
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for CharFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CharFilter::Digits => write!(f, "Digits"),
+            CharFilter::Hex => write!(f, "Hex"),
+            CharFilter::Ascii => write!(f, "Ascii"),
+            CharFilter::Predicate(_) => write!(f, "Predicate"),
+            CharFilter::Regex(regex) => write!(f, "Regex({})", regex.as_str()),
+        }
+    }
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] to restrict what can be typed,
+/// pasted or committed by an input method.
+///
+/// Each inserted character is first passed through `transform`, then checked against `filter`.
+/// Rejected characters are reported with a [`CosmicInputRejected`] event.
+///
+/// ```
+/// # use bevy_cosmic_edit::*;
+/// // Upper case hex digits only
+/// let filter = InputFilter::hex().with_transform(|c| c.to_ascii_uppercase());
+/// ```
+#[derive(Component, Clone, Debug)]
+pub struct InputFilter {
+    pub filter: CharFilter,
+    /// Applied to every character before it is checked
+    pub transform: Option<CharTransform>,
+    /// Insert the accepted part of a paste containing rejected characters, instead of rejecting
+    /// it entirely
+    pub keep_accepted: bool,
+}
+
+/// Function changing each inserted character, see [`InputFilter::transform`]
+#[derive(Clone)]
+pub struct CharTransform(pub Arc<dyn Fn(char) -> char + Send + Sync>);
+
+impl std::fmt::Debug for CharTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CharTransform")
+    }
+}
+
+impl InputFilter {
+    pub fn new(filter: CharFilter) -> Self {
+        Self {
+            filter,
+            transform: None,
+            keep_accepted: true,
+        }
+    }
+
+    pub fn digits() -> Self {
+        Self::new(CharFilter::Digits)
+    }
+
+    pub fn hex() -> Self {
+        Self::new(CharFilter::Hex)
+    }
+
+    pub fn ascii() -> Self {
+        Self::new(CharFilter::Ascii)
+    }
+
+    pub fn predicate(f: impl Fn(char) -> bool + Send + Sync + 'static) -> Self {
+        Self::new(CharFilter::Predicate(Arc::new(f)))
+    }
+
+    /// Accepts characters matched as a whole by `pattern`, such as `[a-z_]`
+    pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(Self::new(CharFilter::Regex(Regex::new(pattern)?)))
+    }
+
+    pub fn with_transform(mut self, f: impl Fn(char) -> char + Send + Sync + 'static) -> Self {
+        self.transform = Some(CharTransform(Arc::new(f)));
+        self
+    }
+
+    /// Rejects a whole paste if any of its characters is rejected
+    pub fn reject_whole(mut self) -> Self {
+        self.keep_accepted = false;
+        self
+    }
+
+    /// Splits `text` into the transformed text to insert and the rejected characters. Control
+    /// characters other than line breaks and tabs are dropped.
+    pub fn apply(&self, text: &str) -> (String, String) {
+        let mut accepted = String::new();
+        let mut rejected = String::new();
+        for c in text.chars() {
+            // Keys like Escape and Delete also send characters, which are never inserted
+            if c.is_control() && c != '\n' && c != '\t' {
+                continue;
+            }
+            let c = match &self.transform {
+                Some(transform) => transform.0(c),
+                None => c,
+            };
+            if self.filter.accepts(c) {
+                accepted.push(c);
+            } else {
+                rejected.push(c);
+            }
+        }
+
+        if !self.keep_accepted && !rejected.is_empty() {
+            return (String::new(), text.to_string());
+        }
+        (accepted, rejected)
+    }
+}
+
+/// Text of an insertion that passes the [`InputFilter`] of `entity`, if it has one
+pub(crate) fn filter_input(
+    filter_opt: Option<&InputFilter>,
+    entity: Entity,
+    text: &str,
+    evw_rejected: &mut EventWriter<CosmicInputRejected>,
+) -> String {
+    let Some(filter) = filter_opt else {
+        return text.to_string();
+    };
+
+    let (accepted, rejected) = filter.apply(text);
+    if !rejected.is_empty() {
+        evw_rejected.send(CosmicInputRejected {
+            entity,
+            text: rejected,
+        });
+    }
+    accepted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn char_filters() {
+        assert!(CharFilter::Digits.accepts('7'));
+        assert!(!CharFilter::Digits.accepts('a'));
+        assert!(CharFilter::Hex.accepts('F'));
+        assert!(!CharFilter::Hex.accepts('g'));
+        assert!(CharFilter::Ascii.accepts('~'));
+        assert!(!CharFilter::Ascii.accepts('é'));
+        assert!(CharFilter::Predicate(Arc::new(|c| c != 'x')).accepts('y'));
+        assert!(!CharFilter::Predicate(Arc::new(|c| c != 'x')).accepts('x'));
+
+        // The whole character has to match
+        let regex = CharFilter::Regex(Regex::new("[a-z_]").unwrap());
+        assert!(regex.accepts('_'));
+        assert!(!regex.accepts('A'));
+        assert!(!CharFilter::Regex(Regex::new("ab").unwrap()).accepts('a'));
+    }
+
+    #[test]
+    fn apply_transforms_then_filters() {
+        let filter = InputFilter::hex().with_transform(|c| c.to_ascii_uppercase());
+        assert_eq!(filter.apply("a1g"), ("A1".into(), "G".into()));
+        assert_eq!(filter.apply("\n"), ("".into(), "\n".into()));
+
+        let filter = InputFilter::digits().reject_whole();
+        assert_eq!(filter.apply("12"), ("12".into(), "".into()));
+        assert_eq!(filter.apply("1a2"), ("".into(), "1a2".into()));
+    }
+
+    #[test]
+    fn apply_drops_control_characters() {
+        let filter = InputFilter::digits();
+        assert_eq!(filter.apply("\u{1b}"), ("".into(), "".into()));
+        assert_eq!(filter.apply("1\u{7f}2"), ("12".into(), "".into()));
+        assert_eq!(filter.apply("\t"), ("".into(), "\t".into()));
+        assert_eq!(
+            InputFilter::ascii().apply("a\tb\n"),
+            ("a\tb\n".into(), "".into())
+        );
+    }
+}
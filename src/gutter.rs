@@ -0,0 +1,255 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use crate::*;
+use bevy::prelude::*;
+use cosmic_text::{Align, AttrsList, Buffer, SwashCache};
+
+pub(crate) struct GutterPlugin;
+
+impl Plugin for GutterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            update_gutter_width
+                .in_set(WidgetSet)
+                .after(set_widget_size)
+                .before(set_buffer_size),
+        );
+    }
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] to show line numbers in a
+/// gutter on the left of the text.
+///
+/// Numbers are drawn next to the first layout run of each line, so they follow wrapping and
+/// scrolling. The gutter is as wide as the largest number, and clicking a number selects its
+/// line.
+#[derive(Component, Clone)]
+pub struct LineNumbers {
+    /// Show the distance to the cursor's line instead of the line number, except on the cursor's
+    /// line
+    pub relative: bool,
+    pub color: Color,
+    /// Color of the cursor's line number while focused
+    pub current_color: Color,
+    pub background_color: Color,
+    /// Space on both sides of the numbers, in buffer pixels
+    pub spacing: f32,
+    /// Digits the gutter always has room for
+    pub min_digits: usize,
+    width: f32,
+    /// Digits and metrics `width` was measured for
+    measured: Option<(usize, Metrics)>,
+    /// Numbers as they were last shaped, with the index of the highlighted one
+    labels: Option<(Vec<String>, Option<usize>)>,
+    buffer: Option<Buffer>,
+}
+
+impl Default for LineNumbers {
+    fn default() -> Self {
+        Self {
+            relative: false,
+            color: Color::GRAY,
+            current_color: Color::BLACK,
+            background_color: Color::rgb(0.93, 0.93, 0.93),
+            spacing: 6.,
+            min_digits: 2,
+            width: 0.,
+            measured: None,
+            labels: None,
+            buffer: None,
+        }
+    }
+}
+
+impl LineNumbers {
+    /// Absolute line numbers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Distances to the cursor's line
+    pub fn relative() -> Self {
+        Self {
+            relative: true,
+            ..default()
+        }
+    }
+
+    /// Width of the gutter in buffer pixels
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    fn label(&self, line: usize, current: Option<usize>) -> String {
+        match current {
+            Some(current) if self.relative && current != line => current.abs_diff(line),
+            _ => line + 1,
+        }
+        .to_string()
+    }
+}
+
+/// Width of the gutter of a widget, zero without [`LineNumbers`]
+pub(crate) fn gutter_width(numbers_opt: Option<&LineNumbers>) -> f32 {
+    numbers_opt.map_or(0., |numbers| numbers.width)
+}
+
+/// Line shown under the gutter at `y` buffer pixels from the top of the text
+pub(crate) fn gutter_line_at(buffer: &Buffer, y: f32) -> Option<usize> {
+    let line_height = buffer.metrics().line_height;
+    buffer
+        .layout_runs()
+        .find(|run| run.line_top <= y && y < run.line_top + line_height)
+        .map(|run| run.line_i)
+}
+
+/// Measures the gutter for the number of digits of the last line
+fn update_gutter_width(
+    mut q: Query<(
+        &mut LineNumbers,
+        &CosmicBuffer,
+        Option<&CosmicEditor>,
+        &DefaultAttrs,
+    )>,
+    mut font_system: ResMut<CosmicFontSystem>,
+) {
+    for (mut numbers, buffer, editor_opt, attrs) in q.iter_mut() {
+        let (line_count, metrics) = match editor_opt {
+            Some(editor) => editor.with_buffer(|b| (b.lines.len(), b.metrics())),
+            None => (buffer.lines.len(), buffer.metrics()),
+        };
+        let digits = line_count.to_string().len().max(numbers.min_digits);
+        if numbers.measured == Some((digits, metrics)) {
+            continue;
+        }
+
+        let mut measure = Buffer::new(&mut font_system.0, metrics);
+        measure.set_size(&mut font_system.0, f32::MAX, f32::MAX);
+        measure.set_text(
+            &mut font_system.0,
+            &"0".repeat(digits),
+            attrs.as_attrs(),
+            Shaping::Advanced,
+        );
+        measure.shape_until_scroll(&mut font_system.0, false);
+        let text_width = measure
+            .layout_runs()
+            .map(|run| run.line_w)
+            .fold(0., f32::max);
+
+        let cache = numbers.bypass_change_detection();
+        cache.measured = Some((digits, metrics));
+        cache.labels = None;
+        // A new width lays the text out again
+        let width = (text_width + numbers.spacing * 2.).ceil();
+        if numbers.width != width {
+            numbers.width = width;
+            numbers.buffer = None;
+        }
+    }
+}
+
+/// Draws the gutter over the left edge of the widget, `current_line` is the cursor's line while
+/// focused
+pub(crate) fn draw_line_numbers(
+    numbers: &mut LineNumbers,
+    buffer: &Buffer,
+    current_line: Option<usize>,
+    attrs: &AttrsOwned,
+    font_system: &mut FontSystem,
+    swash_cache: &mut SwashCache,
+    pixels: &mut [u8],
+    size: Vec2,
+    padding_y: f32,
+) {
+    let (width, height) = (size.x as i32, size.y as i32);
+
+    // Background, covering text scrolled under the gutter
+    let background = numbers.background_color.to_cosmic();
+    for y in 0..height {
+        for x in 0..(numbers.width as i32).min(width) {
+            draw_pixel(pixels, width, height, x, y, background);
+        }
+    }
+
+    // One gutter line per layout run, only the first run of each line is numbered
+    let mut labels = Vec::new();
+    let mut highlighted = None;
+    let mut last_line = None;
+    for run in buffer.layout_runs() {
+        if last_line == Some(run.line_i) {
+            labels.push(String::new());
+            continue;
+        }
+        last_line = Some(run.line_i);
+        if current_line == Some(run.line_i) {
+            highlighted = Some(labels.len());
+        }
+        labels.push(numbers.label(run.line_i, current_line));
+    }
+
+    let metrics = buffer.metrics();
+    let gutter = numbers
+        .buffer
+        .get_or_insert_with(|| Buffer::new(font_system, metrics));
+    if numbers.labels.as_ref() != Some(&(labels.clone(), highlighted)) {
+        let number_attrs = attrs.as_attrs().color(numbers.color.to_cosmic());
+        gutter.set_metrics(font_system, metrics);
+        gutter.set_size(
+            font_system,
+            (numbers.width - numbers.spacing).max(0.),
+            f32::MAX,
+        );
+        gutter.set_text(
+            font_system,
+            &labels.join("\n"),
+            number_attrs,
+            Shaping::Advanced,
+        );
+        for (i, line) in gutter.lines.iter_mut().enumerate() {
+            line.set_align(Some(Align::Right));
+            if highlighted == Some(i) {
+                line.set_attrs_list(AttrsList::new(
+                    number_attrs.color(numbers.current_color.to_cosmic()),
+                ));
+            }
+        }
+        gutter.shape_until_scroll(font_system, false);
+        numbers.labels = Some((labels, highlighted));
+    }
+
+    let color = numbers.color.to_cosmic();
+    gutter.draw(font_system, swash_cache, color, |x, y, w, h, color| {
+        for row in 0..h as i32 {
+            for col in 0..w as i32 {
+                draw_pixel(
+                    pixels,
+                    width,
+                    height,
+                    x + col,
+                    y + row + padding_y as i32,
+                    color,
+                );
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels() {
+        let numbers = LineNumbers::new();
+        assert_eq!(numbers.label(0, None), "1");
+        assert_eq!(numbers.label(9, Some(3)), "10");
+
+        let numbers = LineNumbers::relative();
+        assert_eq!(numbers.label(9, Some(3)), "6");
+        assert_eq!(numbers.label(1, Some(3)), "2");
+        assert_eq!(numbers.label(3, Some(3)), "4");
+        assert_eq!(numbers.label(3, None), "4");
+    }
+}
@@ -0,0 +1,618 @@
+#![allow(clippy::type_complexity)]
+
+use crate::*;
+use bevy::prelude::*;
+use cosmic_text::{AttrsList, BufferLine};
+use std::ops::Range;
+
+pub(crate) struct HighlightPlugin;
+
+impl Plugin for HighlightPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            highlight_syntax
+                .after(InputSet)
+                .after(HistorySet)
+                .before(WidgetSet),
+        );
+    }
+}
+
+/// Colors a line of text for a [`SyntaxHighlight`]
+///
+/// ```
+/// # use bevy_cosmic_edit::*;
+/// # use std::ops::Range;
+/// /// Colors every `TODO` red
+/// struct Todos;
+///
+/// impl SyntaxHighlighter for Todos {
+///     type State = ();
+///
+///     fn highlight_line(
+///         &self,
+///         line: &str,
+///         _state: &mut (),
+///         attrs: &AttrsOwned,
+///     ) -> Vec<(Range<usize>, AttrsOwned)> {
+///         let red = AttrsOwned::new(attrs.as_attrs().color(CosmicColor::rgb(200, 0, 0)));
+///         line.match_indices("TODO")
+///             .map(|(i, m)| (i..i + m.len(), red.clone()))
+///             .collect()
+///     }
+/// }
+/// ```
+pub trait SyntaxHighlighter: Send + Sync + 'static {
+    /// State carried from the end of a line to the start of the next, such as being inside a
+    /// block comment
+    type State: Clone + Default + PartialEq + Send + Sync + 'static;
+
+    /// Byte ranges of `line` with the attributes to draw them with, the rest of the line uses
+    /// `attrs`. `state` is the state at the start of the line and has to be left as it is at the
+    /// end of it.
+    fn highlight_line(
+        &self,
+        line: &str,
+        state: &mut Self::State,
+        attrs: &AttrsOwned,
+    ) -> Vec<(Range<usize>, AttrsOwned)>;
+}
+
+/// Line as it was last highlighted
+struct HighlightedLine<S> {
+    text: String,
+    attrs: AttrsList,
+    start: S,
+    end: S,
+}
+
+/// [`SyntaxHighlighter`] with the lines it highlighted, without its state type
+trait LineHighlighter: Send + Sync + 'static {
+    /// Highlights lines that changed since the last call, returns whether any were
+    fn update(&mut self, lines: &mut [BufferLine], attrs: &AttrsOwned) -> bool;
+}
+
+struct Highlighted<H: SyntaxHighlighter> {
+    highlighter: H,
+    lines: Vec<HighlightedLine<H::State>>,
+}
+
+impl<H: SyntaxHighlighter> LineHighlighter for Highlighted<H> {
+    fn update(&mut self, lines: &mut [BufferLine], attrs: &AttrsOwned) -> bool {
+        let unchanged = |line: &BufferLine, cached: &HighlightedLine<H::State>| {
+            line.text() == cached.text && *line.attrs_list() == cached.attrs
+        };
+
+        let old = std::mem::take(&mut self.lines);
+        let prefix = lines
+            .iter()
+            .zip(old.iter())
+            .take_while(|(line, cached)| unchanged(line, cached))
+            .count();
+        if prefix == lines.len() && prefix == old.len() {
+            self.lines = old;
+            return false;
+        }
+        let suffix = lines
+            .iter()
+            .rev()
+            .zip(old.iter().rev())
+            .take(lines.len().min(old.len()) - prefix)
+            .take_while(|(line, cached)| unchanged(line, cached))
+            .count();
+        let changed_end = lines.len() - suffix;
+
+        // Lines replaced by the edit are dropped, the lines after it keep their cache
+        let replaced = old.len() - prefix - suffix;
+        let mut old = old.into_iter();
+        let mut highlighted: Vec<_> = old.by_ref().take(prefix).collect();
+        let mut old_suffix = old.skip(replaced);
+
+        let mut state = highlighted
+            .last()
+            .map_or_else(H::State::default, |line| line.end.clone());
+        for (i, line) in lines.iter_mut().enumerate().skip(prefix) {
+            if i >= changed_end {
+                // Lines after the edit only change if they start in a different state
+                let cached = old_suffix.next();
+                if let Some(cached) = cached {
+                    if cached.start == state {
+                        highlighted.push(cached);
+                        highlighted.extend(old_suffix);
+                        break;
+                    }
+                }
+            }
+
+            let start = state.clone();
+            let text = line.text();
+            let mut attrs_list = AttrsList::new(attrs.as_attrs());
+            for (range, span_attrs) in self.highlighter.highlight_line(text, &mut state, attrs) {
+                if range.start < range.end
+                    && range.end <= text.len()
+                    && text.is_char_boundary(range.start)
+                    && text.is_char_boundary(range.end)
+                {
+                    attrs_list.add_span(range, span_attrs.as_attrs());
+                }
+            }
+            highlighted.push(HighlightedLine {
+                text: text.to_string(),
+                attrs: attrs_list.clone(),
+                start,
+                end: state.clone(),
+            });
+            line.set_attrs_list(attrs_list);
+        }
+
+        self.lines = highlighted;
+        true
+    }
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] to color its text with a
+/// [`SyntaxHighlighter`].
+///
+/// Lines are highlighted again after they are edited, and so are the lines after them as long as
+/// the edit changed the state they start in. Highlighting is skipped while a [`Placeholder`] is
+/// shown and for [`Password`] fields.
+///
+/// ```
+/// # use bevy_cosmic_edit::*;
+/// let highlight = SyntaxHighlight::new(RonHighlighter::default());
+/// ```
+#[derive(Component)]
+pub struct SyntaxHighlight(Box<dyn LineHighlighter>);
+
+impl SyntaxHighlight {
+    pub fn new<H: SyntaxHighlighter>(highlighter: H) -> Self {
+        Self(Box::new(Highlighted {
+            highlighter,
+            lines: Vec::new(),
+        }))
+    }
+
+    /// Highlights RON with the default [`HighlightTheme`]
+    pub fn ron() -> Self {
+        Self::new(RonHighlighter::default())
+    }
+
+    /// Highlights JSON with the default [`HighlightTheme`]
+    pub fn json() -> Self {
+        Self::new(JsonHighlighter::default())
+    }
+}
+
+/// Colors used by [`RonHighlighter`] and [`JsonHighlighter`]
+#[derive(Clone, Debug)]
+pub struct HighlightTheme {
+    pub string: CosmicColor,
+    pub number: CosmicColor,
+    /// `true`, `false`, `null`, `Some` and `None`
+    pub keyword: CosmicColor,
+    pub comment: CosmicColor,
+    /// Object keys and struct field names
+    pub key: CosmicColor,
+    /// Struct and enum variant names
+    pub type_name: CosmicColor,
+    pub punctuation: CosmicColor,
+}
+
+impl Default for HighlightTheme {
+    fn default() -> Self {
+        Self {
+            string: CosmicColor::rgb(0xa3, 0x15, 0x15),
+            number: CosmicColor::rgb(0x09, 0x86, 0x58),
+            keyword: CosmicColor::rgb(0x00, 0x00, 0xff),
+            comment: CosmicColor::rgb(0x00, 0x80, 0x00),
+            key: CosmicColor::rgb(0x00, 0x10, 0x80),
+            type_name: CosmicColor::rgb(0x26, 0x7f, 0x99),
+            punctuation: CosmicColor::rgb(0x55, 0x55, 0x55),
+        }
+    }
+}
+
+/// State of [`RonHighlighter`] and [`JsonHighlighter`] between lines
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LexState {
+    #[default]
+    Code,
+    /// Inside block comments nested this deep
+    BlockComment(u32),
+    /// Inside a string
+    String,
+    /// Inside a raw string closed by a quote and this many `#`
+    RawString(usize),
+}
+
+/// Reference [`SyntaxHighlighter`] for RON, with nested block comments and multi-line strings
+#[derive(Clone, Debug, Default)]
+pub struct RonHighlighter {
+    pub theme: HighlightTheme,
+}
+
+impl SyntaxHighlighter for RonHighlighter {
+    type State = LexState;
+
+    fn highlight_line(
+        &self,
+        line: &str,
+        state: &mut LexState,
+        attrs: &AttrsOwned,
+    ) -> Vec<(Range<usize>, AttrsOwned)> {
+        highlight_code(line, state, &self.theme, attrs, true)
+    }
+}
+
+/// Reference [`SyntaxHighlighter`] for JSON
+#[derive(Clone, Debug, Default)]
+pub struct JsonHighlighter {
+    pub theme: HighlightTheme,
+}
+
+impl SyntaxHighlighter for JsonHighlighter {
+    type State = LexState;
+
+    fn highlight_line(
+        &self,
+        line: &str,
+        state: &mut LexState,
+        attrs: &AttrsOwned,
+    ) -> Vec<(Range<usize>, AttrsOwned)> {
+        highlight_code(line, state, &self.theme, attrs, false)
+    }
+}
+
+/// Shared lexer of the reference highlighters. Tokens start and end at ASCII characters, so
+/// ranges always fall on character boundaries.
+fn highlight_code(
+    line: &str,
+    state: &mut LexState,
+    theme: &HighlightTheme,
+    attrs: &AttrsOwned,
+    ron: bool,
+) -> Vec<(Range<usize>, AttrsOwned)> {
+    let bytes = line.as_bytes();
+    let len = bytes.len();
+    let mut spans = Vec::new();
+    let mut push = |range: Range<usize>, color: CosmicColor| {
+        if range.start < range.end {
+            spans.push((range, AttrsOwned::new(attrs.as_attrs().color(color))));
+        }
+    };
+
+    let mut i = 0;
+    while i < len {
+        let start = i;
+        match *state {
+            LexState::BlockComment(depth) => {
+                let (end, depth) = scan_block_comment(bytes, i, depth);
+                *state = match depth {
+                    0 => LexState::Code,
+                    depth => LexState::BlockComment(depth),
+                };
+                push(start..end, theme.comment);
+                i = end;
+                continue;
+            }
+            LexState::String => {
+                let (end, closed) = scan_string(bytes, i, b'"');
+                if closed {
+                    *state = LexState::Code;
+                }
+                push(start..end, theme.string);
+                i = end;
+                continue;
+            }
+            LexState::RawString(hashes) => {
+                let (end, closed) = scan_raw_string(bytes, i, hashes);
+                if closed {
+                    *state = LexState::Code;
+                }
+                push(start..end, theme.string);
+                i = end;
+                continue;
+            }
+            LexState::Code => {}
+        }
+
+        let next = bytes.get(i + 1).copied();
+        match bytes[i] {
+            b'/' if ron && next == Some(b'/') => {
+                push(i..len, theme.comment);
+                i = len;
+            }
+            b'/' if ron && next == Some(b'*') => {
+                let (end, depth) = scan_block_comment(bytes, i + 2, 1);
+                if depth > 0 {
+                    *state = LexState::BlockComment(depth);
+                }
+                push(start..end, theme.comment);
+                i = end;
+            }
+            b'"' => {
+                let (end, closed) = scan_string(bytes, i + 1, b'"');
+                if !closed && ron {
+                    *state = LexState::String;
+                }
+                let is_key = !ron && line[end..].trim_start().starts_with(':');
+                push(start..end, if is_key { theme.key } else { theme.string });
+                i = end;
+            }
+            b'\'' if ron => {
+                let (end, _) = scan_string(bytes, i + 1, b'\'');
+                push(start..end, theme.string);
+                i = end;
+            }
+            b'r' if ron && raw_string_hashes(&bytes[i + 1..]).is_some() => {
+                let hashes = raw_string_hashes(&bytes[i + 1..]).unwrap_or_default();
+                let (end, closed) = scan_raw_string(bytes, i + hashes + 2, hashes);
+                if !closed {
+                    *state = LexState::RawString(hashes);
+                }
+                push(start..end, theme.string);
+                i = end;
+            }
+            b'0'..=b'9' => {
+                i = scan_number(bytes, i);
+                push(start..i, theme.number);
+            }
+            b'-' | b'+' if next.is_some_and(|b| b.is_ascii_digit()) => {
+                i = scan_number(bytes, i + 1);
+                push(start..i, theme.number);
+            }
+            b if b.is_ascii_alphabetic() || b == b'_' => {
+                while i < len && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                let word = &line[start..i];
+                let rest = line[i..].trim_start();
+                let color = match word {
+                    "true" | "false" => Some(theme.keyword),
+                    "null" if !ron => Some(theme.keyword),
+                    "Some" | "None" if ron => Some(theme.keyword),
+                    _ if !ron => None,
+                    _ if rest.starts_with(':') && !rest.starts_with("::") => Some(theme.key),
+                    _ if word.starts_with(|c: char| c.is_ascii_uppercase()) => {
+                        Some(theme.type_name)
+                    }
+                    _ => None,
+                };
+                if let Some(color) = color {
+                    push(start..i, color);
+                }
+            }
+            b'{' | b'}' | b'[' | b']' | b'(' | b')' | b',' | b':' => {
+                i += 1;
+                push(start..i, theme.punctuation);
+            }
+            _ => i += 1,
+        }
+    }
+
+    spans
+}
+
+/// End of a block comment starting at `i` inside `depth` comments, and the depth left open
+fn scan_block_comment(bytes: &[u8], mut i: usize, mut depth: u32) -> (usize, u32) {
+    while i < bytes.len() && depth > 0 {
+        if bytes[i..].starts_with(b"*/") {
+            depth -= 1;
+            i += 2;
+        } else if bytes[i..].starts_with(b"/*") {
+            depth += 1;
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    (i, depth)
+}
+
+/// End of a string starting at `i`, after its closing `quote`, and whether it was closed
+fn scan_string(bytes: &[u8], mut i: usize, quote: u8) -> (usize, bool) {
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b if b == quote => return (i + 1, true),
+            _ => i += 1,
+        }
+    }
+    (bytes.len(), false)
+}
+
+/// Number of `#` of a raw string opening after its `r`
+fn raw_string_hashes(bytes: &[u8]) -> Option<usize> {
+    let hashes = bytes.iter().take_while(|b| **b == b'#').count();
+    (bytes.get(hashes) == Some(&b'"')).then_some(hashes)
+}
+
+/// End of a raw string starting at `i`, after its closing quote and `hashes`
+fn scan_raw_string(bytes: &[u8], mut i: usize, hashes: usize) -> (usize, bool) {
+    while i < bytes.len() {
+        if bytes[i] == b'"' && bytes[i + 1..].iter().take(hashes).all(|b| *b == b'#') {
+            let end = i + 1 + hashes;
+            if end <= bytes.len() {
+                return (end, true);
+            }
+        }
+        i += 1;
+    }
+    (bytes.len(), false)
+}
+
+/// End of a number starting at `i`, including hex digits, separators and exponents
+fn scan_number(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() {
+        match bytes[i] {
+            b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'_' | b'.' => i += 1,
+            b'+' | b'-' if matches!(bytes[i - 1], b'e' | b'E') => i += 1,
+            _ => break,
+        }
+    }
+    i
+}
+
+fn highlight_syntax(
+    mut q: Query<(
+        &mut SyntaxHighlight,
+        &mut CosmicBuffer,
+        Option<&mut CosmicEditor>,
+        &DefaultAttrs,
+        Option<&Placeholder>,
+        Option<&Password>,
+    )>,
+) {
+    for (mut highlight, mut buffer, editor_opt, attrs, placeholder_opt, password_opt) in
+        q.iter_mut()
+    {
+        if password_opt.is_some() || placeholder_opt.is_some_and(|p| p.is_active()) {
+            continue;
+        }
+
+        // Only the cache of the highlighter changes
+        let highlight = highlight.bypass_change_detection();
+        match editor_opt {
+            Some(mut editor) => {
+                let changed = editor
+                    .bypass_change_detection()
+                    .with_buffer_mut(|b| highlight.0.update(&mut b.lines, &attrs.0));
+                if changed {
+                    editor.set_redraw(true);
+                }
+            }
+            None => {
+                let changed = highlight
+                    .0
+                    .update(&mut buffer.bypass_change_detection().lines, &attrs.0);
+                if changed {
+                    buffer.set_redraw(true);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmic_text::Shaping;
+
+    fn colored(
+        line: &str,
+        spans: &[(Range<usize>, AttrsOwned)],
+        color: CosmicColor,
+    ) -> Vec<String> {
+        spans
+            .iter()
+            .filter(|(_, attrs)| attrs.color_opt == Some(color))
+            .map(|(range, _)| line[range.clone()].to_string())
+            .collect()
+    }
+
+    #[test]
+    fn ron_and_json() {
+        let attrs = AttrsOwned::new(Attrs::new());
+        let ron = RonHighlighter::default();
+        let theme = &ron.theme;
+        let mut state = LexState::Code;
+
+        let line = "Config(name: \"a\", /* open";
+        let spans = ron.highlight_line(line, &mut state, &attrs);
+        assert_eq!(state, LexState::BlockComment(1));
+        assert_eq!(colored(line, &spans, theme.type_name), vec!["Config"]);
+        assert_eq!(colored(line, &spans, theme.key), vec!["name"]);
+        assert_eq!(colored(line, &spans, theme.comment), vec!["/* open"]);
+
+        let line = "still */ size: Some(-1.5e3),";
+        let spans = ron.highlight_line(line, &mut state, &attrs);
+        assert_eq!(state, LexState::Code);
+        assert_eq!(colored(line, &spans, theme.comment), vec!["still */"]);
+        assert_eq!(colored(line, &spans, theme.keyword), vec!["Some"]);
+        assert_eq!(colored(line, &spans, theme.number), vec!["-1.5e3"]);
+
+        let json = JsonHighlighter::default();
+        let line = r#"{"key": "value", "n": [1, null]}"#;
+        let spans = json.highlight_line(line, &mut state, &attrs);
+        assert_eq!(
+            colored(line, &spans, json.theme.key),
+            vec!["\"key\"", "\"n\""]
+        );
+        assert_eq!(colored(line, &spans, json.theme.string), vec!["\"value\""]);
+        assert_eq!(colored(line, &spans, json.theme.keyword), vec!["null"]);
+    }
+
+    /// [`RonHighlighter`] that records the lines it highlights
+    #[derive(Default)]
+    struct Recording {
+        ron: RonHighlighter,
+        seen: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl SyntaxHighlighter for Recording {
+        type State = LexState;
+
+        fn highlight_line(
+            &self,
+            line: &str,
+            state: &mut LexState,
+            attrs: &AttrsOwned,
+        ) -> Vec<(Range<usize>, AttrsOwned)> {
+            self.seen.lock().unwrap().push(line.to_string());
+            self.ron.highlight_line(line, state, attrs)
+        }
+    }
+
+    #[test]
+    fn only_affected_lines_are_highlighted_again() {
+        let attrs = AttrsOwned::new(Attrs::new());
+        let comment = HighlightTheme::default().comment;
+        let mut highlighted = Highlighted {
+            highlighter: Recording::default(),
+            lines: Vec::new(),
+        };
+        let mut lines: Vec<BufferLine> = ["a: 1,", "/* open", "inside", "close */", "b: 2,"]
+            .into_iter()
+            .map(|text| BufferLine::new(text, AttrsList::new(attrs.as_attrs()), Shaping::Advanced))
+            .collect();
+        let mut update = |lines: &mut Vec<BufferLine>| {
+            let changed = highlighted.update(lines, &attrs);
+            let seen = std::mem::take(&mut *highlighted.highlighter.seen.lock().unwrap());
+            assert_eq!(highlighted.lines.len(), lines.len());
+            (changed, seen)
+        };
+        let commented = |line: &BufferLine| {
+            line.attrs_list()
+                .spans()
+                .iter()
+                .any(|(_, attrs)| attrs.color_opt == Some(comment))
+        };
+        let set_text = |line: &mut BufferLine, text: &str| {
+            line.set_text(text, AttrsList::new(attrs.as_attrs()));
+        };
+
+        let (changed, seen) = update(&mut lines);
+        assert!(changed && seen.len() == 5);
+        assert_eq!(update(&mut lines), (false, vec![]));
+
+        // Editing inside the comment leaves the state after the line as it was
+        set_text(&mut lines[2], "in side");
+        assert_eq!(update(&mut lines), (true, vec!["in side".to_string()]));
+        assert!(commented(&lines[2]) && commented(&lines[3]));
+
+        // Closing the comment early changes the lines after it, up to where the state matches
+        set_text(&mut lines[1], "/* open */");
+        let (_, seen) = update(&mut lines);
+        assert_eq!(seen, vec!["/* open */", "in side", "close */"]);
+        assert!(!commented(&lines[2]));
+
+        // Inserted lines are highlighted on their own, deleted ones need nothing highlighted
+        let new = BufferLine::new("c: 3,", AttrsList::new(attrs.as_attrs()), Shaping::Advanced);
+        lines.insert(1, new);
+        assert_eq!(update(&mut lines), (true, vec!["c: 3,".to_string()]));
+        lines.remove(1);
+        assert_eq!(update(&mut lines), (true, vec![]));
+        assert_eq!(update(&mut lines), (false, vec![]));
+    }
+}
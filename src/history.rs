@@ -0,0 +1,479 @@
+use crate::*;
+use bevy::prelude::*;
+use cosmic_text::{Change, ChangeItem, Cursor, Edit, Selection};
+
+/// System set for undo history bookkeeping. Runs in [`Update`]
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HistorySet;
+
+pub(crate) struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            track_buffer_text
+                .in_set(HistorySet)
+                .after(InputSet)
+                .before(RenderSet),
+        );
+    }
+}
+
+/// Kind of edit, used to decide whether consecutive edits are grouped into one undo step
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EditKind {
+    Insert,
+    Delete,
+    Other,
+}
+
+/// A single undoable step, with the cursor and selection on either side of the change
+#[derive(Clone, Debug)]
+struct HistoryEntry {
+    change: Change,
+    kind: EditKind,
+    cursor_before: Cursor,
+    selection_before: Selection,
+    cursor_after: Cursor,
+    selection_after: Selection,
+}
+
+/// Per-widget undo/redo history.
+///
+/// Records edits made through keyboard input, the clipboard and programmatic
+/// [`CosmicBuffer::set_text`] calls. Typing and deleting are grouped into word-sized steps.
+///
+/// Lives alongside the [`CosmicBuffer`], so it is kept when the [`CosmicEditor`] is dropped on
+/// focus loss.
+///
+/// Undo is bound to Ctrl/Cmd+Z, redo to Ctrl/Cmd+Shift+Z and Ctrl/Cmd+Y.
+#[derive(Component, Debug)]
+pub struct EditHistory {
+    /// Maximum number of undo steps kept
+    pub max_steps: usize,
+    undo_stack: Vec<HistoryEntry>,
+    redo_stack: Vec<HistoryEntry>,
+    /// Set to prevent the next edit from being merged into the previous step
+    sealed: bool,
+    /// Set while every edit is merged into one step, see [`EditHistory::begin_group`]
+    grouping: bool,
+    /// Whether the open group has a step to merge into yet
+    group_started: bool,
+    /// Last known text, used to detect programmatic changes to the buffer
+    text: Option<String>,
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self::new(100)
+    }
+}
+
+impl EditHistory {
+    /// New history keeping at most `max_steps` undo steps
+    pub fn new(max_steps: usize) -> Self {
+        Self {
+            max_steps,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            sealed: false,
+            grouping: false,
+            group_started: false,
+            text: None,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Forget all recorded steps
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.sealed = false;
+        self.grouping = false;
+    }
+
+    /// Ends the current step, so the next edit starts a new one
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    /// Merges every edit until [`EditHistory::end_group`] into one new step, such as a vim
+    /// insert session
+    pub fn begin_group(&mut self) {
+        self.sealed = true;
+        self.grouping = true;
+        self.group_started = false;
+    }
+
+    /// Ends the group started by [`EditHistory::begin_group`]
+    pub fn end_group(&mut self) {
+        if self.grouping {
+            self.grouping = false;
+            self.sealed = true;
+        }
+    }
+
+    /// Records a finished [`Change`] made on `editor`.
+    ///
+    /// `before` is the cursor and selection of the editor before the change was started.
+    pub fn record(&mut self, change: Change, before: (Cursor, Selection), editor: &CosmicEditor) {
+        self.record_with(
+            change,
+            before,
+            (editor.cursor(), editor.selection()),
+            editor.with_buffer(|b| b.get_text()),
+        );
+    }
+
+    /// Records a finished [`Change`] made without a [`CosmicEditor`], such as on an unfocused
+    /// [`CosmicBuffer`]. `text` is the buffer text after the change.
+    pub fn record_with(
+        &mut self,
+        change: Change,
+        before: (Cursor, Selection),
+        after: (Cursor, Selection),
+        text: String,
+    ) {
+        if change.items.is_empty() {
+            return;
+        }
+
+        let kind = edit_kind(&change);
+        let entry = HistoryEntry {
+            change,
+            kind,
+            cursor_before: before.0,
+            selection_before: before.1,
+            cursor_after: after.0,
+            selection_after: after.1,
+        };
+
+        self.push(entry);
+        self.text = Some(text);
+    }
+
+    /// Reverts the last step on `editor`. Returns `true` if anything was undone.
+    pub fn undo(&mut self, editor: &mut CosmicEditor) -> bool {
+        let Some(entry) = self.undo_stack.pop() else {
+            return false;
+        };
+
+        let mut change = entry.change.clone();
+        change.reverse();
+        if !editor.apply_change(&change) {
+            self.undo_stack.push(entry);
+            return false;
+        }
+
+        editor.set_cursor(entry.cursor_before);
+        editor.set_selection(entry.selection_before);
+        editor.set_redraw(true);
+
+        self.text = Some(editor.with_buffer(|b| b.get_text()));
+        self.redo_stack.push(entry);
+        self.sealed = true;
+        self.group_started = false;
+        true
+    }
+
+    /// Re-applies the last undone step on `editor`. Returns `true` if anything was redone.
+    pub fn redo(&mut self, editor: &mut CosmicEditor) -> bool {
+        let Some(entry) = self.redo_stack.pop() else {
+            return false;
+        };
+
+        if !editor.apply_change(&entry.change) {
+            self.redo_stack.push(entry);
+            return false;
+        }
+
+        editor.set_cursor(entry.cursor_after);
+        editor.set_selection(entry.selection_after);
+        editor.set_redraw(true);
+
+        self.text = Some(editor.with_buffer(|b| b.get_text()));
+        self.undo_stack.push(entry);
+        self.sealed = true;
+        self.group_started = false;
+        true
+    }
+
+    fn push(&mut self, entry: HistoryEntry) {
+        self.redo_stack.clear();
+
+        let merge = self.grouping && self.group_started;
+        if merge || !self.sealed {
+            if let Some(last) = self.undo_stack.last_mut() {
+                if merge || should_merge(last, &entry) {
+                    last.change.items.extend(entry.change.items);
+                    last.cursor_after = entry.cursor_after;
+                    last.selection_after = entry.selection_after;
+                    return;
+                }
+            }
+        }
+
+        self.sealed = entry.kind == EditKind::Other;
+        self.group_started = self.grouping;
+        self.undo_stack.push(entry);
+
+        if self.max_steps > 0 && self.undo_stack.len() > self.max_steps {
+            let overflow = self.undo_stack.len() - self.max_steps;
+            self.undo_stack.drain(..overflow);
+        }
+    }
+
+    /// Records a whole-text replacement, as done by [`CosmicBuffer::set_text`]
+    fn record_replace(&mut self, old: String, new: String) {
+        let start = Cursor::new(0, 0);
+        let change = Change {
+            items: vec![
+                ChangeItem {
+                    start,
+                    end: text_end(&old),
+                    text: old,
+                    insert: false,
+                },
+                ChangeItem {
+                    start,
+                    end: text_end(&new),
+                    text: new,
+                    insert: true,
+                },
+            ],
+        };
+
+        self.push(HistoryEntry {
+            change,
+            kind: EditKind::Other,
+            cursor_before: start,
+            selection_before: Selection::None,
+            cursor_after: start,
+            selection_after: Selection::None,
+        });
+    }
+}
+
+/// Cursor at the end of `text`
+fn text_end(text: &str) -> Cursor {
+    let line = text.split('\n').count() - 1;
+    let index = text.rsplit('\n').next().map_or(0, str::len);
+    Cursor::new(line, index)
+}
+
+fn edit_kind(change: &Change) -> EditKind {
+    if change.items.iter().all(|item| item.insert) {
+        EditKind::Insert
+    } else if change.items.iter().all(|item| !item.insert) {
+        EditKind::Delete
+    } else {
+        EditKind::Other
+    }
+}
+
+/// Groups consecutive typing or deleting into one step, breaking at word starts and newlines
+fn should_merge(last: &HistoryEntry, next: &HistoryEntry) -> bool {
+    if last.kind != next.kind || next.kind == EditKind::Other {
+        return false;
+    }
+    if last.cursor_after != next.cursor_before
+        || last.selection_after != Selection::None
+        || next.selection_before != Selection::None
+    {
+        return false;
+    }
+
+    let (Some(last_item), Some(next_item)) = (last.change.items.last(), next.change.items.first())
+    else {
+        return false;
+    };
+
+    if last_item.text.contains('\n') || next_item.text.contains('\n') {
+        return false;
+    }
+
+    // Backspace deletes right to left, so the most recent char is at the start of the item
+    let (prev_char, next_char) = match next.kind {
+        EditKind::Insert => (
+            last_item.text.chars().next_back(),
+            next_item.text.chars().next(),
+        ),
+        _ => (
+            last_item.text.chars().next(),
+            next_item.text.chars().next_back(),
+        ),
+    };
+
+    match (prev_char, next_char) {
+        (Some(prev), Some(next)) => !prev.is_whitespace() || next.is_whitespace(),
+        _ => true,
+    }
+}
+
+/// Records programmatic text changes on unfocused widgets as undo steps
+fn track_buffer_text(
+    mut q: Query<
+        (
+            &CosmicBuffer,
+            Option<&CosmicEditor>,
+            &mut EditHistory,
+            Option<&Placeholder>,
+        ),
+        Or<(Changed<CosmicBuffer>, Changed<CosmicEditor>)>,
+    >,
+) {
+    for (buffer, editor_opt, mut history, placeholder_opt) in q.iter_mut() {
+        let placeholder_active = placeholder_opt.is_some_and(|p| p.is_active());
+
+        let text = if placeholder_active {
+            String::new()
+        } else if let Some(editor) = editor_opt {
+            // Edits in a focused editor are recorded by the input systems
+            editor.with_buffer(|b| b.get_text())
+        } else {
+            buffer.get_text()
+        };
+
+        if history.text.as_ref() == Some(&text) {
+            continue;
+        }
+
+        match history.text.take() {
+            Some(old) if editor_opt.is_none() => {
+                history.record_replace(old, text.clone());
+            }
+            _ => {}
+        }
+        history.text = Some(text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmic_text::{fontdb, Attrs, Buffer, Editor, FontSystem, Metrics, Shaping};
+
+    /// Step typing or deleting `text` at `index` of the first line
+    fn step(kind: EditKind, index: usize, text: &str) -> HistoryEntry {
+        let start = Cursor::new(0, index);
+        let end = Cursor::new(0, index + text.len());
+        let (before, after) = match kind {
+            EditKind::Insert => (start, end),
+            _ => (end, start),
+        };
+        HistoryEntry {
+            change: Change {
+                items: vec![ChangeItem {
+                    start,
+                    end,
+                    text: text.to_string(),
+                    insert: kind == EditKind::Insert,
+                }],
+            },
+            kind,
+            cursor_before: before,
+            selection_before: Selection::None,
+            cursor_after: after,
+            selection_after: Selection::None,
+        }
+    }
+
+    fn editor(text: &str) -> (FontSystem, CosmicEditor) {
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(20., 20.));
+        buffer.set_text(&mut font_system, text, Attrs::new(), Shaping::Advanced);
+        (font_system, CosmicEditor::new(Editor::new(buffer)))
+    }
+
+    #[test]
+    fn typing_merges_until_a_word_starts() {
+        let mut history = EditHistory::default();
+        for (i, c) in "ab cd".chars().enumerate() {
+            history.push(step(EditKind::Insert, i, &c.to_string()));
+        }
+        // "ab " and "cd"
+        assert_eq!(history.undo_stack.len(), 2);
+        assert_eq!(history.undo_stack[0].change.items.len(), 3);
+
+        // Moving the cursor elsewhere starts a new step
+        history.push(step(EditKind::Insert, 0, "x"));
+        assert_eq!(history.undo_stack.len(), 3);
+
+        // So does a newline, and the text typed after it
+        history.push(step(EditKind::Insert, 1, "\n"));
+        assert_eq!(history.undo_stack.len(), 4);
+    }
+
+    #[test]
+    fn backspace_merges_until_a_word_ends() {
+        let mut history = EditHistory::default();
+        // Backspacing "ab cd" from the end
+        for (i, c) in "ab cd".char_indices().rev() {
+            history.push(step(EditKind::Delete, i, &c.to_string()));
+        }
+        // " cd" and "ab"
+        assert_eq!(history.undo_stack.len(), 2);
+
+        // Typing after deleting is a step of its own
+        history.push(step(EditKind::Insert, 0, "x"));
+        assert_eq!(history.undo_stack.len(), 3);
+    }
+
+    #[test]
+    fn sealing_starts_a_new_step() {
+        let mut history = EditHistory::default();
+        history.push(step(EditKind::Insert, 0, "a"));
+        history.seal();
+        history.push(step(EditKind::Insert, 1, "b"));
+        assert_eq!(history.undo_stack.len(), 2);
+    }
+
+    #[test]
+    fn groups_merge_every_edit() {
+        let mut history = EditHistory::default();
+        history.push(step(EditKind::Insert, 0, "a"));
+        history.begin_group();
+        history.push(step(EditKind::Delete, 0, "a"));
+        history.push(step(EditKind::Insert, 0, "b c"));
+        history.seal();
+        history.push(step(EditKind::Insert, 7, "\n"));
+        history.end_group();
+        history.push(step(EditKind::Insert, 8, "d"));
+        assert_eq!(history.undo_stack.len(), 3);
+        assert_eq!(history.undo_stack[1].change.items.len(), 3);
+    }
+
+    #[test]
+    fn undo_and_redo_across_replace() {
+        let (_font_system, mut editor) = editor("new text");
+        let mut history = EditHistory::default();
+        history.record_replace("old".to_string(), "new text".to_string());
+
+        // Typing after a replacement is not merged into it
+        editor.set_cursor(Cursor::new(0, 8));
+        editor.insert_string("!", None);
+        history.push(step(EditKind::Insert, 8, "!"));
+        assert_eq!(history.undo_stack.len(), 2);
+
+        assert!(history.undo(&mut editor));
+        assert_eq!(editor.with_buffer(|b| b.get_text()), "new text");
+        assert!(history.undo(&mut editor));
+        assert_eq!(editor.with_buffer(|b| b.get_text()), "old");
+        assert!(!history.undo(&mut editor));
+
+        assert!(history.redo(&mut editor));
+        assert_eq!(editor.with_buffer(|b| b.get_text()), "new text");
+        assert!(history.redo(&mut editor));
+        assert_eq!(editor.with_buffer(|b| b.get_text()), "new text!");
+        assert!(!history.can_redo());
+    }
+}
@@ -0,0 +1,418 @@
+use crate::*;
+use bevy::{prelude::*, window::PrimaryWindow};
+use cosmic_text::{Action, Cursor, Edit, Selection};
+
+/// System set for input method systems. Runs in [`Update`] and [`PostUpdate`]
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ImeSet;
+
+pub(crate) struct ImePlugin;
+
+impl Plugin for ImePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                set_ime_enabled,
+                read_ime_events.in_set(InputSet).after(kb_input_text),
+            )
+                .chain()
+                .in_set(ImeSet),
+        )
+        .add_systems(
+            PostUpdate,
+            (
+                (set_ime_position, insert_preedit)
+                    .chain()
+                    .after(WidgetSet)
+                    .after(PasswordSet)
+                    .before(RenderSet),
+                remove_preedit.after(RenderSet).before(FocusSet),
+            )
+                .in_set(ImeSet),
+        );
+    }
+}
+
+/// Text being composed by an input method in the focused editor.
+///
+/// Added and removed automatically while composing. Rendered inline at the cursor with an
+/// underline, but not part of the buffer text until committed.
+#[derive(Component, Debug, Default, Clone)]
+pub struct ImePreedit {
+    /// Composing text
+    pub value: String,
+    /// Byte range of the input method's cursor within `value`, [`None`] hides the cursor
+    pub cursor: Option<(usize, usize)>,
+    /// Range of the preedit text while it is inserted into the editor buffer for rendering
+    range: Option<(Cursor, Cursor)>,
+    /// Editor cursor and selection to restore once the preedit text is removed again
+    saved: Option<(Cursor, Selection)>,
+}
+
+impl ImePreedit {
+    /// Buffer range covered by the preedit text during rendering
+    pub(crate) fn range(&self) -> Option<(Cursor, Cursor)> {
+        self.range
+    }
+
+    /// Buffer range covered by the input method's cursor during rendering
+    pub(crate) fn cursor_range(&self) -> Option<(Cursor, Cursor)> {
+        let (start, _) = self.range?;
+        let (begin, end) = self.cursor?;
+        Some((
+            Cursor::new(start.line, start.index + begin),
+            Cursor::new(start.line, start.index + end),
+        ))
+    }
+}
+
+/// Enables IME on the primary window while an editable widget is focused
+fn set_ime_enabled(
+    mut commands: Commands,
+    active_editor: Res<FocusedWidget>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    editable_q: Query<
+        (),
+        (
+            With<CosmicBuffer>,
+            Without<ReadOnly>,
+            Without<Password>,
+            Without<InputMask>,
+        ),
+    >,
+    preedit_q: Query<Entity, With<ImePreedit>>,
+) {
+    if !active_editor.is_changed() {
+        return;
+    }
+
+    for e in preedit_q.iter() {
+        if Some(e) != active_editor.0 {
+            commands.entity(e).remove::<ImePreedit>();
+        }
+    }
+
+    let enabled = active_editor.0.is_some_and(|e| editable_q.contains(e));
+    for mut window in windows.iter_mut() {
+        if window.ime_enabled != enabled {
+            window.ime_enabled = enabled;
+        }
+    }
+}
+
+/// Applies composition and commit events to the focused editor
+fn read_ime_events(
+    mut commands: Commands,
+    active_editor: Res<FocusedWidget>,
+    mut evr_ime: EventReader<Ime>,
+    mut editor_q: Query<
+        (
+            &mut CosmicEditor,
+            &MaxLines,
+            &MaxChars,
+            Option<&mut ImePreedit>,
+            Option<&mut EditHistory>,
+            Option<&InputFilter>,
+        ),
+        Without<ReadOnly>,
+    >,
+    mut evw_changed: EventWriter<CosmicTextChanged>,
+    mut evw_rejected: EventWriter<CosmicInputRejected>,
+    mut font_system: ResMut<CosmicFontSystem>,
+) {
+    let Some(active_editor_entity) = active_editor.0 else {
+        evr_ime.clear();
+        return;
+    };
+
+    let Ok((mut editor, max_lines, max_chars, mut preedit_opt, mut history_opt, filter_opt)) =
+        editor_q.get_mut(active_editor_entity)
+    else {
+        evr_ime.clear();
+        return;
+    };
+
+    for ev in evr_ime.read() {
+        match ev {
+            Ime::Preedit { value, cursor, .. } => {
+                editor.cursor_visible = true;
+                editor.cursor_timer.reset();
+                editor.set_redraw(true);
+
+                if value.is_empty() {
+                    commands.entity(active_editor_entity).remove::<ImePreedit>();
+                    preedit_opt = None;
+                    continue;
+                }
+
+                match preedit_opt.as_mut() {
+                    Some(preedit) => {
+                        preedit.value.clone_from(value);
+                        preedit.cursor = *cursor;
+                    }
+                    None => {
+                        commands.entity(active_editor_entity).insert(ImePreedit {
+                            value: value.clone(),
+                            cursor: *cursor,
+                            ..default()
+                        });
+                    }
+                }
+            }
+            Ime::Commit { value, .. } => {
+                commands.entity(active_editor_entity).remove::<ImePreedit>();
+                preedit_opt = None;
+
+                let value =
+                    filter_input(filter_opt, active_editor_entity, value, &mut evw_rejected);
+                let before = (editor.cursor(), editor.selection());
+                let change = editor.edit_all_cursors(&mut font_system.0, |editor, font_system| {
+                    for c in value.chars() {
+                        let (text_len, line_count) =
+                            editor.with_buffer(|b| (b.get_text().len(), b.lines.len()));
+                        if max_chars.0 != 0 && text_len >= max_chars.0 {
+                            break;
+                        }
+                        if c == '\n' && max_lines.0 != 0 && line_count >= max_lines.0 {
+                            continue;
+                        }
+                        editor.action(font_system, Action::Insert(c));
+                    }
+                });
+
+                if let (Some(change), Some(history)) = (change, &mut history_opt) {
+                    history.record(change, before, &editor);
+                }
+
+                editor.set_redraw(true);
+                evw_changed.send(CosmicTextChanged((
+                    active_editor_entity,
+                    editor.with_buffer(|b| b.get_text()),
+                )));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reports the caret position of the focused editor so the candidate window follows it
+fn set_ime_position(
+    active_editor: Res<FocusedWidget>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    editor_q: Query<(
+        &CosmicEditor,
+        &GlobalTransform,
+        &Sprite,
+        &CosmicPadding,
+        &XOffset,
+    )>,
+    node_q: Query<(&Node, &GlobalTransform, &CosmicSource)>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+) {
+    let Some(active_editor_entity) = active_editor.0 else {
+        return;
+    };
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
+    if !window.ime_enabled {
+        return;
+    }
+    let Ok((editor, sprite_transform, sprite, padding, x_offset)) =
+        editor_q.get(active_editor_entity)
+    else {
+        return;
+    };
+    let Some((camera, camera_transform)) = camera_q.iter().find(|(c, _)| c.is_active) else {
+        return;
+    };
+
+    let mut is_ui_node = false;
+    let mut transform = sprite_transform;
+    let mut size = sprite.custom_size.unwrap_or(Vec2::ONE);
+    for (node, node_transform, source) in node_q.iter() {
+        if source.0 != active_editor_entity {
+            continue;
+        }
+        is_ui_node = true;
+        transform = node_transform;
+        size = node.size();
+    }
+
+    if let Some(position) = get_caret_window_pos(
+        editor,
+        (transform, size, is_ui_node),
+        padding,
+        x_offset,
+        (camera, camera_transform),
+        window.scale_factor(),
+    ) {
+        if window.ime_position != position {
+            window.ime_position = position;
+        }
+    }
+}
+
+/// Temporarily inserts the preedit text at the cursor so it is laid out and rendered inline
+fn insert_preedit(
+    mut q: Query<(&mut CosmicEditor, &mut ImePreedit)>,
+    mut font_system: ResMut<CosmicFontSystem>,
+) {
+    for (mut editor, mut preedit) in q.iter_mut() {
+        if preedit.value.is_empty() || preedit.range.is_some() {
+            continue;
+        }
+
+        insert_preedit_text(&mut editor, &mut preedit);
+        editor.shape_as_needed(&mut font_system.0, false);
+        editor.set_redraw(true);
+    }
+}
+
+fn insert_preedit_text(editor: &mut CosmicEditor, preedit: &mut ImePreedit) {
+    let cursor = editor.cursor();
+    preedit.saved = Some((cursor, editor.selection()));
+
+    editor.set_selection(Selection::None);
+    editor.insert_string(&preedit.value, None);
+    preedit.range = Some((cursor, editor.cursor()));
+
+    if let Some((begin, _)) = preedit.cursor {
+        editor.set_cursor(Cursor::new(cursor.line, cursor.index + begin));
+    }
+}
+
+/// Removes the preedit text inserted by [`insert_preedit`] after rendering
+fn remove_preedit(mut q: Query<(&mut CosmicEditor, &mut ImePreedit)>) {
+    for (mut editor, mut preedit) in q.iter_mut() {
+        remove_preedit_text(&mut editor, &mut preedit);
+    }
+}
+
+fn remove_preedit_text(editor: &mut CosmicEditor, preedit: &mut ImePreedit) {
+    let Some((start, end)) = preedit.range.take() else {
+        return;
+    };
+
+    editor.delete_range(start, end);
+
+    if let Some((cursor, selection)) = preedit.saved.take() {
+        editor.set_cursor(cursor);
+        editor.set_selection(selection);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmic_text::{Attrs, Buffer, Editor, Metrics, Shaping};
+
+    fn app_with_editor(text: &str, cursor: Cursor) -> (App, Entity) {
+        let mut font_system = create_cosmic_font_system(CosmicFontConfig {
+            load_system_fonts: false,
+            ..Default::default()
+        });
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(20., 20.));
+        buffer.set_text(&mut font_system, text, Attrs::new(), Shaping::Advanced);
+        let mut editor = CosmicEditor::new(Editor::new(buffer));
+        editor.set_cursor(cursor);
+
+        let mut app = App::new();
+        app.add_event::<Ime>()
+            .add_event::<CosmicTextChanged>()
+            .add_event::<CosmicInputRejected>()
+            .insert_resource(CosmicFontSystem(font_system))
+            // One frame: events are read, then the preedit is laid out, drawn and taken out
+            .add_systems(
+                Update,
+                (
+                    read_ime_events,
+                    apply_deferred,
+                    insert_preedit,
+                    remove_preedit,
+                )
+                    .chain(),
+            );
+        let entity = app
+            .world
+            .spawn((editor, MaxLines::default(), MaxChars::default()))
+            .id();
+        app.insert_resource(FocusedWidget(Some(entity)));
+        (app, entity)
+    }
+
+    fn text(app: &App, entity: Entity) -> String {
+        let editor = app.world.get::<CosmicEditor>(entity).unwrap();
+        editor.with_buffer(|b| b.get_text())
+    }
+
+    fn preedit_event(value: &str, cursor: Option<(usize, usize)>) -> Ime {
+        Ime::Preedit {
+            window: Entity::PLACEHOLDER,
+            value: value.to_string(),
+            cursor,
+        }
+    }
+
+    #[test]
+    fn preedit_is_inserted_and_removed() {
+        // After the two byte `ñ`, with a selection that outlives the preedit
+        let (mut app, entity) = app_with_editor("añb", Cursor::new(0, 3));
+        let mut editor = app.world.entity_mut(entity).take::<CosmicEditor>().unwrap();
+        editor.set_selection(Selection::Normal(Cursor::new(0, 0)));
+        let mut preedit = ImePreedit {
+            value: "日本".to_string(),
+            cursor: Some((3, 6)),
+            ..default()
+        };
+
+        // Inserted at the cursor, with the input method's cursor on the second character
+        insert_preedit_text(&mut editor, &mut preedit);
+        assert_eq!(editor.with_buffer(|b| b.get_text()), "añ日本b");
+        assert_eq!(
+            preedit.range(),
+            Some((Cursor::new(0, 3), Cursor::new(0, 9)))
+        );
+        assert_eq!(
+            preedit.cursor_range(),
+            Some((Cursor::new(0, 6), Cursor::new(0, 9)))
+        );
+        assert_eq!(editor.cursor(), Cursor::new(0, 6));
+        assert_eq!(editor.selection(), Selection::None);
+
+        remove_preedit_text(&mut editor, &mut preedit);
+        assert_eq!(editor.with_buffer(|b| b.get_text()), "añb");
+        assert_eq!(editor.cursor(), Cursor::new(0, 3));
+        assert_eq!(editor.selection(), Selection::Normal(Cursor::new(0, 0)));
+        assert_eq!(preedit.range(), None);
+
+        // Drawn and taken out again within a frame
+        app.world.entity_mut(entity).insert(editor);
+        app.world.send_event(preedit_event("日本", Some((3, 6))));
+        app.update();
+        assert!(app.world.get::<ImePreedit>(entity).is_some());
+        assert_eq!(text(&app, entity), "añb");
+    }
+
+    #[test]
+    fn commit_replaces_preedit() {
+        let (mut app, entity) = app_with_editor("añb", Cursor::new(0, 3));
+        app.world.send_event(preedit_event("ka", Some((2, 2))));
+        app.update();
+        app.world.send_event(preedit_event("か", None));
+        app.update();
+        assert!(app.world.get::<ImePreedit>(entity).is_some());
+
+        app.world.send_event(Ime::Commit {
+            window: Entity::PLACEHOLDER,
+            value: "火".to_string(),
+        });
+        app.update();
+
+        assert!(app.world.get::<ImePreedit>(entity).is_none());
+        assert_eq!(text(&app, entity), "añ火b");
+        let editor = app.world.get::<CosmicEditor>(entity).unwrap();
+        assert_eq!(editor.cursor(), Cursor::new(0, 6));
+    }
+}
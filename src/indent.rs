@@ -0,0 +1,459 @@
+use crate::*;
+use bevy::prelude::*;
+use cosmic_text::{AttrsList, Buffer, Cursor, Edit, Editor, Selection};
+
+pub(crate) struct IndentPlugin;
+
+impl Plugin for IndentPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, set_tab_width);
+    }
+}
+
+/// What the Tab key inserts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndentStyle {
+    /// A tab character
+    Tab,
+    /// Spaces up to the next multiple of this many columns
+    Spaces(u16),
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] to configure the Tab key and
+/// indentation.
+///
+/// Tab inserts an indent, or indents every selected line when there is a selection. Shift+Tab
+/// outdents the current or selected lines. With `auto_indent`, Enter copies the leading
+/// whitespace of the line it splits.
+///
+/// Without this component Tab inserts a tab character and Enter a bare line break.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Indentation {
+    pub style: IndentStyle,
+    /// Width of a tab character, in spaces. Tabs are drawn up to the next multiple of this
+    /// many columns, counting every character as one column.
+    pub tab_width: u16,
+    pub auto_indent: bool,
+}
+
+impl Default for Indentation {
+    fn default() -> Self {
+        Self {
+            style: IndentStyle::Tab,
+            tab_width: 4,
+            auto_indent: true,
+        }
+    }
+}
+
+impl Indentation {
+    /// Columns covered by one level of indentation
+    fn width(&self) -> usize {
+        match self.style {
+            IndentStyle::Tab => self.tab_width.max(1) as usize,
+            IndentStyle::Spaces(n) => n.max(1) as usize,
+        }
+    }
+
+    /// Text inserted by Tab at `column`, or at the start of a line if [`None`]
+    fn indent_text(&self, column: Option<usize>) -> String {
+        match self.style {
+            IndentStyle::Tab => "\t".to_string(),
+            IndentStyle::Spaces(_) => {
+                let width = self.width();
+                " ".repeat(width - column.unwrap_or(0) % width)
+            }
+        }
+    }
+}
+
+fn set_tab_width(
+    mut q: Query<
+        (&Indentation, &mut CosmicEditor),
+        Or<(Changed<Indentation>, Added<CosmicEditor>)>,
+    >,
+) {
+    for (indentation, mut editor) in q.iter_mut() {
+        editor.set_tab_width(indentation.tab_width.max(1));
+        editor.set_redraw(true);
+    }
+}
+
+/// Indents the selected lines, or inserts an indent at the cursor without a selection
+pub(crate) fn indent(editor: &mut Editor<'static>, indentation: &Indentation) {
+    let Some((first, last)) = selected_lines(editor) else {
+        let cursor = editor.cursor();
+        let column = editor.with_buffer(|b| {
+            visual_column(
+                &b.lines[cursor.line].text()[..cursor.index],
+                indentation.tab_width,
+            )
+        });
+        editor.insert_string(&indentation.indent_text(Some(column)), None);
+        return;
+    };
+
+    let text = indentation.indent_text(None);
+    for line in first..=last {
+        editor.insert_at(Cursor::new(line, 0), &text, None);
+    }
+    shift_selection(editor, first, last, |_| text.len() as isize);
+}
+
+/// Removes one level of indentation from the current or selected lines
+pub(crate) fn unindent(editor: &mut Editor<'static>, indentation: &Indentation) {
+    let (first, last) = selected_lines(editor).unwrap_or_else(|| {
+        let line = editor.cursor().line;
+        (line, line)
+    });
+
+    let removed: Vec<usize> = (first..=last)
+        .map(|line| {
+            let len = editor.with_buffer(|b| {
+                let text = b.lines[line].text();
+                if text.starts_with('\t') {
+                    1
+                } else {
+                    text.chars()
+                        .take(indentation.width())
+                        .take_while(|c| *c == ' ')
+                        .count()
+                }
+            });
+            if len > 0 {
+                editor.delete_range(Cursor::new(line, 0), Cursor::new(line, len));
+            }
+            len
+        })
+        .collect();
+
+    shift_selection(editor, first, last, |line| {
+        -(removed[line - first] as isize)
+    });
+}
+
+/// Copies the leading whitespace of the previous line after a line break was inserted
+pub(crate) fn copy_indent(editor: &mut Editor<'static>) {
+    let cursor = editor.cursor();
+    if cursor.line == 0 {
+        return;
+    }
+
+    let leading: String = editor.with_buffer(|b| {
+        b.lines[cursor.line - 1]
+            .text()
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect()
+    });
+    if !leading.is_empty() {
+        editor.insert_string(&leading, None);
+    }
+}
+
+/// First and last line touched by a non-empty selection. A selection ending at the start of a
+/// line does not include that line.
+fn selected_lines(editor: &Editor<'static>) -> Option<(usize, usize)> {
+    let (start, end) = editor.selection_bounds().filter(|(s, e)| s != e)?;
+    let last = if end.index == 0 && end.line > start.line {
+        end.line - 1
+    } else {
+        end.line
+    };
+    Some((start.line, last))
+}
+
+/// Moves the cursor and selection anchor by the bytes added to or removed from the start of
+/// their lines
+fn shift_selection(
+    editor: &mut Editor<'static>,
+    first: usize,
+    last: usize,
+    delta: impl Fn(usize) -> isize,
+) {
+    let shift = |mut cursor: Cursor| {
+        if cursor.line >= first
+            && cursor.line <= last
+            && (cursor.index > 0 || delta(cursor.line) > 0)
+        {
+            cursor.index = (cursor.index as isize + delta(cursor.line)).max(0) as usize;
+        }
+        cursor
+    };
+
+    let cursor = shift(editor.cursor());
+    let selection = match editor.selection() {
+        Selection::None => Selection::None,
+        Selection::Normal(anchor) => Selection::Normal(shift(anchor)),
+        Selection::Line(anchor) => Selection::Line(shift(anchor)),
+        Selection::Word(anchor) => Selection::Word(shift(anchor)),
+    };
+    editor.set_cursor(cursor);
+    editor.set_selection(selection);
+}
+
+/// Lines of a buffer copy drawn with tabs expanded to spaces, with the text they stand for
+pub(crate) struct ExpandedTabs {
+    tab_width: u16,
+    originals: Vec<(usize, String)>,
+}
+
+impl ExpandedTabs {
+    /// Position in the drawn copy of `cursor` in the edited text
+    pub(crate) fn display_cursor(&self, mut cursor: Cursor) -> Cursor {
+        if let Some(text) = self.original_text(cursor.line) {
+            cursor.index = display_index(text, cursor.index, self.tab_width);
+        }
+        cursor
+    }
+
+    /// Position in the edited text of `cursor` in the drawn copy
+    pub(crate) fn text_cursor(&self, mut cursor: Cursor) -> Cursor {
+        if let Some(text) = self.original_text(cursor.line) {
+            cursor.index = text_index(text, cursor.index, self.tab_width);
+        }
+        cursor
+    }
+
+    fn original_text(&self, line: usize) -> Option<&str> {
+        self.originals
+            .iter()
+            .find(|(i, _)| *i == line)
+            .map(|(_, text)| text.as_str())
+    }
+}
+
+/// Copy of `buffer` with tabs swapped for spaces up to the next tab stop, so they are drawn at
+/// their width while the edited text keeps them. [`None`] if no line has a tab.
+pub(crate) fn expand_tabs(
+    buffer: &Buffer,
+    tab_width: u16,
+    font_system: &mut FontSystem,
+) -> Option<(Buffer, ExpandedTabs)> {
+    if !buffer.lines.iter().any(|line| line.text().contains('\t')) {
+        return None;
+    }
+
+    let mut display = buffer.clone();
+    let mut originals = Vec::new();
+    for (i, line) in display.lines.iter_mut().enumerate() {
+        let text = line.text();
+        if !text.contains('\t') {
+            continue;
+        }
+        let mut attrs_list = AttrsList::new(line.attrs_list().defaults());
+        for (range, attrs) in line.attrs_list().spans() {
+            let start = display_index(text, range.start, tab_width);
+            let end = display_index(text, range.end, tab_width);
+            attrs_list.add_span(start..end, attrs.as_attrs());
+        }
+        let original = text.to_string();
+        line.set_text(expand_line(&original, tab_width), attrs_list);
+        originals.push((i, original));
+    }
+    display.shape_until_scroll(font_system, false);
+
+    Some((
+        display,
+        ExpandedTabs {
+            tab_width,
+            originals,
+        },
+    ))
+}
+
+/// Copy of `editor` to draw, with tabs expanded by [`expand_tabs`] and its cursors moved along
+pub(crate) fn expand_editor_tabs(
+    editor: &CosmicEditor,
+    tab_width: u16,
+    font_system: &mut FontSystem,
+) -> Option<(CosmicEditor, ExpandedTabs)> {
+    let (display, tabs) = editor.with_buffer(|b| expand_tabs(b, tab_width, font_system))?;
+
+    let map_selection = |selection: Selection| match selection {
+        Selection::None => Selection::None,
+        Selection::Normal(c) => Selection::Normal(tabs.display_cursor(c)),
+        Selection::Line(c) => Selection::Line(tabs.display_cursor(c)),
+        Selection::Word(c) => Selection::Word(tabs.display_cursor(c)),
+    };
+
+    let mut shown = CosmicEditor::new(Editor::new(display));
+    shown.set_cursor(tabs.display_cursor(editor.cursor()));
+    shown.set_selection(map_selection(editor.selection()));
+    shown.secondary_cursors = editor
+        .secondary_cursors
+        .iter()
+        .map(|c| SecondaryCursor::new(tabs.display_cursor(c.cursor), map_selection(c.selection)))
+        .collect();
+    shown.cursor_visible = editor.cursor_visible;
+    Some((shown, tabs))
+}
+
+/// Point in the layout of `buffer` matching point `(x, y)` on its text as drawn, which differs
+/// when tabs are expanded
+pub(crate) fn text_point(
+    buffer: &Buffer,
+    indentation_opt: Option<&Indentation>,
+    font_system: &mut FontSystem,
+    (x, y): (i32, i32),
+) -> (i32, i32) {
+    let Some((display, tabs)) =
+        indentation_opt.and_then(|i| expand_tabs(buffer, i.tab_width, font_system))
+    else {
+        return (x, y);
+    };
+
+    display
+        .hit(x as f32, y as f32)
+        .and_then(|cursor| get_cursor_position(buffer, tabs.text_cursor(cursor)))
+        .map_or((x, y), |(x, top)| {
+            (
+                x.round() as i32,
+                (top + buffer.metrics().line_height / 2.) as i32,
+            )
+        })
+}
+
+/// `text` with every tab replaced by spaces up to the next tab stop
+fn expand_line(text: &str, tab_width: u16) -> String {
+    let tab_width = tab_width.max(1) as usize;
+    let mut expanded = String::with_capacity(text.len());
+    let mut column = 0;
+    for c in text.chars() {
+        if c == '\t' {
+            let spaces = tab_width - column % tab_width;
+            expanded.push_str(&" ".repeat(spaces));
+            column += spaces;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded
+}
+
+/// Index in the expanded `text` matching byte `index` of `text`
+fn display_index(text: &str, index: usize, tab_width: u16) -> usize {
+    expand_line(&text[..index.min(text.len())], tab_width).len()
+}
+
+/// Index in `text` matching byte `index` of the expanded text. Inside a tab this is the nearer
+/// side of it.
+fn text_index(text: &str, index: usize, tab_width: u16) -> usize {
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        let end = display_index(text, i + c.len_utf8(), tab_width);
+        if index < end {
+            return if index - start > (end - start) / 2 {
+                i + c.len_utf8()
+            } else {
+                i
+            };
+        }
+        start = end;
+    }
+    text.len()
+}
+
+/// Column of the end of `text`, with tabs advancing to the next tab stop
+fn visual_column(text: &str, tab_width: u16) -> usize {
+    let tab_width = tab_width.max(1) as usize;
+    text.chars().fold(0, |column, c| {
+        if c == '\t' {
+            column + tab_width - column % tab_width
+        } else {
+            column + 1
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmic_text::{Attrs, Metrics, Shaping};
+
+    #[test]
+    fn tabs_expand_to_tab_stops() {
+        assert_eq!(expand_line("\tx", 4), "    x");
+        assert_eq!(expand_line("ab\tx\t", 4), "ab  x   ");
+        assert_eq!(expand_line("abcd\tx", 4), "abcd    x");
+
+        // Cursors inside a tab go to its nearer side
+        assert_eq!(display_index("ab\tx", 3, 4), 4);
+        assert_eq!(text_index("ab\tx", 4, 4), 3);
+        assert_eq!(text_index("ab\tx", 2, 4), 2);
+        assert_eq!(text_index("ab\tx", 3, 4), 2);
+        assert_eq!(text_index("ab\tx", 5, 4), 4);
+    }
+
+    #[test]
+    fn tab_width_changes_what_is_drawn() {
+        let mut font_system = create_cosmic_font_system(CosmicFontConfig {
+            load_system_fonts: false,
+            ..Default::default()
+        });
+        let mut x_after_tab = |tab_width| {
+            let mut buffer = Buffer::new(&mut font_system, Metrics::new(20., 20.));
+            buffer.set_size(&mut font_system, 1000., 100.);
+            buffer.set_text(&mut font_system, "\tx", Attrs::new(), Shaping::Advanced);
+            let (display, tabs) = expand_tabs(&buffer, tab_width, &mut font_system).unwrap();
+            let x = display
+                .layout_runs()
+                .next()
+                .unwrap()
+                .glyphs
+                .last()
+                .unwrap()
+                .x;
+
+            // The edited text keeps its tab, cursors map across
+            assert_eq!(buffer.lines[0].text(), "\tx");
+            let after_tab = tabs.display_cursor(Cursor::new(0, 1));
+            assert_eq!(after_tab.index, tab_width as usize);
+            assert_eq!(tabs.text_cursor(after_tab), Cursor::new(0, 1));
+            x
+        };
+
+        let narrow = x_after_tab(2);
+        let wide = x_after_tab(8);
+        assert!(narrow > 0.);
+        assert!((wide - narrow * 4.).abs() < 0.01, "{narrow} {wide}");
+    }
+
+    #[test]
+    fn clicks_land_where_tabs_are_drawn() {
+        let mut font_system = create_cosmic_font_system(CosmicFontConfig {
+            load_system_fonts: false,
+            ..Default::default()
+        });
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(20., 20.));
+        buffer.set_size(&mut font_system, 1000., 100.);
+        buffer.set_text(&mut font_system, "\tx\ty", Attrs::new(), Shaping::Advanced);
+        buffer.shape_until_scroll(&mut font_system, false);
+        let indentation = Indentation {
+            tab_width: 8,
+            ..Default::default()
+        };
+
+        // Just left of the `y`, drawn after two tab stops
+        let (display, _) = expand_tabs(&buffer, 8, &mut font_system).unwrap();
+        let y_x = display
+            .layout_runs()
+            .next()
+            .unwrap()
+            .glyphs
+            .last()
+            .unwrap()
+            .x;
+        let (x, y) = text_point(
+            &buffer,
+            Some(&indentation),
+            &mut font_system,
+            (y_x as i32 + 1, 5),
+        );
+        assert_eq!(buffer.hit(x as f32, y as f32), Some(Cursor::new(0, 3)));
+
+        // Without tab expansion points are left alone
+        assert_eq!(text_point(&buffer, None, &mut font_system, (7, 5)), (7, 5));
+    }
+}
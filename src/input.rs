@@ -30,11 +30,12 @@ impl Plugin for InputPlugin {
         app.add_systems(PreUpdate, input_mouse.in_set(InputSet))
             .add_systems(
                 Update,
-                (kb_move_cursor, kb_input_text, kb_clipboard)
+                (kb_move_cursor, kb_input_text, kb_clipboard, kb_undo_redo)
                     .chain()
                     .in_set(InputSet),
             )
-            .insert_resource(ClickTimer(Timer::from_seconds(0.5, TimerMode::Once)));
+            .insert_resource(ClickTimer(Timer::from_seconds(0.5, TimerMode::Once)))
+            .init_resource::<CosmicKeymap>();
     }
 }
 
@@ -65,11 +66,17 @@ pub(crate) fn input_mouse(
     mut editor_q: Query<(
         &mut CosmicEditor,
         &GlobalTransform,
-        &CosmicTextAlign,
+        (&CosmicTextAlign, &CosmicWrap),
         Entity,
         &XOffset,
         &mut Sprite,
         Option<&ScrollDisabled>,
+        Option<&LineNumbers>,
+        Option<&Scrollbars>,
+        Option<&SmoothScroll>,
+        Option<&ContextMenu>,
+        Option<&TextDrag>,
+        Option<&Indentation>,
     )>,
     node_q: Query<(&Node, &GlobalTransform, &CosmicSource)>,
     mut font_system: ResMut<CosmicFontSystem>,
@@ -77,6 +84,7 @@ pub(crate) fn input_mouse(
     camera_q: Query<(&Camera, &GlobalTransform)>,
     mut click_timer: ResMut<ClickTimer>,
     mut click_count: Local<usize>,
+    mut scroll_remainder: Local<f32>,
     time: Res<Time>,
     evr_mouse_motion: EventReader<MouseMotion>,
 ) {
@@ -111,11 +119,17 @@ pub(crate) fn input_mouse(
     if let Ok((
         mut editor,
         sprite_transform,
-        text_position,
+        (text_position, mode),
         entity,
         x_offset,
         sprite,
         scroll_disabled,
+        numbers_opt,
+        scrollbars_opt,
+        smooth_opt,
+        menu_opt,
+        drag_opt,
+        indentation_opt,
     )) = editor_q.get_mut(active_editor_entity)
     {
         let buffer = editor.with_buffer(|b| b.clone());
@@ -137,6 +151,7 @@ pub(crate) fn input_mouse(
         }
 
         let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        let alt = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
 
         // if shift key is pressed
         let already_has_selection = editor.selection() != Selection::None;
@@ -145,17 +160,18 @@ pub(crate) fn input_mouse(
             editor.set_selection(Selection::Normal(cursor));
         }
 
-        let (padding_x, padding_y) = match text_position {
-            CosmicTextAlign::Center { padding: _ } => (
-                get_x_offset_center(width * scale_factor, &buffer),
-                get_y_offset_center(height * scale_factor, &buffer),
-            ),
-            CosmicTextAlign::TopLeft { padding } => (*padding, *padding),
-            CosmicTextAlign::Left { padding } => (
-                *padding,
-                get_y_offset_center(height * scale_factor, &buffer),
-            ),
-        };
+        // Same origin as the text is drawn at
+        let gutter = gutter_width(numbers_opt);
+        let origin = text_origin(
+            text_position,
+            mode,
+            Vec2::new(width, height) * scale_factor,
+            &buffer,
+            gutter,
+        );
+        // Text scrolled by part of a line is drawn that much higher
+        let partial = smooth_opt.map_or(0., |s| s.partial_line(buffer.metrics().line_height));
+        let (padding_x, padding_y) = (origin.x as i32, (origin.y - partial) as i32);
         let point = |node_cursor_pos: (f32, f32)| {
             (
                 (node_cursor_pos.0 * scale_factor) as i32 - padding_x,
@@ -163,6 +179,14 @@ pub(crate) fn input_mouse(
             )
         };
 
+        // Presses on a scrollbar, a context menu or dragged text do not move the cursor
+        if scrollbars_opt.is_some_and(|s| s.captures_pointer())
+            || menu_opt.is_some_and(|m| m.captures_pointer())
+            || drag_opt.is_some_and(|d| d.captures_pointer())
+        {
+            return;
+        }
+
         if buttons.just_pressed(MouseButton::Left) {
             editor.cursor_visible = true;
             editor.cursor_timer.reset();
@@ -177,9 +201,34 @@ pub(crate) fn input_mouse(
             ) {
                 let (mut x, y) = point(node_cursor_pos);
                 x += x_offset.left as i32;
+
+                // Clicking a line number selects the line
+                if node_cursor_pos.0 * scale_factor < gutter {
+                    if let Some(line) = gutter_line_at(&buffer, y as f32) {
+                        editor.clear_secondary_cursors();
+                        editor.set_selection(Selection::Normal(Cursor::new(line, 0)));
+                        let end = if line + 1 < buffer.lines.len() {
+                            Cursor::new(line + 1, 0)
+                        } else {
+                            Cursor::new(line, buffer.lines[line].text().len())
+                        };
+                        editor.set_cursor(end);
+                        editor.set_redraw(true);
+                    }
+                    return;
+                }
+
+                // Tabs are drawn wider than they are laid out
+                let (x, y) = text_point(&buffer, indentation_opt, &mut font_system.0, (x, y));
                 if shift {
                     editor.action(&mut font_system.0, Action::Drag { x, y });
+                } else if alt {
+                    if let Some(cursor) = editor.with_buffer(|b| b.hit(x as f32, y as f32)) {
+                        editor.add_cursor(cursor);
+                    }
                 } else {
+                    editor.clear_secondary_cursors();
+                    editor.mark_active = false;
                     match *click_count {
                         1 => {
                             editor.action(&mut font_system.0, Action::Click { x, y });
@@ -206,7 +255,7 @@ pub(crate) fn input_mouse(
             return;
         }
 
-        if buttons.pressed(MouseButton::Left) && *click_count == 0 {
+        if buttons.pressed(MouseButton::Left) && *click_count == 0 && !alt {
             if let Some(node_cursor_pos) = get_node_cursor_pos(
                 primary_window,
                 transform,
@@ -217,6 +266,7 @@ pub(crate) fn input_mouse(
             ) {
                 let (mut x, y) = point(node_cursor_pos);
                 x += x_offset.left as i32;
+                let (x, y) = text_point(&buffer, indentation_opt, &mut font_system.0, (x, y));
                 if active_editor.is_changed() && !shift {
                     editor.action(&mut font_system.0, Action::Click { x, y });
                 } else {
@@ -226,7 +276,8 @@ pub(crate) fn input_mouse(
             return;
         }
 
-        if scroll_disabled.is_none() {
+        // Smooth scrolling reads the wheel itself
+        if scroll_disabled.is_none() && smooth_opt.is_none() {
             for ev in scroll_evr.read() {
                 match ev.unit {
                     MouseScrollUnit::Line => {
@@ -238,13 +289,18 @@ pub(crate) fn input_mouse(
                         );
                     }
                     MouseScrollUnit::Pixel => {
+                        // Fractions of a line add up over events instead of being dropped
                         let line_height = buffer.metrics().line_height;
-                        editor.action(
-                            &mut font_system.0,
-                            Action::Scroll {
-                                lines: -(ev.y / line_height) as i32,
-                            },
-                        );
+                        let lines = *scroll_remainder - ev.y / line_height;
+                        *scroll_remainder = lines.fract();
+                        if lines.trunc() != 0. {
+                            editor.action(
+                                &mut font_system.0,
+                                Action::Scroll {
+                                    lines: lines.trunc() as i32,
+                                },
+                            );
+                        }
                     }
                 }
             }
@@ -255,36 +311,40 @@ pub(crate) fn input_mouse(
 pub fn kb_move_cursor(
     active_editor: Res<FocusedWidget>,
     keys: Res<ButtonInput<KeyCode>>,
-    mut cosmic_edit_query: Query<(&mut CosmicEditor,)>,
+    mut cosmic_edit_query: Query<(
+        &mut CosmicEditor,
+        Option<&CosmicKeymap>,
+        Option<&VimState>,
+        Option<&Autocomplete>,
+    )>,
+    keymap: Res<CosmicKeymap>,
+    menu_commands: Res<MenuCommands>,
     mut font_system: ResMut<CosmicFontSystem>,
 ) {
     let Some(active_editor_entity) = active_editor.0 else {
         return;
     };
-    if let Ok((mut editor,)) = cosmic_edit_query.get_mut(active_editor_entity) {
+    if let Ok((mut editor, keymap_opt, vim_state_opt, autocomplete_opt)) =
+        cosmic_edit_query.get_mut(active_editor_entity)
+    {
+        // Vim handles its own keys outside of insert mode
+        let vim_keys = vim_state_opt.is_some_and(|s| !s.is_insert());
+        let mut commands = if vim_keys {
+            Vec::new()
+        } else {
+            keymap_opt.unwrap_or(&*keymap).just_pressed(&keys)
+        };
+        // Chosen from a context menu
+        commands.extend(menu_commands.of(active_editor_entity));
+        if vim_keys && commands.is_empty() {
+            return;
+        }
+
         if keys.get_just_pressed().len() != 0 {
             editor.cursor_visible = true;
             editor.cursor_timer.reset();
         }
 
-        let command = keypress_command(&keys);
-
-        #[cfg(target_arch = "wasm32")]
-        let command = if web_sys::window()
-            .unwrap()
-            .navigator()
-            .user_agent()
-            .unwrap_or("NoUA".into())
-            .contains("Macintosh")
-        {
-            keys.any_pressed([KeyCode::SuperLeft, KeyCode::SuperRight])
-        } else {
-            command
-        };
-
-        #[cfg(target_os = "macos")]
-        let option = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
-
         let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
 
         // if shift key is pressed
@@ -294,106 +354,56 @@ pub fn kb_move_cursor(
             editor.set_selection(Selection::Normal(cursor));
         }
 
-        #[cfg(target_os = "macos")]
-        let should_jump = command && option;
-        #[cfg(not(target_os = "macos"))]
-        let should_jump = command;
+        // Up and Down move through the candidates of an open autocomplete popup
+        let completing = autocomplete_opt.is_some_and(|a| a.captures_keys());
 
-        if should_jump && keys.just_pressed(KeyCode::ArrowLeft) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::PreviousWord));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
-        }
-        if should_jump && keys.just_pressed(KeyCode::ArrowRight) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::NextWord));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
-        }
-        if should_jump && keys.just_pressed(KeyCode::Home) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::BufferStart));
-            if !shift {
-                editor.set_selection(Selection::None);
+        for command in commands {
+            if completing && matches!(command, EditorCommand::Up | EditorCommand::Down) {
+                continue;
             }
-            return;
-        }
-        if should_jump && keys.just_pressed(KeyCode::End) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::BufferEnd));
-            if !shift {
-                editor.set_selection(Selection::None);
+            if let Some(motion) = command.motion() {
+                let extend = shift || editor.mark_active;
+                editor.edit_all_cursors(&mut font_system.0, |editor, font_system| {
+                    if shift && editor.selection() == Selection::None {
+                        let cursor = editor.cursor();
+                        editor.set_selection(Selection::Normal(cursor));
+                    }
+                    editor.action(font_system, Action::Motion(motion));
+                    if !extend {
+                        editor.set_selection(Selection::None);
+                    }
+                });
+                return;
             }
-            return;
-        }
 
-        if keys.just_pressed(KeyCode::ArrowLeft) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::Left));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
-        }
-        if keys.just_pressed(KeyCode::ArrowRight) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::Right));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
-        }
-        if keys.just_pressed(KeyCode::ArrowUp) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::Up));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
-        }
-        if keys.just_pressed(KeyCode::ArrowDown) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::Down));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
-        }
-        if keys.just_pressed(KeyCode::Escape) {
-            editor.action(&mut font_system.0, Action::Escape);
-        }
-        if command && keys.just_pressed(KeyCode::KeyA) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::BufferEnd));
-            let current_cursor = editor.cursor();
-            editor.set_selection(Selection::Normal(Cursor {
-                line: 0,
-                index: 0,
-                affinity: current_cursor.affinity,
-            }));
-            return;
-        }
-        if keys.just_pressed(KeyCode::Home) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::Home));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
-        }
-        if keys.just_pressed(KeyCode::End) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::End));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
-        }
-        if keys.just_pressed(KeyCode::PageUp) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::PageUp));
-            if !shift {
-                editor.set_selection(Selection::None);
-            }
-            return;
-        }
-        if keys.just_pressed(KeyCode::PageDown) {
-            editor.action(&mut font_system.0, Action::Motion(Motion::PageDown));
-            if !shift {
-                editor.set_selection(Selection::None);
+            match command {
+                EditorCommand::Escape => {
+                    editor.mark_active = false;
+                    editor.clear_secondary_cursors();
+                    editor.action(&mut font_system.0, Action::Escape);
+                }
+                EditorCommand::SetMark => {
+                    let cursor = editor.cursor();
+                    editor.set_selection(Selection::Normal(cursor));
+                    editor.mark_active = true;
+                    return;
+                }
+                EditorCommand::AddNextOccurrence => {
+                    editor.add_next_occurrence(&mut font_system.0);
+                    return;
+                }
+                EditorCommand::SelectAll => {
+                    editor.clear_secondary_cursors();
+                    editor.action(&mut font_system.0, Action::Motion(Motion::BufferEnd));
+                    let current_cursor = editor.cursor();
+                    editor.set_selection(Selection::Normal(Cursor {
+                        line: 0,
+                        index: 0,
+                        affinity: current_cursor.affinity,
+                    }));
+                    return;
+                }
+                _ => {}
             }
         }
     }
@@ -410,8 +420,18 @@ pub(crate) fn kb_input_text(
         &MaxChars,
         Entity,
         Option<&ReadOnly>,
+        Option<&mut EditHistory>,
+        Option<&CosmicKeymap>,
+        Option<&VimState>,
+        Option<&Indentation>,
+        Option<&InputFilter>,
+        Option<&InputMask>,
+        Option<&Autocomplete>,
     )>,
+    keymap: Res<CosmicKeymap>,
+    menu_commands: Res<MenuCommands>,
     mut evw_changed: EventWriter<CosmicTextChanged>,
+    mut evw_rejected: EventWriter<CosmicInputRejected>,
     mut font_system: ResMut<CosmicFontSystem>,
     mut is_deleting: Local<bool>,
 ) {
@@ -419,9 +439,34 @@ pub(crate) fn kb_input_text(
         return;
     };
 
-    if let Ok((mut editor, buffer, max_lines, max_chars, entity, readonly_opt)) =
-        cosmic_edit_query.get_mut(active_editor_entity)
+    if let Ok((
+        mut editor,
+        buffer,
+        max_lines,
+        max_chars,
+        entity,
+        readonly_opt,
+        history_opt,
+        keymap_opt,
+        vim_state_opt,
+        indentation_opt,
+        filter_opt,
+        mask_opt,
+        autocomplete_opt,
+    )) = cosmic_edit_query.get_mut(active_editor_entity)
     {
+        // Outside of insert mode typed keys are vim commands, not text. Masked fields type
+        // into their slots instead.
+        let vim_keys = vim_state_opt.is_some_and(|s| !s.is_insert());
+        // Chosen from a context menu
+        let menu: Vec<EditorCommand> = menu_commands.of(entity).collect();
+        if vim_keys || mask_opt.is_some() {
+            char_evr.clear();
+            if mask_opt.is_some() || menu.is_empty() {
+                return;
+            }
+        }
+
         let command = keypress_command(&keys);
         if keys.get_just_pressed().len() != 0 {
             editor.cursor_visible = true;
@@ -429,58 +474,79 @@ pub(crate) fn kb_input_text(
         }
         let readonly = readonly_opt.is_some();
 
-        if keys.just_pressed(KeyCode::Backspace) & !readonly {
-            // fix for issue #8
-            let select = editor.selection();
-            match select {
-                Selection::Line(cursor) => {
-                    if editor.cursor().line == cursor.line && editor.cursor().index == cursor.index
-                    {
-                        editor.set_selection(Selection::None);
-                    }
-                }
-                Selection::Normal(cursor) => {
-                    if editor.cursor().line == cursor.line && editor.cursor().index == cursor.index
-                    {
-                        editor.set_selection(Selection::None);
-                    }
-                }
-                Selection::Word(cursor) => {
-                    if editor.cursor().line == cursor.line && editor.cursor().index == cursor.index
-                    {
-                        editor.set_selection(Selection::None);
-                    }
-                }
-                Selection::None => {}
-            }
+        if readonly {
+            return;
+        }
 
+        let before = (editor.cursor(), editor.selection());
+
+        let keymap = keymap_opt.unwrap_or(&*keymap);
+        let mut commands = if vim_keys {
+            Vec::new()
+        } else {
+            keymap.just_pressed(&keys)
+        };
+        commands.extend(menu);
+
+        // Tab and Enter accepted an autocomplete candidate
+        if autocomplete_opt.is_some_and(|a| a.captures_keys())
+            && commands
+                .iter()
+                .any(|c| matches!(c, EditorCommand::Newline | EditorCommand::Indent))
+        {
+            char_evr.clear();
+            return;
+        }
+
+        // Collected first, then applied at every cursor
+        let mut actions = Vec::new();
+
+        if commands.contains(&EditorCommand::Backspace) {
+            // Delete once now, key repeats arrive as character events while held
+            actions.push(Action::Backspace);
+            char_evr.clear();
             *is_deleting = true;
-            #[cfg(target_arch = "wasm32")]
-            editor.action(&mut font_system.0, Action::Backspace);
         }
 
-        if keys.just_released(KeyCode::Backspace) {
+        if keymap
+            .keys_for(EditorCommand::Backspace)
+            .any(|key| keys.just_released(key))
+        {
             *is_deleting = false;
         }
-        if keys.just_pressed(KeyCode::Delete) && !readonly {
-            editor.action(&mut font_system.0, Action::Delete);
-            editor.with_buffer_mut(|b| b.set_redraw(true));
+        if commands.contains(&EditorCommand::Delete) {
+            actions.push(Action::Delete);
         }
 
-        if readonly {
-            return;
+        let is_indent = commands.contains(&EditorCommand::Indent);
+        let is_unindent = commands.contains(&EditorCommand::Unindent) && indentation_opt.is_some();
+        if is_indent && indentation_opt.is_none() {
+            let text = filter_input(filter_opt, entity, "\t", &mut evw_rejected);
+            actions.extend(text.chars().map(Action::Insert));
         }
 
-        let mut is_edit = false;
+        let mut is_edit = is_indent
+            || is_unindent
+            || commands
+                .iter()
+                .any(|c| matches!(c, EditorCommand::Backspace | EditorCommand::Delete));
+        // Keys bound to other commands, like Emacs' Alt+F, may still produce characters
+        let is_bound = commands.iter().any(|c| {
+            !matches!(
+                c,
+                EditorCommand::Backspace | EditorCommand::Delete | EditorCommand::Newline
+            )
+        });
         let mut is_return = false;
-        if keys.just_pressed(KeyCode::Enter) {
+        if commands.contains(&EditorCommand::Newline) {
             is_return = true;
             if (max_lines.0 == 0 || buffer.lines.len() < max_lines.0)
                 && (max_chars.0 == 0 || buffer.get_text().len() < max_chars.0)
             {
                 // to have new line on wasm rather than E
-                is_edit = true;
-                editor.action(&mut font_system.0, Action::Insert('\n'));
+                let text = filter_input(filter_opt, entity, "\n", &mut evw_rejected);
+                is_edit |= !text.is_empty();
+                actions.extend(text.chars().map(Action::Insert));
             }
         }
 
@@ -488,17 +554,54 @@ pub(crate) fn kb_input_text(
             for char_ev in char_evr.read() {
                 is_edit = true;
                 if *is_deleting {
-                    editor.action(&mut font_system.0, Action::Backspace);
-                } else if !command && (max_chars.0 == 0 || buffer.get_text().len() < max_chars.0) {
-                    let b = char_ev.char.as_bytes();
+                    actions.push(Action::Backspace);
+                } else if !command
+                    && !is_bound
+                    && (max_chars.0 == 0 || buffer.get_text().len() < max_chars.0)
+                {
+                    let text = filter_input(filter_opt, entity, &char_ev.char, &mut evw_rejected);
+                    let b = text.as_bytes();
                     for c in b {
                         let c: char = (*c).into();
-                        editor.action(&mut font_system.0, Action::Insert(c));
+                        actions.push(Action::Insert(c));
                     }
                 }
             }
         }
 
+        if actions.is_empty() && !is_indent && !is_unindent {
+            return;
+        }
+
+        let change = editor.edit_all_cursors(&mut font_system.0, |editor, font_system| {
+            if let Some(indentation) = indentation_opt {
+                if is_indent {
+                    indent(editor, indentation);
+                }
+                if is_unindent {
+                    unindent(editor, indentation);
+                }
+            }
+
+            for action in actions.iter() {
+                if *action == Action::Backspace {
+                    clear_empty_selection(editor);
+                }
+                editor.action(font_system, *action);
+
+                if *action == Action::Insert('\n') && indentation_opt.is_some_and(|i| i.auto_indent)
+                {
+                    copy_indent(editor);
+                }
+            }
+        });
+        editor.with_buffer_mut(|b| b.set_redraw(true));
+        editor.mark_active = false;
+
+        if let (Some(change), Some(mut history)) = (change, history_opt) {
+            history.record(change, before, &editor);
+        }
+
         if !is_edit {
             return;
         }
@@ -522,73 +625,85 @@ pub fn kb_clipboard(
         &MaxChars,
         Entity,
         Option<&ReadOnly>,
+        Option<&mut EditHistory>,
+        Option<&CosmicKeymap>,
+        Option<&InputFilter>,
+        Option<&InputMask>,
+        &DefaultAttrs,
+        Option<&RichPaste>,
     )>,
+    keymap: Res<CosmicKeymap>,
+    mut evw_rejected: EventWriter<CosmicInputRejected>,
     _channel: Option<Res<WasmPasteAsyncChannel>>,
+    menu_commands: Res<MenuCommands>,
+    mut rich_clipboard: ResMut<RichClipboard>,
 ) {
     let Some(active_editor_entity) = active_editor.0 else {
         return;
     };
 
-    if let Ok((mut editor, buffer, max_lines, max_chars, entity, readonly_opt)) =
-        cosmic_edit_query.get_mut(active_editor_entity)
+    if let Ok((
+        mut editor,
+        buffer,
+        max_lines,
+        max_chars,
+        entity,
+        readonly_opt,
+        history_opt,
+        keymap_opt,
+        filter_opt,
+        mask_opt,
+        attrs,
+        rich_paste_opt,
+    )) = cosmic_edit_query.get_mut(active_editor_entity)
     {
-        let command = keypress_command(&keys);
+        let mut commands = keymap_opt.unwrap_or(&*keymap).just_pressed(&keys);
+        // Chosen from a context menu
+        commands.extend(menu_commands.of(entity));
 
         let readonly = readonly_opt.is_some();
+        // Masked fields cut and paste through their slots
+        let masked = mask_opt.is_some() && cfg!(not(target_arch = "wasm32"));
+
+        let before = (editor.cursor(), editor.selection());
 
-        let mut is_clipboard = false;
         #[cfg(not(target_arch = "wasm32"))]
-        {
-            if let Ok(mut clipboard) = arboard::Clipboard::new() {
-                if command && keys.just_pressed(KeyCode::KeyC) {
-                    if let Some(text) = editor.copy_selection() {
-                        clipboard.set_text(text).unwrap();
-                        return;
-                    }
-                }
-                if command && keys.just_pressed(KeyCode::KeyX) && !readonly {
-                    if let Some(text) = editor.copy_selection() {
-                        clipboard.set_text(text).unwrap();
-                        editor.delete_selection();
-                    }
-                    is_clipboard = true;
-                }
-                if command && keys.just_pressed(KeyCode::KeyV) && !readonly {
-                    if let Ok(text) = clipboard.get_text() {
-                        for c in text.chars() {
-                            if max_chars.0 == 0 || buffer.get_text().len() < max_chars.0 {
-                                if c == 0xA as char {
-                                    if max_lines.0 == 0 || buffer.lines.len() < max_lines.0 {
-                                        editor.action(&mut font_system.0, Action::Insert(c));
-                                    }
-                                } else {
-                                    editor.action(&mut font_system.0, Action::Insert(c));
-                                }
-                            }
-                        }
-                    }
-                    is_clipboard = true;
-                }
-            }
-        }
+        let (is_clipboard, change) = rich_clipboard.run_commands(
+            &commands,
+            &mut editor,
+            &buffer,
+            entity,
+            attrs,
+            rich_paste_opt,
+            filter_opt,
+            max_chars,
+            max_lines,
+            !readonly && !masked,
+            &mut font_system.0,
+            &mut evw_rejected,
+        );
 
+        #[cfg(target_arch = "wasm32")]
+        let (mut is_clipboard, mut change) = (false, None);
         #[cfg(target_arch = "wasm32")]
         {
-            if command && keys.just_pressed(KeyCode::KeyC) {
-                if let Some(text) = editor.copy_selection() {
+            if commands.contains(&EditorCommand::Copy) {
+                if let Some(text) = editor.copy_all_selections() {
                     write_clipboard_wasm(text.as_str());
                     return;
                 }
             }
 
-            if command && keys.just_pressed(KeyCode::KeyX) && !readonly {
-                if let Some(text) = editor.copy_selection() {
+            if commands.contains(&EditorCommand::Cut) && !readonly {
+                if let Some(text) = editor.copy_all_selections() {
                     write_clipboard_wasm(text.as_str());
-                    editor.delete_selection();
+                    change = editor.edit_all_cursors(&mut font_system.0, |editor, _| {
+                        editor.delete_selection();
+                    });
                 }
                 is_clipboard = true;
             }
-            if command && keys.just_pressed(KeyCode::KeyV) && !readonly {
+            if commands.contains(&EditorCommand::Paste) && !readonly {
                 let tx = _channel.unwrap().tx.clone();
                 let _task = AsyncComputeTaskPool::get().spawn(async move {
                     let promise = read_clipboard_wasm();
@@ -610,31 +725,95 @@ pub fn kb_clipboard(
             return;
         }
 
+        if let (Some(change), Some(mut history)) = (change, history_opt) {
+            history.seal();
+            history.record(change, before, &editor);
+            history.seal();
+        }
+
         evw_changed.send(CosmicTextChanged((entity, buffer.get_text())));
     }
 }
 
-fn keypress_command(keys: &ButtonInput<KeyCode>) -> bool {
-    #[cfg(target_os = "macos")]
-    let command = keys.any_pressed([KeyCode::SuperLeft, KeyCode::SuperRight]);
+pub(crate) fn kb_undo_redo(
+    active_editor: Res<FocusedWidget>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut evw_changed: EventWriter<CosmicTextChanged>,
+    mut cosmic_edit_query: Query<
+        (
+            &mut CosmicEditor,
+            &mut EditHistory,
+            Entity,
+            Option<&CosmicKeymap>,
+        ),
+        Without<ReadOnly>,
+    >,
+    keymap: Res<CosmicKeymap>,
+) {
+    let Some(active_editor_entity) = active_editor.0 else {
+        return;
+    };
 
-    #[cfg(not(target_os = "macos"))]
-    let command = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let Ok((mut editor, mut history, entity, keymap_opt)) =
+        cosmic_edit_query.get_mut(active_editor_entity)
+    else {
+        return;
+    };
 
-    #[cfg(target_arch = "wasm32")]
-    let command = if web_sys::window()
-        .unwrap()
-        .navigator()
-        .user_agent()
-        .unwrap_or("NoUA".into())
-        .contains("Macintosh")
-    {
+    let mut changed = false;
+    for command in keymap_opt.unwrap_or(&*keymap).just_pressed(&keys) {
+        match command {
+            EditorCommand::Undo => {
+                editor.clear_secondary_cursors();
+                changed |= history.undo(&mut editor);
+            }
+            EditorCommand::Redo => {
+                editor.clear_secondary_cursors();
+                changed |= history.redo(&mut editor);
+            }
+            _ => {}
+        }
+    }
+
+    if changed {
+        evw_changed.send(CosmicTextChanged((
+            entity,
+            editor.with_buffer(|b| b.get_text()),
+        )));
+    }
+}
+
+/// Drops a selection that is empty, so Backspace deletes a character instead of nothing.
+///
+/// Fix for issue #8
+fn clear_empty_selection(editor: &mut cosmic_text::Editor<'static>) {
+    match editor.selection() {
+        Selection::Line(cursor) | Selection::Normal(cursor) | Selection::Word(cursor) => {
+            if editor.cursor().line == cursor.line && editor.cursor().index == cursor.index {
+                editor.set_selection(Selection::None);
+            }
+        }
+        Selection::None => {}
+    }
+}
+
+pub(crate) fn keypress_command(keys: &ButtonInput<KeyCode>) -> bool {
+    if is_mac() {
         keys.any_pressed([KeyCode::SuperLeft, KeyCode::SuperRight])
     } else {
-        command
-    };
+        keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+    }
+}
 
-    command
+/// Writes `text` to the system clipboard
+pub(crate) fn write_clipboard_text(text: &str) {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Ok(mut clipboard) = arboard::Clipboard::new() {
+        let _ = clipboard.set_text(text.to_string());
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    write_clipboard_wasm(text);
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -669,31 +848,50 @@ pub fn poll_wasm_paste(
             &crate::DefaultAttrs,
             &MaxChars,
             &MaxChars,
+            Option<&mut EditHistory>,
+            Option<&InputFilter>,
         ),
         Without<ReadOnly>,
     >,
     mut evw_changed: EventWriter<CosmicTextChanged>,
+    mut evw_rejected: EventWriter<CosmicInputRejected>,
     mut font_system: ResMut<CosmicFontSystem>,
 ) {
     let inlet = channel.rx.try_recv();
     match inlet {
         Ok(inlet) => {
             let entity = inlet.entity;
-            if let Ok((mut editor, mut buffer, attrs, max_chars, max_lines)) =
-                editor_q.get_mut(entity)
+            if let Ok((
+                mut editor,
+                mut buffer,
+                attrs,
+                max_chars,
+                max_lines,
+                history_opt,
+                filter_opt,
+            )) = editor_q.get_mut(entity)
             {
-                let text = inlet.text;
+                let text = filter_input(filter_opt, entity, &inlet.text, &mut evw_rejected);
                 let attrs = &attrs.0;
-                for c in text.chars() {
-                    if max_chars.0 == 0 || buffer.get_text().len() < max_chars.0 {
-                        if c == 0xA as char {
-                            if max_lines.0 == 0 || buffer.lines.len() < max_lines.0 {
-                                editor.action(&mut font_system.0, Action::Insert(c));
+                let before = (editor.cursor(), editor.selection());
+                let change = editor.edit_all_cursors(&mut font_system.0, |editor, font_system| {
+                    for c in text.chars() {
+                        if max_chars.0 == 0 || buffer.get_text().len() < max_chars.0 {
+                            if c == 0xA as char {
+                                if max_lines.0 == 0 || buffer.lines.len() < max_lines.0 {
+                                    editor.action(font_system, Action::Insert(c));
+                                }
+                            } else {
+                                editor.action(font_system, Action::Insert(c));
                             }
-                        } else {
-                            editor.action(&mut font_system.0, Action::Insert(c));
                         }
                     }
+                });
+
+                if let (Some(change), Some(mut history)) = (change, history_opt) {
+                    history.seal();
+                    history.record(change, before, &editor);
+                    history.seal();
                 }
 
                 evw_changed.send(CosmicTextChanged((entity, buffer.get_text())));
@@ -0,0 +1,113 @@
+#![allow(clippy::too_many_arguments)]
+
+use crate::*;
+use bevy::prelude::*;
+use cosmic_text::Selection;
+
+/// Why [`insert_text`] left a widget as it was
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum NotInserted {
+    /// Nothing was left of the text after the widget's [`InputFilter`]
+    Empty,
+    /// The text would go over the widget's [`MaxChars`] or [`MaxLines`]
+    ExceedsLimits,
+}
+
+/// Inserts `text` into a widget, focused or not, as one undo step and returns the cursor at
+/// its end.
+///
+/// The text goes in at `at`, or else at the cursor of a focused widget or the start of an
+/// unfocused one. It is filtered first and only inserted while it fits the widget's limits. A
+/// widget showing its placeholder counts as empty and has it cleared.
+pub(crate) fn insert_text(
+    entity: Entity,
+    text: &str,
+    at: Option<Cursor>,
+    buffer: &mut CosmicBuffer,
+    mut editor_opt: Option<&mut CosmicEditor>,
+    history_opt: Option<&mut EditHistory>,
+    filter_opt: Option<&InputFilter>,
+    placeholder_opt: Option<&mut Placeholder>,
+    attrs: &DefaultAttrs,
+    max_chars: &MaxChars,
+    max_lines: &MaxLines,
+    font_system: &mut FontSystem,
+    evw_rejected: &mut EventWriter<CosmicInputRejected>,
+    evw_changed: &mut EventWriter<CosmicTextChanged>,
+) -> Result<Cursor, NotInserted> {
+    let text = filter_input(filter_opt, entity, text, evw_rejected);
+    if text.is_empty() {
+        return Err(NotInserted::Empty);
+    }
+    let placeholder_opt = placeholder_opt.filter(|p| p.is_active());
+    let current = match (&placeholder_opt, &editor_opt) {
+        (Some(_), _) => String::new(),
+        (None, Some(editor)) => editor.with_buffer(|b| b.get_text()),
+        (None, None) => buffer.get_text(),
+    };
+    if !fits_limits(&current, &text, max_chars, max_lines) {
+        return Err(NotInserted::ExceedsLimits);
+    }
+    // A widget showing its placeholder is empty
+    let at = match placeholder_opt {
+        Some(placeholder) => {
+            match editor_opt.as_deref_mut() {
+                Some(editor) => {
+                    editor.with_buffer_mut(|b| placeholder.clear(b, attrs, font_system))
+                }
+                None => placeholder.clear(&mut buffer.0, attrs, font_system),
+            }
+            Some(Cursor::new(0, 0))
+        }
+        None => at,
+    };
+
+    let end = match editor_opt {
+        Some(editor) => {
+            let at = at.unwrap_or_else(|| editor.cursor());
+            let before = (editor.cursor(), editor.selection());
+            editor.clear_secondary_cursors();
+            editor.start_change();
+            let end = editor.insert_at(at, &text, None);
+            editor.set_selection(Selection::None);
+            editor.set_cursor(end);
+            let change = editor.finish_change();
+            editor.shape_as_needed(font_system, false);
+            editor.set_redraw(true);
+            if let (Some(change), Some(history)) = (change, history_opt) {
+                history.seal();
+                history.record(change, before, editor);
+                history.seal();
+            }
+            evw_changed.send(CosmicTextChanged((
+                entity,
+                editor.with_buffer(|b| b.get_text()),
+            )));
+            end
+        }
+        None => {
+            // Unfocused widgets are edited through an editor of their own buffer
+            let at = at.unwrap_or(Cursor::new(0, 0));
+            let mut edit = Editor::new(buffer.0.clone());
+            edit.start_change();
+            let end = edit.insert_at(at, &text, None);
+            let change = edit.finish_change();
+            buffer.lines = edit.with_buffer(|b| b.lines.clone());
+            buffer.shape_until_scroll(font_system, false);
+            buffer.set_redraw(true);
+            if let (Some(change), Some(history)) = (change, history_opt) {
+                history.seal();
+                history.record_with(
+                    change,
+                    (at, Selection::None),
+                    (end, Selection::None),
+                    buffer.get_text(),
+                );
+                history.seal();
+            }
+            evw_changed.send(CosmicTextChanged((entity, buffer.get_text())));
+            end
+        }
+    };
+    Ok(end)
+}
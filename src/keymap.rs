@@ -0,0 +1,564 @@
+use bevy::{prelude::*, utils::HashMap};
+use cosmic_text::Motion;
+
+/// Named editor commands that can be bound to a [`KeyChord`] in a [`CosmicKeymap`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EditorCommand {
+    Left,
+    Right,
+    Up,
+    Down,
+    PreviousWord,
+    NextWord,
+    Home,
+    End,
+    BufferStart,
+    BufferEnd,
+    PageUp,
+    PageDown,
+    SelectAll,
+    Escape,
+    Backspace,
+    Delete,
+    Newline,
+    /// Insert an indent, or indent the selected lines
+    Indent,
+    /// Outdent the current or selected lines
+    Unindent,
+    Copy,
+    Cut,
+    Paste,
+    Undo,
+    Redo,
+    /// Select the next occurrence of the selection with an extra cursor
+    AddNextOccurrence,
+    /// Set the mark, motions extend the selection from it until it is cleared
+    SetMark,
+    /// Kill to the end of the line, or the line break when already there
+    KillLine,
+    /// Kill the selection
+    KillRegion,
+    /// Copy the selection to the kill ring
+    CopyRegion,
+    /// Insert the most recent kill
+    Yank,
+    /// Replace the text just yanked with the previous kill
+    YankPop,
+    /// Open the search bar, sends [`CosmicSearchOpened`]
+    Find,
+    /// Open the search bar with replacing, sends [`CosmicSearchOpened`]
+    Replace,
+    /// Select the next match of the [`CosmicSearch`] query
+    FindNext,
+    /// Select the previous match of the [`CosmicSearch`] query
+    FindPrevious,
+}
+
+impl EditorCommand {
+    /// The cursor [`Motion`] performed by this command, if it is a motion.
+    ///
+    /// Motions extend the selection when Shift is held, so they also trigger on their chord plus
+    /// Shift.
+    pub fn motion(&self) -> Option<Motion> {
+        match self {
+            EditorCommand::Left => Some(Motion::Left),
+            EditorCommand::Right => Some(Motion::Right),
+            EditorCommand::Up => Some(Motion::Up),
+            EditorCommand::Down => Some(Motion::Down),
+            EditorCommand::PreviousWord => Some(Motion::PreviousWord),
+            EditorCommand::NextWord => Some(Motion::NextWord),
+            EditorCommand::Home => Some(Motion::Home),
+            EditorCommand::End => Some(Motion::End),
+            EditorCommand::BufferStart => Some(Motion::BufferStart),
+            EditorCommand::BufferEnd => Some(Motion::BufferEnd),
+            EditorCommand::PageUp => Some(Motion::PageUp),
+            EditorCommand::PageDown => Some(Motion::PageDown),
+            _ => None,
+        }
+    }
+}
+
+/// A key plus the modifiers that must be held with it.
+///
+/// `command` is the platform command key: Cmd on macOS, Ctrl everywhere else. Keymaps bind it as
+/// that key, so a `command` chord and its Ctrl or Cmd chord are the same binding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub key: KeyCode,
+    pub command: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub super_key: bool,
+}
+
+impl KeyChord {
+    /// Chord for a key without modifiers
+    pub fn new(key: KeyCode) -> Self {
+        Self {
+            key,
+            command: false,
+            ctrl: false,
+            alt: false,
+            shift: false,
+            super_key: false,
+        }
+    }
+
+    /// Require the platform command key (Cmd on macOS, Ctrl elsewhere)
+    pub fn command(mut self) -> Self {
+        self.command = true;
+        self
+    }
+
+    pub fn ctrl(mut self) -> Self {
+        self.ctrl = true;
+        self
+    }
+
+    pub fn alt(mut self) -> Self {
+        self.alt = true;
+        self
+    }
+
+    pub fn shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+
+    pub fn super_key(mut self) -> Self {
+        self.super_key = true;
+        self
+    }
+
+    /// This chord with `command` resolved to Ctrl or Super for the current platform, which is
+    /// how chords are bound and looked up
+    fn resolved(self) -> Self {
+        let mac = is_mac();
+        Self {
+            command: false,
+            ctrl: self.ctrl || (self.command && !mac),
+            super_key: self.super_key || (self.command && mac),
+            ..self
+        }
+    }
+}
+
+impl From<KeyCode> for KeyChord {
+    fn from(key: KeyCode) -> Self {
+        KeyChord::new(key)
+    }
+}
+
+/// Maps [`KeyChord`]s to [`EditorCommand`]s for focused editors.
+///
+/// Used as a global [`Resource`], and as a [`Component`] on a widget to override the global map
+/// for that widget only.
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use bevy_cosmic_edit::*;
+/// let mut keymap = CosmicKeymap::default();
+/// // Disable pasting, and move to the start of the buffer on Ctrl+Shift+U
+/// keymap.unbind_command(EditorCommand::Paste);
+/// keymap.bind(
+///     KeyChord::new(KeyCode::KeyU).ctrl().shift(),
+///     EditorCommand::BufferStart,
+/// );
+/// ```
+#[derive(Resource, Component, Clone, Debug)]
+pub struct CosmicKeymap {
+    bindings: HashMap<KeyChord, EditorCommand>,
+}
+
+impl Default for CosmicKeymap {
+    fn default() -> Self {
+        if is_mac() {
+            Self::macos()
+        } else if cfg!(target_os = "windows") {
+            Self::windows()
+        } else {
+            Self::linux()
+        }
+    }
+}
+
+impl CosmicKeymap {
+    /// Keymap without any bindings
+    pub fn empty() -> Self {
+        Self {
+            bindings: HashMap::default(),
+        }
+    }
+
+    /// Default bindings for macOS
+    pub fn macos() -> Self {
+        let mut keymap = Self::common();
+        keymap
+            .bind(
+                KeyChord::new(KeyCode::ArrowLeft).super_key().alt(),
+                EditorCommand::PreviousWord,
+            )
+            .bind(
+                KeyChord::new(KeyCode::ArrowRight).super_key().alt(),
+                EditorCommand::NextWord,
+            )
+            .bind(
+                KeyChord::new(KeyCode::Home).super_key().alt(),
+                EditorCommand::BufferStart,
+            )
+            .bind(
+                KeyChord::new(KeyCode::End).super_key().alt(),
+                EditorCommand::BufferEnd,
+            )
+            .bind(
+                KeyChord::new(KeyCode::KeyA).super_key(),
+                EditorCommand::SelectAll,
+            )
+            .bind(
+                KeyChord::new(KeyCode::KeyC).super_key(),
+                EditorCommand::Copy,
+            )
+            .bind(KeyChord::new(KeyCode::KeyX).super_key(), EditorCommand::Cut)
+            .bind(
+                KeyChord::new(KeyCode::KeyV).super_key(),
+                EditorCommand::Paste,
+            )
+            .bind(
+                KeyChord::new(KeyCode::KeyZ).super_key(),
+                EditorCommand::Undo,
+            )
+            .bind(
+                KeyChord::new(KeyCode::KeyZ).super_key().shift(),
+                EditorCommand::Redo,
+            )
+            .bind(
+                KeyChord::new(KeyCode::KeyY).super_key(),
+                EditorCommand::Redo,
+            )
+            .bind(
+                KeyChord::new(KeyCode::KeyD).super_key(),
+                EditorCommand::AddNextOccurrence,
+            )
+            .bind(
+                KeyChord::new(KeyCode::KeyF).super_key(),
+                EditorCommand::Find,
+            )
+            .bind(
+                KeyChord::new(KeyCode::KeyF).super_key().alt(),
+                EditorCommand::Replace,
+            );
+        keymap
+    }
+
+    /// Default bindings for Windows
+    pub fn windows() -> Self {
+        Self::linux()
+    }
+
+    /// Default bindings for Linux
+    pub fn linux() -> Self {
+        let mut keymap = Self::common();
+        keymap
+            .bind(
+                KeyChord::new(KeyCode::ArrowLeft).ctrl(),
+                EditorCommand::PreviousWord,
+            )
+            .bind(
+                KeyChord::new(KeyCode::ArrowRight).ctrl(),
+                EditorCommand::NextWord,
+            )
+            .bind(
+                KeyChord::new(KeyCode::Home).ctrl(),
+                EditorCommand::BufferStart,
+            )
+            .bind(KeyChord::new(KeyCode::End).ctrl(), EditorCommand::BufferEnd)
+            .bind(
+                KeyChord::new(KeyCode::KeyA).ctrl(),
+                EditorCommand::SelectAll,
+            )
+            .bind(KeyChord::new(KeyCode::KeyC).ctrl(), EditorCommand::Copy)
+            .bind(KeyChord::new(KeyCode::KeyX).ctrl(), EditorCommand::Cut)
+            .bind(KeyChord::new(KeyCode::KeyV).ctrl(), EditorCommand::Paste)
+            .bind(KeyChord::new(KeyCode::KeyZ).ctrl(), EditorCommand::Undo)
+            .bind(
+                KeyChord::new(KeyCode::KeyZ).ctrl().shift(),
+                EditorCommand::Redo,
+            )
+            .bind(KeyChord::new(KeyCode::KeyY).ctrl(), EditorCommand::Redo)
+            .bind(
+                KeyChord::new(KeyCode::KeyD).ctrl(),
+                EditorCommand::AddNextOccurrence,
+            )
+            .bind(KeyChord::new(KeyCode::KeyF).ctrl(), EditorCommand::Find)
+            .bind(KeyChord::new(KeyCode::KeyH).ctrl(), EditorCommand::Replace);
+        keymap
+    }
+
+    /// Emacs style bindings, with kills and yanks going through the [`KillRing`]
+    pub fn emacs() -> Self {
+        let mut keymap = Self::common();
+        keymap
+            .bind(KeyChord::new(KeyCode::KeyA).ctrl(), EditorCommand::Home)
+            .bind(KeyChord::new(KeyCode::KeyE).ctrl(), EditorCommand::End)
+            .bind(KeyChord::new(KeyCode::KeyF).ctrl(), EditorCommand::Right)
+            .bind(KeyChord::new(KeyCode::KeyB).ctrl(), EditorCommand::Left)
+            .bind(KeyChord::new(KeyCode::KeyN).ctrl(), EditorCommand::Down)
+            .bind(KeyChord::new(KeyCode::KeyP).ctrl(), EditorCommand::Up)
+            .bind(KeyChord::new(KeyCode::KeyF).alt(), EditorCommand::NextWord)
+            .bind(
+                KeyChord::new(KeyCode::KeyB).alt(),
+                EditorCommand::PreviousWord,
+            )
+            .bind(
+                KeyChord::new(KeyCode::Comma).alt().shift(),
+                EditorCommand::BufferStart,
+            )
+            .bind(
+                KeyChord::new(KeyCode::Period).alt().shift(),
+                EditorCommand::BufferEnd,
+            )
+            .bind(KeyChord::new(KeyCode::KeyV).ctrl(), EditorCommand::PageDown)
+            .bind(KeyChord::new(KeyCode::KeyV).alt(), EditorCommand::PageUp)
+            .bind(KeyChord::new(KeyCode::KeyD).ctrl(), EditorCommand::Delete)
+            .bind(KeyChord::new(KeyCode::KeyG).ctrl(), EditorCommand::Escape)
+            .bind(KeyChord::new(KeyCode::Space).ctrl(), EditorCommand::SetMark)
+            .bind(KeyChord::new(KeyCode::KeyK).ctrl(), EditorCommand::KillLine)
+            .bind(
+                KeyChord::new(KeyCode::KeyW).ctrl(),
+                EditorCommand::KillRegion,
+            )
+            .bind(
+                KeyChord::new(KeyCode::KeyW).alt(),
+                EditorCommand::CopyRegion,
+            )
+            .bind(KeyChord::new(KeyCode::KeyY).ctrl(), EditorCommand::Yank)
+            .bind(KeyChord::new(KeyCode::KeyY).alt(), EditorCommand::YankPop)
+            .bind(KeyChord::new(KeyCode::Slash).ctrl(), EditorCommand::Undo)
+            .bind(
+                KeyChord::new(KeyCode::Minus).ctrl().shift(),
+                EditorCommand::Undo,
+            );
+        keymap
+    }
+
+    /// Bindings shared by every platform
+    fn common() -> Self {
+        let mut keymap = Self::empty();
+        keymap
+            .bind(KeyCode::ArrowLeft, EditorCommand::Left)
+            .bind(KeyCode::ArrowRight, EditorCommand::Right)
+            .bind(KeyCode::ArrowUp, EditorCommand::Up)
+            .bind(KeyCode::ArrowDown, EditorCommand::Down)
+            .bind(KeyCode::Home, EditorCommand::Home)
+            .bind(KeyCode::End, EditorCommand::End)
+            .bind(KeyCode::PageUp, EditorCommand::PageUp)
+            .bind(KeyCode::PageDown, EditorCommand::PageDown)
+            .bind(KeyCode::Escape, EditorCommand::Escape)
+            .bind(KeyCode::Backspace, EditorCommand::Backspace)
+            .bind(KeyCode::Delete, EditorCommand::Delete)
+            .bind(KeyCode::Enter, EditorCommand::Newline)
+            // Held modifiers do not stop editing keys, as with plain text input
+            .bind(
+                KeyChord::new(KeyCode::Backspace).shift(),
+                EditorCommand::Backspace,
+            )
+            .bind(
+                KeyChord::new(KeyCode::Backspace).ctrl(),
+                EditorCommand::Backspace,
+            )
+            .bind(
+                KeyChord::new(KeyCode::Delete).shift(),
+                EditorCommand::Delete,
+            )
+            .bind(KeyChord::new(KeyCode::Delete).ctrl(), EditorCommand::Delete)
+            .bind(
+                KeyChord::new(KeyCode::Enter).shift(),
+                EditorCommand::Newline,
+            )
+            .bind(KeyCode::Tab, EditorCommand::Indent)
+            .bind(KeyChord::new(KeyCode::Tab).shift(), EditorCommand::Unindent)
+            .bind(KeyCode::F3, EditorCommand::FindNext)
+            .bind(
+                KeyChord::new(KeyCode::F3).shift(),
+                EditorCommand::FindPrevious,
+            );
+        keymap
+    }
+
+    /// Binds `chord` to `command`, replacing any previous binding of `chord`
+    pub fn bind(&mut self, chord: impl Into<KeyChord>, command: EditorCommand) -> &mut Self {
+        self.bindings.insert(chord.into().resolved(), command);
+        self
+    }
+
+    /// Builder version of [`CosmicKeymap::bind`]
+    pub fn with_binding(mut self, chord: impl Into<KeyChord>, command: EditorCommand) -> Self {
+        self.bind(chord, command);
+        self
+    }
+
+    /// Removes the binding for `chord`
+    pub fn unbind(&mut self, chord: impl Into<KeyChord>) -> &mut Self {
+        self.bindings.remove(&chord.into().resolved());
+        self
+    }
+
+    /// Removes every chord bound to `command`
+    pub fn unbind_command(&mut self, command: EditorCommand) -> &mut Self {
+        self.bindings.retain(|_, c| *c != command);
+        self
+    }
+
+    /// Command bound to `chord`, if any
+    pub fn get(&self, chord: impl Into<KeyChord>) -> Option<EditorCommand> {
+        self.bindings.get(&chord.into().resolved()).copied()
+    }
+
+    /// Keys of all chords bound to `command`
+    pub fn keys_for(&self, command: EditorCommand) -> impl Iterator<Item = KeyCode> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, c)| **c == command)
+            .map(|(chord, _)| chord.key)
+    }
+
+    /// Iterates over all bindings
+    pub fn bindings(&self) -> impl Iterator<Item = (&KeyChord, &EditorCommand)> {
+        self.bindings.iter()
+    }
+
+    /// Commands triggered by keys pressed this frame.
+    ///
+    /// Chords match when exactly their modifiers are held. Motions also match with Shift added,
+    /// which extends the selection.
+    pub fn just_pressed(&self, keys: &ButtonInput<KeyCode>) -> Vec<EditorCommand> {
+        let mut commands = Vec::new();
+        for key in keys.get_just_pressed() {
+            let chord = KeyChord {
+                key: *key,
+                command: false,
+                ctrl: keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]),
+                alt: keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]),
+                shift: keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]),
+                super_key: keys.any_pressed([KeyCode::SuperLeft, KeyCode::SuperRight]),
+            };
+            let found = self.bindings.get(&chord).copied().or_else(|| {
+                if !chord.shift {
+                    return None;
+                }
+                let unshifted = KeyChord {
+                    shift: false,
+                    ..chord
+                };
+                self.bindings
+                    .get(&unshifted)
+                    .copied()
+                    .filter(|command| command.motion().is_some())
+            });
+
+            if let Some(command) = found {
+                commands.push(command);
+            }
+        }
+        commands
+    }
+}
+
+/// Whether the command key is Cmd rather than Ctrl
+pub(crate) fn is_mac() -> bool {
+    #[cfg(target_arch = "wasm32")]
+    return web_sys::window()
+        .unwrap()
+        .navigator()
+        .user_agent()
+        .unwrap_or("NoUA".into())
+        .contains("Macintosh");
+
+    #[cfg(not(target_arch = "wasm32"))]
+    return cfg!(target_os = "macos");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn motions_match_with_shift() {
+        let keymap = CosmicKeymap::linux();
+        let mut keys = ButtonInput::<KeyCode>::default();
+        keys.press(KeyCode::ShiftLeft);
+        keys.press(KeyCode::ControlLeft);
+        keys.press(KeyCode::ArrowLeft);
+        assert_eq!(
+            keymap.just_pressed(&keys),
+            vec![EditorCommand::PreviousWord]
+        );
+
+        let mut keys = ButtonInput::<KeyCode>::default();
+        keys.press(KeyCode::ShiftLeft);
+        keys.press(KeyCode::ControlLeft);
+        keys.press(KeyCode::KeyZ);
+        assert_eq!(keymap.just_pressed(&keys), vec![EditorCommand::Redo]);
+
+        // Other commands do not pick up an unbound Shift
+        let keymap = keymap.with_binding(KeyCode::F5, EditorCommand::Copy);
+        let mut keys = ButtonInput::<KeyCode>::default();
+        keys.press(KeyCode::ShiftLeft);
+        keys.press(KeyCode::F5);
+        assert!(keymap.just_pressed(&keys).is_empty());
+    }
+
+    #[test]
+    fn editing_keys_match_with_modifiers() {
+        let keymap = CosmicKeymap::linux();
+        for (modifier, key, command) in [
+            (KeyCode::ShiftLeft, KeyCode::Enter, EditorCommand::Newline),
+            (
+                KeyCode::ShiftLeft,
+                KeyCode::Backspace,
+                EditorCommand::Backspace,
+            ),
+            (
+                KeyCode::ControlLeft,
+                KeyCode::Backspace,
+                EditorCommand::Backspace,
+            ),
+            (KeyCode::ShiftRight, KeyCode::Delete, EditorCommand::Delete),
+            (
+                KeyCode::ControlRight,
+                KeyCode::Delete,
+                EditorCommand::Delete,
+            ),
+        ] {
+            let mut keys = ButtonInput::<KeyCode>::default();
+            keys.press(modifier);
+            keys.press(key);
+            assert_eq!(keymap.just_pressed(&keys), vec![command]);
+        }
+    }
+
+    #[test]
+    fn command_chords_replace_platform_chords() {
+        let mut keymap = CosmicKeymap::default();
+        keymap.bind(KeyChord::new(KeyCode::KeyC).command(), EditorCommand::Cut);
+        assert_eq!(keymap.keys_for(EditorCommand::Copy).count(), 0);
+        assert_eq!(
+            keymap.get(KeyChord::new(KeyCode::KeyC).command()),
+            Some(EditorCommand::Cut)
+        );
+
+        let command_key = if is_mac() {
+            KeyCode::SuperLeft
+        } else {
+            KeyCode::ControlLeft
+        };
+        let mut keys = ButtonInput::<KeyCode>::default();
+        keys.press(command_key);
+        keys.press(KeyCode::KeyC);
+        assert_eq!(keymap.just_pressed(&keys), vec![EditorCommand::Cut]);
+
+        keymap.unbind(KeyChord::new(KeyCode::KeyC).command());
+        assert!(keymap.just_pressed(&keys).is_empty());
+    }
+}
@@ -0,0 +1,368 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use crate::*;
+use bevy::prelude::*;
+use cosmic_text::{Change, Cursor, Edit, Selection};
+use std::collections::VecDeque;
+
+pub(crate) struct KillRingPlugin;
+
+impl Plugin for KillRingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KillRing>()
+            .add_systems(Update, kb_kill_ring.in_set(InputSet).after(kb_clipboard));
+    }
+}
+
+/// Text killed with [`EditorCommand::KillLine`], [`EditorCommand::KillRegion`] and
+/// [`EditorCommand::CopyRegion`], shared by all editors.
+///
+/// Kills are also written to the system clipboard, and text copied elsewhere is added to the
+/// ring when yanking. Bound by [`CosmicKeymap::emacs`].
+#[derive(Resource, Debug)]
+pub struct KillRing {
+    /// Maximum number of kills kept
+    pub max_entries: usize,
+    /// Most recent kill first
+    entries: VecDeque<String>,
+    /// Editor and cursor after the last kill, so consecutive kills are joined
+    last_kill: Option<(Entity, Cursor)>,
+    /// Editor, range and ring position of the last yank, for yank-pop
+    last_yank: Option<(Entity, Cursor, Cursor, usize)>,
+}
+
+impl Default for KillRing {
+    fn default() -> Self {
+        Self {
+            max_entries: 60,
+            entries: VecDeque::new(),
+            last_kill: None,
+            last_yank: None,
+        }
+    }
+}
+
+impl KillRing {
+    /// Kills, most recent first
+    pub fn entries(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(String::as_str)
+    }
+
+    /// Adds a kill, or appends it to the previous one if `append` is set
+    pub fn push(&mut self, text: String, append: bool) {
+        match self.entries.front_mut() {
+            Some(front) if append => front.push_str(&text),
+            _ => {
+                self.entries.push_front(text);
+                self.entries.truncate(self.max_entries.max(1));
+            }
+        }
+    }
+
+    /// Adds text killed from `start` in `entity`, joined to the previous kill if that ended there
+    fn kill(&mut self, entity: Entity, start: Cursor, text: String, end: Cursor) {
+        let append = self.last_kill == Some((entity, start));
+        self.push(text, append);
+        self.last_kill = Some((entity, end));
+    }
+
+    /// Inserts the latest kill at the cursor
+    fn yank(&mut self, entity: Entity, editor: &mut CosmicEditor, max_chars: &MaxChars) -> bool {
+        let Some(text) = self.entries.front().cloned() else {
+            return false;
+        };
+        editor.clear_secondary_cursors();
+        editor.delete_selection();
+        editor.mark_active = false;
+
+        let text = limit_chars(editor, &text, max_chars);
+        let start = editor.cursor();
+        editor.insert_string(&text, None);
+        self.last_yank = Some((entity, start, editor.cursor(), 0));
+        true
+    }
+
+    /// Replaces the text just yanked with the next older kill
+    fn yank_pop(
+        &mut self,
+        entity: Entity,
+        editor: &mut CosmicEditor,
+        max_chars: &MaxChars,
+    ) -> bool {
+        // Only directly after a yank, with the cursor still behind the yanked text
+        let last_yank = self
+            .last_yank
+            .filter(|(e, _, end, _)| *e == entity && editor.cursor() == *end);
+        let Some((_, start, end, index)) = last_yank else {
+            return false;
+        };
+        let index = (index + 1) % self.entries.len().max(1);
+        let text = self.entries[index].clone();
+
+        editor.delete_range(start, end);
+        editor.set_cursor(start);
+        let text = limit_chars(editor, &text, max_chars);
+        editor.insert_string(&text, None);
+        self.last_yank = Some((entity, start, editor.cursor(), index));
+        true
+    }
+}
+
+fn kb_kill_ring(
+    active_editor: Res<FocusedWidget>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut char_evr: EventReader<ReceivedCharacter>,
+    mut kill_ring: ResMut<KillRing>,
+    mut cosmic_edit_query: Query<(
+        &mut CosmicEditor,
+        Option<&mut EditHistory>,
+        Option<&CosmicKeymap>,
+        Option<&ReadOnly>,
+        &MaxChars,
+    )>,
+    keymap: Res<CosmicKeymap>,
+    mut evw_changed: EventWriter<CosmicTextChanged>,
+    mut font_system: ResMut<CosmicFontSystem>,
+) {
+    let Some(active_editor_entity) = active_editor.0 else {
+        return;
+    };
+    let Ok((mut editor, history_opt, keymap_opt, readonly_opt, max_chars)) =
+        cosmic_edit_query.get_mut(active_editor_entity)
+    else {
+        return;
+    };
+
+    let commands = keymap_opt.unwrap_or(&*keymap).just_pressed(&keys);
+    // Typing in between starts a new kill
+    if char_evr.read().count() > 0 {
+        kill_ring.last_kill = None;
+    }
+    let readonly = readonly_opt.is_some();
+
+    let before = (editor.cursor(), editor.selection());
+    let mut change = Change::default();
+
+    for command in commands {
+        // Only consecutive kills are joined
+        if command != EditorCommand::KillLine {
+            kill_ring.last_kill = None;
+        }
+        if !matches!(
+            command,
+            EditorCommand::KillRegion | EditorCommand::CopyRegion
+        ) {
+            editor.start_change();
+        }
+
+        match command {
+            EditorCommand::KillLine if !readonly => {
+                editor.clear_secondary_cursors();
+                let cursor = editor.cursor();
+                let end = editor.with_buffer(|b| {
+                    let len = b.lines[cursor.line].text().len();
+                    if cursor.index < len {
+                        Some(Cursor::new(cursor.line, len))
+                    } else if cursor.line + 1 < b.lines.len() {
+                        Some(Cursor::new(cursor.line + 1, 0))
+                    } else {
+                        None
+                    }
+                });
+                let Some(end) = end else {
+                    editor.finish_change();
+                    continue;
+                };
+
+                editor.set_selection(Selection::Normal(end));
+                let text = editor.copy_selection().unwrap_or_default();
+                editor.delete_selection();
+
+                kill_ring.kill(active_editor_entity, cursor, text, editor.cursor());
+                write_clipboard(&kill_ring);
+            }
+            EditorCommand::KillRegion | EditorCommand::CopyRegion => {
+                let kill = command == EditorCommand::KillRegion;
+                if kill && readonly {
+                    continue;
+                }
+                let Some(text) = editor.copy_all_selections() else {
+                    continue;
+                };
+
+                kill_ring.push(text, false);
+                write_clipboard(&kill_ring);
+                editor.mark_active = false;
+
+                if kill {
+                    let killed = editor.edit_all_cursors(&mut font_system.0, |editor, _| {
+                        editor.delete_selection();
+                    });
+                    change
+                        .items
+                        .extend(killed.into_iter().flat_map(|c| c.items));
+                } else {
+                    editor.set_selection(Selection::None);
+                }
+                continue;
+            }
+            EditorCommand::Yank if !readonly => {
+                read_clipboard(&mut kill_ring);
+                kill_ring.yank(active_editor_entity, &mut editor, max_chars);
+            }
+            EditorCommand::YankPop if !readonly => {
+                kill_ring.yank_pop(active_editor_entity, &mut editor, max_chars);
+            }
+            _ => {}
+        }
+
+        if let Some(c) = editor.finish_change() {
+            change.items.extend(c.items);
+        }
+    }
+
+    if change.items.is_empty() {
+        return;
+    }
+
+    if let Some(mut history) = history_opt {
+        history.seal();
+        history.record(change, before, &editor);
+        history.seal();
+    }
+
+    editor.set_redraw(true);
+    evw_changed.send(CosmicTextChanged((
+        active_editor_entity,
+        editor.with_buffer(|b| b.get_text()),
+    )));
+}
+
+/// Shortens `text` so the buffer stays within [`MaxChars`]
+fn limit_chars(editor: &CosmicEditor, text: &str, max_chars: &MaxChars) -> String {
+    if max_chars.0 == 0 {
+        return text.to_string();
+    }
+    let len = editor.with_buffer(|b| b.get_text().len());
+    text.chars()
+        .scan(len, |len, c| {
+            *len += c.len_utf8();
+            (*len <= max_chars.0).then_some(c)
+        })
+        .collect()
+}
+
+fn write_clipboard(kill_ring: &KillRing) {
+    if let Some(text) = kill_ring.entries.front() {
+        write_clipboard_text(text);
+    }
+}
+
+/// Adds text copied outside of the ring, unless it is already the latest kill
+fn read_clipboard(_kill_ring: &mut KillRing) {
+    // Reading the clipboard is async on wasm, only kills from the ring are yanked there
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Ok(text) = arboard::Clipboard::new().and_then(|mut c| c.get_text()) {
+        if !text.is_empty() && _kill_ring.entries.front() != Some(&text) {
+            _kill_ring.push(text, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmic_text::{Attrs, Buffer, Editor, Metrics, Shaping};
+
+    fn editor(text: &str) -> CosmicEditor {
+        let mut font_system = create_cosmic_font_system(CosmicFontConfig {
+            load_system_fonts: false,
+            ..Default::default()
+        });
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(20., 20.));
+        buffer.set_text(&mut font_system, text, Attrs::new(), Shaping::Advanced);
+        CosmicEditor::new(Editor::new(buffer))
+    }
+
+    fn text(editor: &CosmicEditor) -> String {
+        editor.with_buffer(|b| b.get_text())
+    }
+
+    #[test]
+    fn consecutive_kills_are_joined() {
+        let entity = Entity::from_raw(0);
+        let mut kill_ring = KillRing::default();
+        kill_ring.kill(entity, Cursor::new(0, 0), "foo".into(), Cursor::new(0, 0));
+        kill_ring.kill(entity, Cursor::new(0, 0), "\n".into(), Cursor::new(0, 0));
+        assert_eq!(kill_ring.entries().collect::<Vec<_>>(), ["foo\n"]);
+
+        // Elsewhere, or in another editor
+        kill_ring.kill(entity, Cursor::new(1, 0), "bar".into(), Cursor::new(1, 0));
+        kill_ring.kill(
+            Entity::from_raw(1),
+            Cursor::new(1, 0),
+            "baz".into(),
+            Cursor::new(1, 0),
+        );
+        assert_eq!(
+            kill_ring.entries().collect::<Vec<_>>(),
+            ["baz", "bar", "foo\n"]
+        );
+
+        // After another command
+        kill_ring.last_kill = None;
+        kill_ring.kill(
+            Entity::from_raw(1),
+            Cursor::new(1, 0),
+            "qux".into(),
+            Cursor::new(1, 0),
+        );
+        assert_eq!(kill_ring.entries().next(), Some("qux"));
+    }
+
+    #[test]
+    fn ring_is_bounded() {
+        let mut kill_ring = KillRing {
+            max_entries: 2,
+            ..default()
+        };
+        for text in ["a", "b", "c"] {
+            kill_ring.push(text.into(), false);
+        }
+        assert_eq!(kill_ring.entries().collect::<Vec<_>>(), ["c", "b"]);
+    }
+
+    #[test]
+    fn yank_and_yank_pop() {
+        let entity = Entity::from_raw(0);
+        let mut editor = editor("foo ");
+        editor.set_cursor(Cursor::new(0, 4));
+        let mut kill_ring = KillRing::default();
+        assert!(!kill_ring.yank(entity, &mut editor, &MaxChars(0)));
+
+        kill_ring.push("bar".into(), false);
+        kill_ring.push("baz".into(), false);
+        assert!(!kill_ring.yank_pop(entity, &mut editor, &MaxChars(0)));
+
+        assert!(kill_ring.yank(entity, &mut editor, &MaxChars(0)));
+        assert_eq!(text(&editor), "foo baz");
+        assert!(kill_ring.yank_pop(entity, &mut editor, &MaxChars(0)));
+        assert_eq!(text(&editor), "foo bar");
+        assert!(kill_ring.yank_pop(entity, &mut editor, &MaxChars(0)));
+        assert_eq!(text(&editor), "foo baz");
+
+        // Not once the cursor has moved away
+        editor.set_cursor(Cursor::new(0, 0));
+        assert!(!kill_ring.yank_pop(entity, &mut editor, &MaxChars(0)));
+    }
+
+    #[test]
+    fn yank_respects_max_chars() {
+        let mut editor = editor("foo");
+        editor.set_cursor(Cursor::new(0, 3));
+        let mut kill_ring = KillRing::default();
+        kill_ring.push("barbaz".into(), false);
+        kill_ring.yank(Entity::from_raw(0), &mut editor, &MaxChars(6));
+        assert_eq!(text(&editor), "foobar");
+    }
+}
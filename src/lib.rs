@@ -82,24 +82,50 @@
 //! MIT or Apache-2.0
 #![allow(clippy::type_complexity)]
 
+mod autocomplete;
 mod buffer;
+mod context_menu;
 mod cosmic_edit;
 mod cursor;
 mod events;
+mod file_drop;
+mod filter;
 mod focus;
+mod gutter;
+mod highlight;
+mod history;
+mod ime;
+mod indent;
 mod input;
+mod insert;
+mod keymap;
+mod kill_ring;
+mod mask;
+mod multi_cursor;
+mod numeric;
+mod overflow;
 mod password;
 mod placeholder;
+mod primary_selection;
 mod render;
+mod rich_clipboard;
+mod scrollbar;
+mod search;
+mod smooth_scroll;
+mod text_drag;
+mod touch;
 mod user_select;
 mod util;
+mod vim;
 mod widget;
 
 use std::{path::PathBuf, time::Duration};
 
 use bevy::{prelude::*, transform::TransformSystem};
 
+pub use autocomplete::*;
 pub use buffer::*;
+pub use context_menu::*;
 pub use cosmic_edit::*;
 #[doc(no_inline)]
 pub use cosmic_text::{
@@ -109,13 +135,35 @@ pub use cosmic_text::{
 };
 pub use cursor::*;
 pub use events::*;
+pub use file_drop::*;
+pub use filter::*;
 pub use focus::*;
+pub use gutter::*;
+pub use highlight::*;
+pub use history::*;
+pub use ime::*;
+pub use indent::*;
 pub use input::*;
+pub(crate) use insert::*;
+pub use keymap::*;
+pub use kill_ring::*;
+pub use mask::*;
+pub use multi_cursor::*;
+pub use numeric::*;
+pub use overflow::*;
 pub use password::*;
 pub use placeholder::*;
+pub use primary_selection::*;
 pub use render::*;
+pub use rich_clipboard::*;
+pub use scrollbar::*;
+pub use search::*;
+pub use smooth_scroll::*;
+pub use text_drag::*;
+pub use touch::*;
 pub use user_select::*;
 pub use util::*;
+pub use vim::*;
 pub use widget::*;
 
 /// Plugin struct that adds systems and initializes resources related to cosmic edit functionality.
@@ -139,7 +187,27 @@ impl Plugin for CosmicEditPlugin {
             PasswordPlugin,
             EventsPlugin,
             UserSelectPlugin,
+            HistoryPlugin,
+            ImePlugin,
         ))
+        .add_plugins((
+            VimPlugin,
+            KillRingPlugin,
+            IndentPlugin,
+            SearchPlugin,
+            MaskPlugin,
+            NumericPlugin,
+            AutocompletePlugin,
+            HighlightPlugin,
+            GutterPlugin,
+            OverflowPlugin,
+            ScrollbarPlugin,
+            SmoothScrollPlugin,
+            TouchPlugin,
+            ContextMenuPlugin,
+            TextDragPlugin,
+        ))
+        .add_plugins((FileDropPlugin, PrimarySelectionPlugin, RichClipboardPlugin))
         .insert_resource(CosmicFontSystem(font_system));
 
         #[cfg(target_arch = "wasm32")]
@@ -0,0 +1,568 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use crate::*;
+use bevy::prelude::*;
+use cosmic_text::{Cursor, Edit, Editor, Selection};
+use std::ops::Range;
+
+pub(crate) struct MaskPlugin;
+
+impl Plugin for MaskPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, kb_mask.in_set(InputSet).after(kb_clipboard))
+            .add_systems(
+                Update,
+                (sync_mask, snap_mask_cursor)
+                    .chain()
+                    .after(InputSet)
+                    .before(HistorySet),
+            );
+    }
+}
+
+/// Characters accepted by a slot of an [`InputMask`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Slot {
+    /// `9`
+    Digit,
+    /// `A`
+    Letter,
+    /// `*`
+    Alphanumeric,
+}
+
+impl Slot {
+    fn accepts(&self, c: char) -> bool {
+        match self {
+            Slot::Digit => c.is_ascii_digit(),
+            Slot::Letter => c.is_alphabetic(),
+            Slot::Alphanumeric => c.is_alphanumeric(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MaskToken {
+    Slot(Slot),
+    Literal(char),
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] to format its text with a
+/// pattern such as `(999) 999-9999`, `99/99/9999` or `AAAA-9999`.
+///
+/// In the pattern `9` is a digit slot, `A` a letter slot and `*` a letter or digit slot. Any
+/// other character is a literal separator, `\` makes the next character a literal.
+///
+/// Separators are inserted automatically and skipped by the cursor, Backspace removes the
+/// previous filled slot and unfilled slots show the placeholder character. The typed characters
+/// without separators are available with [`InputMask::value`], while
+/// [`BufferExtras::get_text`] returns the formatted text.
+///
+/// Masked fields are single line, and are not meant to be combined with a [`Placeholder`].
+#[derive(Component, Clone, Debug)]
+pub struct InputMask {
+    tokens: Vec<MaskToken>,
+    placeholder: char,
+    /// Characters typed into the slots, without separators
+    value: String,
+    /// Cursor offset after the last snap, to snap in the direction of travel
+    last_caret: Option<usize>,
+}
+
+impl InputMask {
+    /// Mask with `_` as the placeholder character
+    pub fn new(pattern: &str) -> Self {
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                '9' => MaskToken::Slot(Slot::Digit),
+                'A' => MaskToken::Slot(Slot::Letter),
+                '*' => MaskToken::Slot(Slot::Alphanumeric),
+                '\\' => match chars.next() {
+                    Some(escaped) => MaskToken::Literal(escaped),
+                    None => break,
+                },
+                c => MaskToken::Literal(c),
+            });
+        }
+
+        Self {
+            tokens,
+            placeholder: '_',
+            value: String::new(),
+            last_caret: None,
+        }
+    }
+
+    /// Character shown in unfilled slots
+    pub fn with_placeholder(mut self, placeholder: char) -> Self {
+        self.placeholder = placeholder;
+        self
+    }
+
+    /// Characters typed into the slots, without separators
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Whether every slot is filled
+    pub fn is_complete(&self) -> bool {
+        self.value.chars().count() == self.slots().count()
+    }
+
+    /// Formats `value` with the mask, showing the placeholder in unfilled slots. Characters that
+    /// do not fit their slot are left out.
+    pub fn format(&self, value: &str) -> String {
+        let value = self.normalize(value.chars().collect());
+        let mut value = value.into_iter();
+        self.tokens
+            .iter()
+            .map(|token| match token {
+                MaskToken::Literal(c) => *c,
+                MaskToken::Slot(_) => value.next().unwrap_or(self.placeholder),
+            })
+            .collect()
+    }
+
+    fn slots(&self) -> impl Iterator<Item = Slot> + '_ {
+        self.tokens.iter().filter_map(|token| match token {
+            MaskToken::Slot(slot) => Some(*slot),
+            MaskToken::Literal(_) => None,
+        })
+    }
+
+    /// Keeps the longest prefix of `value` that fits the slots
+    fn normalize(&self, mut value: Vec<char>) -> Vec<char> {
+        let fits = self
+            .slots()
+            .zip(value.iter())
+            .take_while(|(slot, c)| slot.accepts(**c))
+            .count();
+        value.truncate(fits);
+        value
+    }
+
+    /// Byte offset of each slot in the formatted text, plus the end of the last slot
+    fn carets(&self, value: &str) -> Vec<usize> {
+        let formatted = self.format(value);
+        let mut carets = Vec::new();
+        let mut last_slot_end = 0;
+        for ((offset, c), token) in formatted.char_indices().zip(self.tokens.iter()) {
+            if let MaskToken::Slot(_) = token {
+                carets.push(offset);
+                last_slot_end = offset + c.len_utf8();
+            }
+        }
+        carets.push(last_slot_end);
+        carets
+    }
+
+    /// Index of the slot the cursor at `offset` is in front of
+    fn slot_at(&self, offset: usize) -> usize {
+        let carets = self.carets(&self.value);
+        carets[..carets.len() - 1]
+            .iter()
+            .filter(|start| **start < offset)
+            .count()
+    }
+
+    /// Slots covered by the byte range `start..end`
+    fn slots_in(&self, start: usize, end: usize) -> Range<usize> {
+        self.slot_at(start)..self.slot_at(end)
+    }
+
+    /// Extracts the slot characters from text that was edited without the mask
+    fn conform(&self, text: &str) -> String {
+        let mut value = String::new();
+        let mut tokens = self.tokens.iter().peekable();
+        for c in text.chars() {
+            while let Some(token) = tokens.peek() {
+                match token {
+                    MaskToken::Literal(l) if *l == c => {
+                        tokens.next();
+                        break;
+                    }
+                    // Separators missing from the text are skipped
+                    MaskToken::Literal(_) => {
+                        tokens.next();
+                    }
+                    MaskToken::Slot(slot) if slot.accepts(c) => {
+                        value.push(c);
+                        tokens.next();
+                        break;
+                    }
+                    // Placeholders and stray characters are dropped
+                    MaskToken::Slot(_) => break,
+                }
+            }
+        }
+
+        // Unfilled slots of formatted text show the placeholder, even one that fits the slot
+        let formatted = text.chars().count() == self.tokens.len()
+            && text
+                .chars()
+                .zip(&self.tokens)
+                .all(|(c, token)| match token {
+                    MaskToken::Literal(l) => c == *l,
+                    MaskToken::Slot(_) => true,
+                });
+        if formatted {
+            value.truncate(value.trim_end_matches(self.placeholder).len());
+        }
+        value
+    }
+
+    fn is_literal(&self, c: char) -> bool {
+        self.tokens.contains(&MaskToken::Literal(c))
+    }
+}
+
+/// Edits of a masked value, with the slot the cursor ends up in front of
+struct MaskEdit {
+    value: Vec<char>,
+    slot: usize,
+    rejected: String,
+}
+
+impl MaskEdit {
+    fn remove(&mut self, range: Range<usize>) {
+        let end = range.end.min(self.value.len());
+        if range.start < end {
+            self.value.drain(range.start..end);
+        }
+        self.slot = range.start;
+    }
+
+    fn insert(&mut self, mask: &InputMask, c: char) {
+        let slot = self.slot.min(self.value.len());
+        let fits = mask.slots().nth(slot).is_some_and(|s| s.accepts(c));
+        if fits {
+            self.value.insert(slot, c);
+            self.value = mask.normalize(std::mem::take(&mut self.value));
+            self.slot = slot + 1;
+        } else if !mask.is_literal(c) && !c.is_control() {
+            // Typed separators are skipped silently
+            self.rejected.push(c);
+        }
+    }
+}
+
+/// Typing, deleting, cutting and pasting in the focused masked field
+fn kb_mask(
+    active_editor: Res<FocusedWidget>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut char_evr: EventReader<ReceivedCharacter>,
+    mut q: Query<(
+        &mut InputMask,
+        &mut CosmicEditor,
+        Option<&mut EditHistory>,
+        Option<&CosmicKeymap>,
+        Option<&ReadOnly>,
+    )>,
+    keymap: Res<CosmicKeymap>,
+    menu_commands: Res<MenuCommands>,
+    mut evw_changed: EventWriter<CosmicTextChanged>,
+    mut evw_rejected: EventWriter<CosmicInputRejected>,
+) {
+    let Some(entity) = active_editor.0 else {
+        char_evr.clear();
+        return;
+    };
+    let Ok((mut mask, mut editor, history_opt, keymap_opt, readonly_opt)) = q.get_mut(entity)
+    else {
+        char_evr.clear();
+        return;
+    };
+    if readonly_opt.is_some() {
+        char_evr.clear();
+        return;
+    }
+
+    let mut commands = keymap_opt.unwrap_or(&*keymap).just_pressed(&keys);
+    // Chosen from a context menu
+    commands.extend(menu_commands.of(entity));
+    let command_held = keypress_command(&keys);
+
+    let mut selected = editor
+        .selection_bounds()
+        .filter(|(start, end)| start != end)
+        .map(|(start, end)| mask.slots_in(start.index, end.index));
+    let mut edit = MaskEdit {
+        value: mask.value.chars().collect(),
+        slot: mask.slot_at(editor.cursor().index),
+        rejected: String::new(),
+    };
+
+    let backspace = |edit: &mut MaskEdit, selected: &mut Option<Range<usize>>| match selected.take()
+    {
+        Some(range) => edit.remove(range),
+        None if edit.slot > 0 => edit.remove(edit.slot - 1..edit.slot),
+        None => {}
+    };
+
+    if commands.contains(&EditorCommand::Backspace) {
+        // Key repeats arrive as character events while held
+        backspace(&mut edit, &mut selected);
+        char_evr.clear();
+    }
+    if commands.contains(&EditorCommand::Delete) {
+        match selected.take() {
+            Some(range) => edit.remove(range),
+            None => edit.remove(edit.slot..edit.slot + 1),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if commands.contains(&EditorCommand::Cut) {
+        if let Some(range) = selected.take() {
+            if let Some(text) = editor.copy_selection() {
+                write_clipboard_text(&text);
+            }
+            edit.remove(range);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if commands.contains(&EditorCommand::Paste) {
+        if let Ok(text) = arboard::Clipboard::new().and_then(|mut c| c.get_text()) {
+            if let Some(range) = selected.take() {
+                edit.remove(range);
+            }
+            for c in text.chars() {
+                edit.insert(&mask, c);
+            }
+        }
+    }
+
+    for char_ev in char_evr.read() {
+        for c in char_ev.char.chars() {
+            match c {
+                '\u{8}' => backspace(&mut edit, &mut selected),
+                _ if command_held => {}
+                c => {
+                    if let Some(range) = selected.take() {
+                        edit.remove(range);
+                    }
+                    edit.insert(&mask, c);
+                }
+            }
+        }
+    }
+
+    if !edit.rejected.is_empty() {
+        evw_rejected.send(CosmicInputRejected {
+            entity,
+            text: std::mem::take(&mut edit.rejected),
+        });
+    }
+
+    // Characters shifted into slots they do not fit are dropped
+    edit.value = mask.normalize(edit.value);
+    let value: String = edit.value.iter().collect();
+    if value == mask.value {
+        return;
+    }
+    let caret = mask.carets(&value)[edit.slot.min(edit.value.len())];
+
+    let before = (editor.cursor(), editor.selection());
+    let formatted = mask.format(&value);
+    editor.start_change();
+    replace_text(&mut editor, &formatted);
+    editor.set_selection(Selection::None);
+    editor.set_cursor(Cursor::new(0, caret));
+    let change = editor.finish_change();
+    editor.set_redraw(true);
+
+    if let (Some(change), Some(mut history)) = (change, history_opt) {
+        history.seal();
+        history.record(change, before, &editor);
+        history.seal();
+    }
+
+    mask.value = value;
+    mask.last_caret = Some(caret);
+    evw_changed.send(CosmicTextChanged((entity, formatted)));
+}
+
+/// Formats text changed without the mask, such as by [`CosmicBuffer::set_text`] or undo
+fn sync_mask(
+    mut q: Query<
+        (
+            Entity,
+            &mut InputMask,
+            &mut CosmicBuffer,
+            Option<&mut CosmicEditor>,
+            Option<&mut EditHistory>,
+            &DefaultAttrs,
+        ),
+        Or<(
+            Changed<InputMask>,
+            Changed<CosmicBuffer>,
+            Changed<CosmicEditor>,
+        )>,
+    >,
+    mut evw_changed: EventWriter<CosmicTextChanged>,
+    mut font_system: ResMut<CosmicFontSystem>,
+) {
+    for (entity, mut mask, mut buffer, editor_opt, history_opt, attrs) in q.iter_mut() {
+        let text = match editor_opt.as_ref() {
+            Some(editor) => editor.with_buffer(|b| b.get_text()),
+            None => buffer.get_text(),
+        };
+        if text == mask.format(&mask.value) {
+            continue;
+        }
+
+        let value = mask.conform(&text);
+        let formatted = mask.format(&value);
+        mask.value = value;
+        if formatted == text {
+            continue;
+        }
+
+        match editor_opt {
+            Some(mut editor) => {
+                let before = (editor.cursor(), editor.selection());
+                let slot = mask.slot_at(editor.cursor().index);
+                editor.start_change();
+                replace_text(&mut editor, &formatted);
+                editor.set_selection(Selection::None);
+                let caret = mask.carets(&mask.value)[slot.min(mask.value.chars().count())];
+                editor.set_cursor(Cursor::new(0, caret));
+                let change = editor.finish_change();
+                editor.set_redraw(true);
+
+                if let (Some(change), Some(mut history)) = (change, history_opt) {
+                    history.record(change, before, &editor);
+                }
+            }
+            None => {
+                buffer.set_text(&mut font_system, &formatted, attrs.as_attrs());
+            }
+        }
+        evw_changed.send(CosmicTextChanged((entity, formatted)));
+    }
+}
+
+/// Keeps the cursor in front of a slot, skipping separators in the direction it moved
+fn snap_mask_cursor(mut q: Query<(&mut InputMask, &mut CosmicEditor)>) {
+    for (mut mask, mut editor) in q.iter_mut() {
+        let cursor = editor.cursor();
+        let filled = mask.value.chars().count();
+        let carets = mask.carets(&mask.value);
+        // Only up to the first unfilled slot
+        let carets = &carets[..=filled.min(carets.len() - 1)];
+
+        let max = carets[carets.len() - 1];
+        let offset = if cursor.line == 0 { cursor.index } else { max };
+        let target = if carets.contains(&offset) {
+            offset
+        } else if offset > max {
+            max
+        } else if mask.last_caret.is_some_and(|last| offset < last) {
+            carets
+                .iter()
+                .rev()
+                .find(|c| **c <= offset)
+                .copied()
+                .unwrap_or(carets[0])
+        } else {
+            carets
+                .iter()
+                .find(|c| **c >= offset)
+                .copied()
+                .unwrap_or(max)
+        };
+
+        if target != cursor.index || cursor.line != 0 {
+            editor.set_cursor(Cursor::new(0, target));
+            editor.set_redraw(true);
+        }
+        mask.bypass_change_detection().last_caret = Some(target);
+    }
+}
+
+/// Replaces all text of `editor`, as part of the current change
+pub(crate) fn replace_text(editor: &mut Editor<'static>, text: &str) {
+    let end = editor.with_buffer(|b| {
+        let last = b.lines.len() - 1;
+        Cursor::new(last, b.lines[last].text().len())
+    });
+    editor.delete_range(Cursor::new(0, 0), end);
+    editor.insert_at(Cursor::new(0, 0), text, None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_and_conform() {
+        let mask = InputMask::new("(999) 999-9999");
+        assert_eq!(mask.format(""), "(___) ___-____");
+        assert_eq!(mask.format("5551234"), "(555) 123-4___");
+        assert_eq!(mask.conform("(555) 123-4___"), "5551234");
+        assert_eq!(mask.conform("5551234567"), "5551234567");
+        assert_eq!(mask.conform("(55) 1"), "551");
+        assert_eq!(
+            mask.carets("55"),
+            vec![1, 2, 3, 6, 7, 8, 10, 11, 12, 13, 14]
+        );
+
+        let mask = InputMask::new("AAAA-9999").with_placeholder('#');
+        assert_eq!(mask.format("ab12"), "ab##-####");
+        assert_eq!(InputMask::new(r"\9-99").format("1"), "9-1_");
+    }
+
+    #[test]
+    fn placeholders_fitting_slots_are_not_typed() {
+        let mask = InputMask::new("999-999").with_placeholder('0');
+        assert_eq!(mask.format("12"), "120-000");
+        assert_eq!(mask.conform("120-000"), "12");
+        assert_eq!(mask.conform("000-000"), "");
+        // Text that is not formatted is taken as typed
+        assert_eq!(mask.conform("120000"), "120000");
+    }
+
+    #[test]
+    fn edits_skip_separators() {
+        let mut mask = InputMask::new("(999) 999-9999");
+        mask.value = "5551234".into();
+        let value = |edit: &MaskEdit| edit.value.iter().collect::<String>();
+
+        // In front of the `4`, right after the `-`
+        let mut edit = MaskEdit {
+            value: mask.value.chars().collect(),
+            slot: mask.slot_at(10),
+            rejected: String::new(),
+        };
+        assert_eq!(edit.slot, 6);
+
+        // Backspace removes the digit in front of the separator
+        edit.remove(edit.slot - 1..edit.slot);
+        assert_eq!((value(&edit).as_str(), edit.slot), ("555124", 5));
+        // Delete removes the digit after the cursor
+        edit.remove(edit.slot..edit.slot + 1);
+        assert_eq!((value(&edit).as_str(), edit.slot), ("55512", 5));
+
+        // Typed separators are skipped, other characters rejected
+        edit.insert(&mask, '-');
+        edit.insert(&mask, 'x');
+        edit.insert(&mask, '9');
+        assert_eq!((value(&edit).as_str(), edit.slot), ("555129", 6));
+        assert_eq!(edit.rejected, "x");
+        // The cursor lands after the separator, in front of the next slot
+        assert_eq!(mask.carets(&value(&edit))[edit.slot], 10);
+
+        // Characters shifted into slots they do not fit are dropped
+        let mask = InputMask::new("AAAA-9999");
+        let mut edit = MaskEdit {
+            value: "ab12".chars().collect(),
+            slot: 0,
+            rejected: String::new(),
+        };
+        edit.insert(&mask, 'c');
+        assert_eq!(value(&edit), "cab");
+    }
+}
@@ -0,0 +1,410 @@
+use crate::*;
+use cosmic_text::{Change, ChangeItem, Cursor, Edit, Editor, FontSystem, Selection};
+
+/// An extra cursor, and its selection, of a [`CosmicEditor`] editing in several places at once.
+///
+/// Added with Alt+Click, or by selecting the next occurrence of the selection with Ctrl/Cmd+D.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SecondaryCursor {
+    pub cursor: Cursor,
+    pub selection: Selection,
+}
+
+impl SecondaryCursor {
+    pub fn new(cursor: Cursor, selection: Selection) -> Self {
+        Self { cursor, selection }
+    }
+
+    /// Start and end of the selected range, or [`None`] if nothing is selected
+    pub fn selection_bounds(&self) -> Option<(Cursor, Cursor)> {
+        let anchor = selection_anchor(&self.selection)?;
+        Some(ordered(anchor, self.cursor))
+    }
+}
+
+impl CosmicEditor {
+    /// Whether the editor has any [`SecondaryCursor`]s
+    pub fn has_secondary_cursors(&self) -> bool {
+        !self.secondary_cursors.is_empty()
+    }
+
+    /// Keeps only the primary cursor
+    pub fn clear_secondary_cursors(&mut self) {
+        if self.has_secondary_cursors() {
+            self.secondary_cursors.clear();
+            self.editor.set_redraw(true);
+        }
+    }
+
+    /// Moves the primary cursor to `cursor`, keeping the previous primary cursor as a secondary
+    /// one
+    pub fn add_cursor(&mut self, cursor: Cursor) {
+        let previous = SecondaryCursor::new(self.editor.cursor(), self.editor.selection());
+        self.secondary_cursors.push(previous);
+        self.editor.set_cursor(cursor);
+        self.editor.set_selection(Selection::None);
+        self.merge_cursors();
+        self.editor.set_redraw(true);
+    }
+
+    /// Runs `f` once at the primary cursor and once at every secondary cursor, adjusting the
+    /// other cursors for the text each run inserted or deleted.
+    ///
+    /// Returns everything changed as a single [`Change`], for undo history.
+    pub fn edit_all_cursors<F>(&mut self, font_system: &mut FontSystem, mut f: F) -> Option<Change>
+    where
+        F: FnMut(&mut Editor<'static>, &mut FontSystem),
+    {
+        let mut cursors = vec![SecondaryCursor::new(
+            self.editor.cursor(),
+            self.editor.selection(),
+        )];
+        cursors.extend(self.secondary_cursors.iter().copied());
+
+        let mut change = Change::default();
+        for i in 0..cursors.len() {
+            if i > 0 {
+                self.editor.set_cursor(cursors[i].cursor);
+                self.editor.set_selection(cursors[i].selection);
+            }
+
+            self.editor.start_change();
+            f(&mut self.editor, font_system);
+            let items = self
+                .editor
+                .finish_change()
+                .map(|c| c.items)
+                .unwrap_or_default();
+
+            for item in items.iter() {
+                for (j, other) in cursors.iter_mut().enumerate() {
+                    if j == i {
+                        continue;
+                    }
+                    adjust_cursor(&mut other.cursor, item);
+                    if let Some(anchor) = selection_anchor_mut(&mut other.selection) {
+                        adjust_cursor(anchor, item);
+                    }
+                }
+            }
+
+            cursors[i] = SecondaryCursor::new(self.editor.cursor(), self.editor.selection());
+            change.items.extend(items);
+        }
+
+        self.editor.set_cursor(cursors[0].cursor);
+        self.editor.set_selection(cursors[0].selection);
+        self.secondary_cursors = cursors.split_off(1);
+        self.merge_cursors();
+
+        if change.items.is_empty() {
+            None
+        } else {
+            Some(change)
+        }
+    }
+
+    /// Selected text of every cursor, joined by newlines in document order
+    pub fn copy_all_selections(&self) -> Option<String> {
+        if !self.has_secondary_cursors() {
+            return self.editor.copy_selection();
+        }
+
+        let mut ranges: Vec<(Cursor, Cursor)> = self
+            .secondary_cursors
+            .iter()
+            .filter_map(|c| c.selection_bounds())
+            .collect();
+        ranges.extend(self.editor.selection_bounds());
+        ranges.sort_by_key(|(start, _)| (start.line, start.index));
+
+        if ranges.is_empty() {
+            return None;
+        }
+
+        let selections: Vec<String> = self.editor.with_buffer(|b| {
+            let text = b.get_text();
+            ranges
+                .iter()
+                .map(|(start, end)| {
+                    text[cursor_to_offset(b, *start)..cursor_to_offset(b, *end)].to_string()
+                })
+                .collect()
+        });
+
+        Some(selections.join("\n"))
+    }
+
+    /// Selects the next occurrence of the selected text with a new cursor, wrapping around at the
+    /// end of the buffer. Selects the word under the cursor if nothing is selected.
+    ///
+    /// Returns `false` if there is nothing left to select.
+    pub fn add_next_occurrence(&mut self, font_system: &mut FontSystem) -> bool {
+        let Some((start, end)) = self.editor.selection_bounds().filter(|(s, e)| s != e) else {
+            self.editor.shape_as_needed(font_system, false);
+            self.editor
+                .set_selection(Selection::Word(self.editor.cursor()));
+            let Some((word_start, word_end)) =
+                self.editor.selection_bounds().filter(|(s, e)| s != e)
+            else {
+                self.editor.set_selection(Selection::None);
+                return false;
+            };
+            self.editor.set_cursor(word_end);
+            self.editor.set_selection(Selection::Normal(word_start));
+            self.editor.set_redraw(true);
+            return true;
+        };
+
+        let mut selected: Vec<(usize, usize)> = Vec::new();
+        let found = self.editor.with_buffer(|b| {
+            let text = b.get_text();
+            let from = cursor_to_offset(b, start);
+            let to = cursor_to_offset(b, end);
+            let needle = &text[from..to];
+
+            selected.push((from, to));
+            for c in self.secondary_cursors.iter() {
+                if let Some((s, e)) = c.selection_bounds() {
+                    selected.push((cursor_to_offset(b, s), cursor_to_offset(b, e)));
+                }
+            }
+
+            // Search after the current selection first, then wrap around
+            text[to..]
+                .match_indices(needle)
+                .map(|(i, _)| i + to)
+                .chain(text[..to].match_indices(needle).map(|(i, _)| i))
+                .map(|i| (i, i + needle.len()))
+                .find(|range| !selected.contains(range))
+                .map(|(s, e)| (offset_to_cursor(b, s), offset_to_cursor(b, e)))
+        });
+
+        let Some((next_start, next_end)) = found else {
+            return false;
+        };
+
+        self.secondary_cursors.push(SecondaryCursor::new(
+            self.editor.cursor(),
+            self.editor.selection(),
+        ));
+        self.editor.set_cursor(next_end);
+        self.editor.set_selection(Selection::Normal(next_start));
+        self.editor.set_redraw(true);
+        true
+    }
+
+    /// Merges cursors that sit at the same position or whose selections overlap
+    pub(crate) fn merge_cursors(&mut self) {
+        if !self.has_secondary_cursors() {
+            return;
+        }
+
+        let primary = SecondaryCursor::new(self.editor.cursor(), self.editor.selection());
+        let mut cursors: Vec<(SecondaryCursor, bool)> = vec![(primary, true)];
+        cursors.extend(self.secondary_cursors.iter().map(|c| (*c, false)));
+
+        let range = |c: &SecondaryCursor| {
+            let (s, e) = c.selection_bounds().unwrap_or((c.cursor, c.cursor));
+            ((s.line, s.index), (e.line, e.index))
+        };
+        cursors.sort_by_key(|(c, _)| range(c).0);
+
+        let mut merged: Vec<(SecondaryCursor, bool)> = Vec::with_capacity(cursors.len());
+        for (cursor, is_primary) in cursors {
+            let Some((last, last_primary)) = merged.last_mut() else {
+                merged.push((cursor, is_primary));
+                continue;
+            };
+
+            let (last_start, last_end) = range(last);
+            let (start, end) = range(&cursor);
+            let overlaps =
+                start < last_end || (start == last_end && (start == end || last_start == last_end));
+            if !overlaps {
+                merged.push((cursor, is_primary));
+                continue;
+            }
+
+            // Union of both ranges, selected forwards
+            if end > last_end {
+                let anchor = last.selection_bounds().map_or(last.cursor, |(s, _)| s);
+                let new_end = cursor.selection_bounds().map_or(cursor.cursor, |(_, e)| e);
+                *last = if anchor == new_end {
+                    SecondaryCursor::new(new_end, Selection::None)
+                } else {
+                    SecondaryCursor::new(new_end, Selection::Normal(anchor))
+                };
+            }
+            *last_primary |= is_primary;
+        }
+
+        let primary_index = merged.iter().position(|(_, p)| *p).unwrap_or(0);
+        let (primary, _) = merged.remove(primary_index);
+        self.editor.set_cursor(primary.cursor);
+        self.editor.set_selection(primary.selection);
+        self.secondary_cursors = merged.into_iter().map(|(c, _)| c).collect();
+    }
+}
+
+fn selection_anchor(selection: &Selection) -> Option<Cursor> {
+    match selection {
+        Selection::None => None,
+        Selection::Normal(c) | Selection::Line(c) | Selection::Word(c) => Some(*c),
+    }
+}
+
+fn selection_anchor_mut(selection: &mut Selection) -> Option<&mut Cursor> {
+    match selection {
+        Selection::None => None,
+        Selection::Normal(c) | Selection::Line(c) | Selection::Word(c) => Some(c),
+    }
+}
+
+fn ordered(a: Cursor, b: Cursor) -> (Cursor, Cursor) {
+    if (a.line, a.index) <= (b.line, b.index) {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// Moves `cursor` to account for the text inserted or deleted by `item`
+pub(crate) fn adjust_cursor(cursor: &mut Cursor, item: &ChangeItem) {
+    let pos = (cursor.line, cursor.index);
+    let start = (item.start.line, item.start.index);
+
+    if item.insert {
+        if pos < start {
+            return;
+        }
+        let newlines = item.text.matches('\n').count();
+        if cursor.line == item.start.line {
+            let tail = cursor.index - item.start.index;
+            cursor.index = match item.text.rfind('\n') {
+                Some(i) => item.text.len() - i - 1 + tail,
+                None => cursor.index + item.text.len(),
+            };
+        }
+        cursor.line += newlines;
+    } else {
+        let end = (item.end.line, item.end.index);
+        if pos <= start {
+            return;
+        }
+        if pos < end {
+            cursor.line = item.start.line;
+            cursor.index = item.start.index;
+            return;
+        }
+        if cursor.line == item.end.line {
+            cursor.index = item.start.index + (cursor.index - item.end.index);
+        }
+        cursor.line -= item.end.line - item.start.line;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmic_text::{fontdb, Attrs, Buffer, Metrics, Shaping};
+
+    fn item(start: (usize, usize), end: (usize, usize), text: &str, insert: bool) -> ChangeItem {
+        ChangeItem {
+            start: Cursor::new(start.0, start.1),
+            end: Cursor::new(end.0, end.1),
+            text: text.to_string(),
+            insert,
+        }
+    }
+
+    fn adjusted(cursor: (usize, usize), item: &ChangeItem) -> (usize, usize) {
+        let mut cursor = Cursor::new(cursor.0, cursor.1);
+        adjust_cursor(&mut cursor, item);
+        (cursor.line, cursor.index)
+    }
+
+    fn editor(text: &str) -> (FontSystem, CosmicEditor) {
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(20., 20.));
+        buffer.set_text(&mut font_system, text, Attrs::new(), Shaping::Advanced);
+        (font_system, CosmicEditor::new(Editor::new(buffer)))
+    }
+
+    #[test]
+    fn inserts_move_later_cursors() {
+        let typed = item((0, 2), (0, 4), "xy", true);
+        // Before the edit
+        assert_eq!(adjusted((0, 1), &typed), (0, 1));
+        // At and after the edit, on the same line
+        assert_eq!(adjusted((0, 2), &typed), (0, 4));
+        assert_eq!(adjusted((0, 5), &typed), (0, 7));
+        // Later lines keep their index
+        assert_eq!(adjusted((1, 5), &typed), (1, 5));
+
+        let lines = item((0, 2), (1, 1), "x\ny", true);
+        assert_eq!(adjusted((0, 5), &lines), (1, 4));
+        assert_eq!(adjusted((2, 3), &lines), (3, 3));
+    }
+
+    #[test]
+    fn deletes_move_later_cursors() {
+        let deleted = item((0, 2), (0, 4), "xy", false);
+        assert_eq!(adjusted((0, 1), &deleted), (0, 1));
+        assert_eq!(adjusted((0, 6), &deleted), (0, 4));
+        // Cursors inside the deleted text end up at its start
+        assert_eq!(adjusted((0, 3), &deleted), (0, 2));
+
+        let lines = item((0, 2), (1, 1), "x\ny", false);
+        assert_eq!(adjusted((1, 0), &lines), (0, 2));
+        assert_eq!(adjusted((1, 4), &lines), (0, 5));
+        assert_eq!(adjusted((3, 4), &lines), (2, 4));
+    }
+
+    #[test]
+    fn edits_at_every_cursor() {
+        let (mut font_system, mut editor) = editor("ab ab\nab");
+        editor.set_cursor(Cursor::new(0, 2));
+        editor.add_cursor(Cursor::new(0, 5));
+        editor.add_cursor(Cursor::new(1, 2));
+
+        let change = editor.edit_all_cursors(&mut font_system, |e, _| e.insert_string("X\n", None));
+        assert_eq!(change.map(|c| c.items.len()), Some(3));
+        assert_eq!(editor.with_buffer(|b| b.get_text()), "abX\n abX\n\nabX\n");
+        assert_eq!(editor.secondary_cursors.len(), 2);
+
+        // Deleting back over the inserted text puts every cursor where it started
+        editor.edit_all_cursors(&mut font_system, |e, f| {
+            e.action(f, Action::Backspace);
+            e.action(f, Action::Backspace);
+        });
+        assert_eq!(editor.with_buffer(|b| b.get_text()), "ab ab\nab");
+        let mut cursors: Vec<_> = editor
+            .secondary_cursors
+            .iter()
+            .map(|c| (c.cursor.line, c.cursor.index))
+            .collect();
+        cursors.push((editor.cursor().line, editor.cursor().index));
+        cursors.sort();
+        assert_eq!(cursors, vec![(0, 2), (0, 5), (1, 2)]);
+    }
+
+    #[test]
+    fn merges_touching_cursors() {
+        let (_font_system, mut editor) = editor("hello world");
+        editor.set_cursor(Cursor::new(0, 3));
+        editor.add_cursor(Cursor::new(0, 3));
+        assert!(!editor.has_secondary_cursors());
+
+        // A cursor inside another cursor's selection is merged into it
+        editor.add_cursor(Cursor::new(0, 8));
+        editor.secondary_cursors[0] =
+            SecondaryCursor::new(Cursor::new(0, 5), Selection::Normal(Cursor::new(0, 0)));
+        editor.set_cursor(Cursor::new(0, 2));
+        editor.merge_cursors();
+        assert!(!editor.has_secondary_cursors());
+        assert_eq!(editor.cursor(), Cursor::new(0, 5));
+        assert_eq!(editor.selection(), Selection::Normal(Cursor::new(0, 0)));
+    }
+}
@@ -0,0 +1,500 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use crate::*;
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    window::PrimaryWindow,
+};
+use cosmic_text::{Cursor, Edit, Selection};
+
+pub(crate) struct NumericPlugin;
+
+impl Plugin for NumericPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (sync_numeric_input, step_numeric, update_numeric_value)
+                .chain()
+                .after(InputSet)
+                .before(HistorySet),
+        );
+    }
+}
+
+/// Pixels the pointer has to move before a press on a [`NumericField`] starts scrubbing
+const SCRUB_THRESHOLD: f32 = 4.;
+
+/// Whether a [`NumericField`] holds whole numbers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumberKind {
+    Integer,
+    Float,
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] to make it a number input.
+///
+/// Only digits, a minus sign and the decimal separator can be typed. Up/Down and the mouse wheel
+/// change the value by `step` and PageUp/PageDown by ten steps. With a `drag_speed`, dragging
+/// horizontally over the widget scrubs the value instead of selecting text. The value is clamped
+/// to `min` and `max` when the field loses focus.
+///
+/// Every change of the parsed value is reported with a [`CosmicNumberChanged`] event.
+#[derive(Component, Clone, Debug)]
+pub struct NumericField {
+    pub kind: NumberKind,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub step: f64,
+    /// Digits after the decimal separator when a float is formatted, [`None`] for as many as
+    /// needed
+    pub decimals: Option<usize>,
+    /// Separator between the integer and fractional part, from the system locale by default
+    pub decimal_separator: char,
+    /// Steps per logical pixel dragged to scrub the value, [`None`] to select text by dragging
+    pub drag_speed: Option<f32>,
+    value: Option<f64>,
+    focused: bool,
+    scrub: Option<Scrub>,
+    /// What the widget's [`InputFilter`] was made for
+    filter: FieldFilter,
+    /// Fractional wheel steps not applied yet
+    wheel_remainder: f32,
+}
+
+/// Where the [`InputFilter`] of a [`NumericField`] comes from
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum FieldFilter {
+    #[default]
+    Unset,
+    /// Inserted by the user, and left alone
+    User,
+    /// Accepts numbers of this kind with this decimal separator
+    Numbers(NumberKind, char),
+}
+
+/// Horizontal drag over a [`NumericField`]
+#[derive(Clone, Copy, Debug)]
+struct Scrub {
+    press_x: f32,
+    last_x: f32,
+    active: bool,
+    /// Fractional steps not applied yet
+    remainder: f32,
+}
+
+impl Default for NumericField {
+    fn default() -> Self {
+        Self {
+            kind: NumberKind::Float,
+            min: None,
+            max: None,
+            step: 1.,
+            decimals: None,
+            decimal_separator: locale_decimal_separator(),
+            drag_speed: None,
+            value: None,
+            focused: false,
+            scrub: None,
+            filter: FieldFilter::Unset,
+            wheel_remainder: 0.,
+        }
+    }
+}
+
+impl NumericField {
+    pub fn integer() -> Self {
+        Self {
+            kind: NumberKind::Integer,
+            ..default()
+        }
+    }
+
+    pub fn float() -> Self {
+        Self::default()
+    }
+
+    pub fn with_range(mut self, min: f64, max: f64) -> Self {
+        self.min = Some(min);
+        self.max = Some(max);
+        self
+    }
+
+    pub fn with_step(mut self, step: f64) -> Self {
+        self.step = step;
+        self
+    }
+
+    pub fn with_decimals(mut self, decimals: usize) -> Self {
+        self.decimals = Some(decimals);
+        self
+    }
+
+    /// Scrubs the value by `drag_speed` steps per logical pixel dragged over the widget
+    pub fn with_scrubbing(mut self, drag_speed: f32) -> Self {
+        self.drag_speed = Some(drag_speed);
+        self
+    }
+
+    /// Last value parsed from the text, [`None`] while the text is not a number
+    pub fn value(&self) -> Option<f64> {
+        self.value
+    }
+
+    /// Parses `text`, accepting `.` as well as the decimal separator
+    pub fn parse(&self, text: &str) -> Option<f64> {
+        let text = text.trim().replace(self.decimal_separator, ".");
+        match self.kind {
+            NumberKind::Integer => text.parse::<i64>().ok().map(|v| v as f64),
+            NumberKind::Float => text.parse::<f64>().ok().filter(|v| v.is_finite()),
+        }
+    }
+
+    /// Formats `value` with the decimal separator
+    pub fn format(&self, value: f64) -> String {
+        let text = match (self.kind, self.decimals) {
+            (NumberKind::Integer, _) => format!("{}", value.round() as i64),
+            (NumberKind::Float, Some(decimals)) => format!("{value:.decimals$}"),
+            (NumberKind::Float, None) => format!("{value}"),
+        };
+        text.replace('.', &self.decimal_separator.to_string())
+    }
+
+    /// `value` moved by `steps` steps and clamped, rounded to the decimals of the step or of
+    /// `value`, whichever has more
+    pub fn step_value(&self, value: f64, steps: f64) -> f64 {
+        let decimals = decimal_places(self.step).max(decimal_places(value));
+        let stepped = format!("{:.decimals$}", value + steps * self.step);
+        self.clamp(stepped.parse().unwrap_or(value))
+    }
+
+    /// Limits `value` to the range, rounding it for integers
+    pub fn clamp(&self, value: f64) -> f64 {
+        let value = match self.kind {
+            NumberKind::Integer => value.round(),
+            NumberKind::Float => value,
+        };
+        let value = self.min.map_or(value, |min| value.max(min));
+        self.max.map_or(value, |max| value.min(max))
+    }
+}
+
+/// Digits after the decimal point of the shortest form of `value`
+fn decimal_places(value: f64) -> usize {
+    let text = value.to_string();
+    text.find('.').map_or(0, |point| text.len() - point - 1)
+}
+
+/// Whether `c` can be typed into a number of `kind`
+fn accepts(kind: NumberKind, decimal_separator: char, c: char) -> bool {
+    c.is_ascii_digit()
+        || c == '-'
+        || (kind == NumberKind::Float && (c == '.' || c == decimal_separator))
+}
+
+/// Decimal separator of the system locale
+fn locale_decimal_separator() -> char {
+    let locale = sys_locale::get_locale().unwrap_or_else(|| String::from("en-US"));
+    let language = locale.split(['-', '_']).next().unwrap_or_default();
+    match language {
+        "bg" | "ca" | "cs" | "da" | "de" | "el" | "es" | "et" | "fi" | "fr" | "hr" | "hu"
+        | "id" | "it" | "lt" | "lv" | "nb" | "nl" | "nn" | "no" | "pl" | "pt" | "ro" | "ru"
+        | "sk" | "sl" | "sr" | "sv" | "tr" | "uk" | "vi" => ',',
+        _ => '.',
+    }
+}
+
+/// Restricts typing to numbers of the field's current kind and decimal separator, and leaves the
+/// mouse wheel to stepping
+fn sync_numeric_input(
+    mut commands: Commands,
+    mut q: Query<(Entity, &mut NumericField, Option<&InputFilter>), Changed<NumericField>>,
+) {
+    for (entity, mut field, filter_opt) in q.iter_mut() {
+        match field.filter {
+            FieldFilter::User => continue,
+            FieldFilter::Unset => {
+                commands.entity(entity).insert(ScrollDisabled);
+                if filter_opt.is_some() {
+                    field.bypass_change_detection().filter = FieldFilter::User;
+                    continue;
+                }
+            }
+            FieldFilter::Numbers(..) => {}
+        }
+
+        let (kind, separator) = (field.kind, field.decimal_separator);
+        if field.filter != FieldFilter::Numbers(kind, separator) {
+            commands
+                .entity(entity)
+                .insert(InputFilter::predicate(move |c| accepts(kind, separator, c)));
+            field.bypass_change_detection().filter = FieldFilter::Numbers(kind, separator);
+        }
+    }
+}
+
+/// Steps the value with the keyboard, mouse wheel and scrubbing
+fn step_numeric(
+    active_editor: Res<FocusedWidget>,
+    keys: Res<ButtonInput<KeyCode>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut scroll_evr: EventReader<MouseWheel>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mut q: Query<(
+        Entity,
+        &mut NumericField,
+        &mut CosmicBuffer,
+        Option<&mut CosmicEditor>,
+        Option<&mut EditHistory>,
+        Option<&CosmicKeymap>,
+        Option<&ReadOnly>,
+        &DefaultAttrs,
+        &GlobalTransform,
+        &Sprite,
+    )>,
+    node_q: Query<(&Node, &GlobalTransform, &CosmicSource)>,
+    keymap: Res<CosmicKeymap>,
+    mut evw_changed: EventWriter<CosmicTextChanged>,
+    mut font_system: ResMut<CosmicFontSystem>,
+) {
+    let scroll: f32 = scroll_evr
+        .read()
+        .map(|ev| match ev.unit {
+            MouseScrollUnit::Line => ev.y,
+            MouseScrollUnit::Pixel => ev.y / 20.,
+        })
+        .sum();
+    let window = windows.get_single().ok();
+    let camera = camera_q.iter().find(|(c, _)| c.is_active);
+
+    for (
+        entity,
+        mut field,
+        mut buffer,
+        mut editor_opt,
+        history_opt,
+        keymap_opt,
+        readonly_opt,
+        attrs,
+        sprite_transform,
+        sprite,
+    ) in q.iter_mut()
+    {
+        if readonly_opt.is_some() {
+            continue;
+        }
+        let mut steps = 0.;
+
+        let (transform, size, is_ui_node) =
+            widget_node(entity, (sprite_transform, sprite), &node_q);
+        let hovered = match (window, camera) {
+            (Some(window), Some((camera, camera_transform))) => get_node_cursor_pos(
+                window,
+                transform,
+                (size.x, size.y),
+                is_ui_node,
+                camera,
+                camera_transform,
+            )
+            .is_some(),
+            _ => false,
+        };
+
+        if active_editor.0 == Some(entity) && editor_opt.is_some() {
+            for command in keymap_opt.unwrap_or(&*keymap).just_pressed(&keys) {
+                steps += match command {
+                    EditorCommand::Up => 1.,
+                    EditorCommand::Down => -1.,
+                    EditorCommand::PageUp => 10.,
+                    EditorCommand::PageDown => -10.,
+                    _ => 0.,
+                };
+            }
+            // Smooth scrolling sends fractions of a step
+            if hovered && scroll != 0. {
+                field.wheel_remainder += scroll;
+                let whole = field.wheel_remainder.trunc();
+                field.wheel_remainder -= whole;
+                steps += whole as f64;
+            }
+        }
+
+        // Scrubbing starts with a press on the widget and follows the pointer until release
+        let pointer_x = window.and_then(|w| w.cursor_position()).map(|p| p.x);
+        if buttons.just_pressed(MouseButton::Left) {
+            field.scrub = match (hovered && field.drag_speed.is_some(), pointer_x) {
+                (true, Some(x)) => Some(Scrub {
+                    press_x: x,
+                    last_x: x,
+                    active: false,
+                    remainder: 0.,
+                }),
+                _ => None,
+            };
+        }
+
+        let drag_speed = field.drag_speed.unwrap_or_default();
+        let mut scrubbed = false;
+        match (field.scrub.as_mut(), pointer_x) {
+            (Some(scrub), Some(x)) if buttons.pressed(MouseButton::Left) => {
+                if !scrub.active && (x - scrub.press_x).abs() > SCRUB_THRESHOLD {
+                    scrub.active = true;
+                }
+                if scrub.active {
+                    scrub.remainder += (x - scrub.last_x) * drag_speed;
+                    let whole = scrub.remainder.trunc();
+                    scrub.remainder -= whole;
+                    steps += whole as f64;
+                    scrubbed = true;
+                }
+                scrub.last_x = x;
+            }
+            (Some(_), _) => field.scrub = None,
+            _ => {}
+        }
+
+        if scrubbed {
+            // Scrubbing replaces the drag selection
+            if let Some(editor) = editor_opt.as_mut() {
+                if editor.selection() != Selection::None {
+                    editor.set_selection(Selection::None);
+                }
+            }
+        }
+
+        if steps == 0. {
+            continue;
+        }
+
+        let current = field.value.unwrap_or_else(|| field.clamp(0.));
+        let value = field.step_value(current, steps);
+        if field.value == Some(value) {
+            continue;
+        }
+        let text = field.format(value);
+        set_number_text(
+            &text,
+            &mut buffer,
+            editor_opt.as_deref_mut(),
+            history_opt,
+            attrs,
+            &mut font_system,
+        );
+        evw_changed.send(CosmicTextChanged((entity, text)));
+    }
+}
+
+/// Parses the text after every change, and clamps it when the field loses focus
+fn update_numeric_value(
+    active_editor: Res<FocusedWidget>,
+    mut q: Query<(
+        Entity,
+        &mut NumericField,
+        &mut CosmicBuffer,
+        Option<&mut CosmicEditor>,
+        Option<&mut EditHistory>,
+        &DefaultAttrs,
+    )>,
+    mut evw_number: EventWriter<CosmicNumberChanged>,
+    mut evw_changed: EventWriter<CosmicTextChanged>,
+    mut font_system: ResMut<CosmicFontSystem>,
+) {
+    for (entity, mut field, mut buffer, mut editor_opt, history_opt, attrs) in q.iter_mut() {
+        let focused = active_editor.0 == Some(entity);
+        let blurred = field.focused && !focused;
+        if field.focused != focused {
+            field.focused = focused;
+        }
+
+        let text = match editor_opt.as_ref() {
+            Some(editor) => editor.with_buffer(|b| b.get_text()),
+            None => buffer.get_text(),
+        };
+        let mut value = field.parse(&text);
+
+        if blurred {
+            if let Some(parsed) = value {
+                let clamped = field.clamp(parsed);
+                let formatted = field.format(clamped);
+                if field.parse(&formatted) != Some(parsed) {
+                    set_number_text(
+                        &formatted,
+                        &mut buffer,
+                        editor_opt.as_deref_mut(),
+                        history_opt,
+                        attrs,
+                        &mut font_system,
+                    );
+                    evw_changed.send(CosmicTextChanged((entity, formatted)));
+                    value = Some(clamped);
+                }
+            }
+        }
+
+        if value.is_some() && value != field.value {
+            field.value = value;
+            evw_number.send(CosmicNumberChanged {
+                entity,
+                value: value.unwrap_or_default(),
+            });
+        }
+    }
+}
+
+/// Replaces the text of a numeric field, as an undo step when it is focused
+fn set_number_text(
+    text: &str,
+    buffer: &mut CosmicBuffer,
+    editor_opt: Option<&mut CosmicEditor>,
+    history_opt: Option<Mut<EditHistory>>,
+    attrs: &DefaultAttrs,
+    font_system: &mut CosmicFontSystem,
+) {
+    let Some(editor) = editor_opt else {
+        buffer.set_text(font_system, text, attrs.as_attrs());
+        return;
+    };
+
+    let before = (editor.cursor(), editor.selection());
+    editor.start_change();
+    replace_text(editor, text);
+    editor.set_selection(Selection::None);
+    editor.set_cursor(Cursor::new(0, text.len()));
+    let change = editor.finish_change();
+    editor.set_redraw(true);
+
+    if let (Some(change), Some(mut history)) = (change, history_opt) {
+        history.record(change, before, editor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_format() {
+        let mut field = NumericField::float().with_range(0., 10.).with_decimals(2);
+        field.decimal_separator = ',';
+        assert_eq!(field.parse("1,5"), Some(1.5));
+        assert_eq!(field.parse("1.5"), Some(1.5));
+        assert_eq!(field.parse("-"), None);
+        assert_eq!(field.format(field.clamp(12.)), "10,00");
+
+        let field = NumericField::integer().with_range(-5., 5.);
+        assert_eq!(field.parse("3.5"), None);
+        assert_eq!(field.format(field.clamp(-7.2)), "-5");
+    }
+
+    #[test]
+    fn steps_without_float_noise() {
+        let field = NumericField::float().with_step(0.1);
+        assert_eq!(field.format(field.step_value(0.2, 1.)), "0.3");
+        assert_eq!(field.format(field.step_value(1.25, -3.)), "0.95");
+        assert_eq!(field.format(field.step_value(0., 3.)), "0.3");
+
+        let field = NumericField::float().with_range(0., 1.).with_step(0.25);
+        assert_eq!(field.format(field.step_value(0.9, 1.)), "1");
+    }
+}
@@ -0,0 +1,325 @@
+#![allow(clippy::type_complexity)]
+
+use crate::*;
+use bevy::prelude::*;
+use std::ops::Range;
+
+use cosmic_text::{AttrsList, Buffer};
+
+pub(crate) struct OverflowPlugin;
+
+impl Plugin for OverflowPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            (update_overflow, clear_overflow_on_focus)
+                .after(WidgetSet)
+                .before(RenderSet),
+        );
+    }
+}
+
+/// How text that does not fit is shown, see [`TextOverflow`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowMode {
+    /// Cut off at the edge of the widget
+    #[default]
+    Clip,
+    /// Shortened at the end, `A long senten…`
+    Ellipsis,
+    /// Shortened in the middle, for file paths, `/home/user/…/file.txt`
+    MiddleEllipsis,
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] using
+/// [`CosmicWrap::InfiniteLine`] to shorten lines that do not fit while it is not focused.
+///
+/// Only the drawn text is shortened, [`BufferExtras::get_text`] and the editor get the full text
+/// back on focus. While shortened, the full text is available from [`TextOverflow::tooltip`].
+#[derive(Component, Clone, Debug)]
+pub struct TextOverflow {
+    pub mode: OverflowMode,
+    /// Text standing in for the removed part
+    pub ellipsis: String,
+    tooltip: Option<String>,
+    /// Text and width the overflow was computed for
+    measured: Option<(String, f32)>,
+    /// Shortened copy of the buffer, drawn instead of it
+    display: Option<Buffer>,
+}
+
+impl Default for TextOverflow {
+    fn default() -> Self {
+        Self {
+            mode: OverflowMode::Clip,
+            ellipsis: String::from("…"),
+            tooltip: None,
+            measured: None,
+            display: None,
+        }
+    }
+}
+
+impl TextOverflow {
+    pub fn new(mode: OverflowMode) -> Self {
+        Self { mode, ..default() }
+    }
+
+    pub fn clip() -> Self {
+        Self::new(OverflowMode::Clip)
+    }
+
+    pub fn ellipsis() -> Self {
+        Self::new(OverflowMode::Ellipsis)
+    }
+
+    pub fn middle_ellipsis() -> Self {
+        Self::new(OverflowMode::MiddleEllipsis)
+    }
+
+    /// Full text while some of it is cut off, to show on hover
+    pub fn tooltip(&self) -> Option<&str> {
+        self.tooltip.as_deref()
+    }
+
+    /// Shortened buffer to draw in place of the widget's buffer
+    pub(crate) fn display(&self) -> Option<&Buffer> {
+        self.display.as_ref()
+    }
+}
+
+/// Byte range of a glyph in its line, and its width
+type GlyphWidth = (usize, usize, f32);
+
+/// Part of a shortened line
+#[derive(Clone, Debug, PartialEq)]
+enum Piece {
+    /// Byte range of the line that is kept
+    Kept(Range<usize>),
+    Ellipsis,
+}
+
+/// Pieces of `line` that fit `available` pixels, or [`None`] if it already fits. `glyphs` are
+/// in logical order.
+fn shorten(
+    line: &str,
+    glyphs: &[GlyphWidth],
+    available: f32,
+    ellipsis_width: f32,
+    mode: OverflowMode,
+) -> Option<Vec<Piece>> {
+    let total: f32 = glyphs.iter().map(|(_, _, w)| w).sum();
+    if total <= available {
+        return None;
+    }
+
+    let budget = (available - ellipsis_width).max(0.);
+    // End of the glyphs from the front that fit in `budget`
+    let head = |budget: f32| {
+        let mut used = 0.;
+        let mut end = 0;
+        for (_, glyph_end, w) in glyphs {
+            if used + w > budget {
+                break;
+            }
+            used += w;
+            end = *glyph_end;
+        }
+        (end, used)
+    };
+
+    match mode {
+        OverflowMode::Clip => Some(vec![Piece::Kept(0..line.len())]),
+        OverflowMode::Ellipsis => {
+            let (end, _) = head(budget);
+            let end = line[..end].trim_end().len();
+            Some(vec![Piece::Kept(0..end), Piece::Ellipsis])
+        }
+        OverflowMode::MiddleEllipsis => {
+            let (end, used) = head(budget / 2.);
+            let mut tail_used = 0.;
+            let mut start = line.len();
+            for (glyph_start, _, w) in glyphs.iter().rev() {
+                if *glyph_start < end || used + tail_used + w > budget {
+                    break;
+                }
+                tail_used += w;
+                start = *glyph_start;
+            }
+            Some(vec![
+                Piece::Kept(0..end),
+                Piece::Ellipsis,
+                Piece::Kept(start..line.len()),
+            ])
+        }
+    }
+}
+
+/// Text of `pieces` of `line`, with the spans of `attrs` carried over to the kept ranges. The
+/// ellipsis takes the attributes of the text before it.
+fn shortened_line(
+    line: &str,
+    attrs: &AttrsList,
+    pieces: &[Piece],
+    ellipsis: &str,
+) -> (String, AttrsList) {
+    let mut text = String::new();
+    let mut list = AttrsList::new(attrs.defaults());
+    for piece in pieces {
+        let offset = text.len();
+        match piece {
+            Piece::Kept(range) => {
+                text.push_str(&line[range.clone()]);
+                for (span, span_attrs) in attrs.spans() {
+                    let (start, end) = (span.start.max(range.start), span.end.min(range.end));
+                    if start < end {
+                        let shifted = start - range.start + offset..end - range.start + offset;
+                        list.add_span(shifted, span_attrs.as_attrs());
+                    }
+                }
+            }
+            Piece::Ellipsis => {
+                text.push_str(ellipsis);
+                if let Some(before) = offset.checked_sub(1) {
+                    let before = AttrsOwned::new(list.get_span(before));
+                    list.add_span(offset..text.len(), before.as_attrs());
+                }
+            }
+        }
+    }
+    (text, list)
+}
+
+/// Shortens the drawn text of unfocused single line widgets that overflow
+fn update_overflow(
+    mut q: Query<
+        (
+            &mut TextOverflow,
+            &mut CosmicBuffer,
+            &CosmicWrap,
+            &CosmicWidgetSize,
+            &CosmicTextAlign,
+            Option<&LineNumbers>,
+        ),
+        Without<CosmicEditor>,
+    >,
+    mut font_system: ResMut<CosmicFontSystem>,
+) {
+    for (mut overflow, mut buffer, wrap, size, position, numbers_opt) in q.iter_mut() {
+        if *wrap != CosmicWrap::InfiniteLine {
+            continue;
+        }
+
+        let available = size.x - position.padding().x * 2. - gutter_width(numbers_opt);
+        let text = buffer.get_text();
+        let measured = Some((text.clone(), available));
+        if overflow.measured == measured && !overflow.is_changed() {
+            continue;
+        }
+
+        let mut display = buffer.0.clone();
+        display.shape_until_scroll(&mut font_system.0, false);
+
+        let ellipsis_width = {
+            let attrs = display.lines.first().map(|l| l.attrs_list().defaults());
+            let mut measure = Buffer::new(&mut font_system.0, display.metrics());
+            measure.set_size(&mut font_system.0, f32::MAX, f32::MAX);
+            measure.set_text(
+                &mut font_system.0,
+                &overflow.ellipsis,
+                attrs.unwrap_or(Attrs::new()),
+                Shaping::Advanced,
+            );
+            measure.shape_until_scroll(&mut font_system.0, false);
+            measure.layout_runs().map(|run| run.line_w).sum::<f32>()
+        };
+
+        let shortened: Vec<(usize, Vec<Piece>)> = display
+            .layout_runs()
+            .filter_map(|run| {
+                let mut glyphs: Vec<GlyphWidth> =
+                    run.glyphs.iter().map(|g| (g.start, g.end, g.w)).collect();
+                glyphs.sort_by_key(|(start, _, _)| *start);
+                shorten(run.text, &glyphs, available, ellipsis_width, overflow.mode)
+                    .map(|pieces| (run.line_i, pieces))
+            })
+            .collect();
+
+        // Only a cache update, keeps `Changed<TextOverflow>` for user changes
+        let overflow = overflow.bypass_change_detection();
+        overflow.tooltip = (!shortened.is_empty()).then(|| text.clone());
+        overflow.measured = measured;
+        overflow.display = match overflow.mode {
+            OverflowMode::Clip => None,
+            _ if shortened.is_empty() => None,
+            _ => {
+                for (line_i, pieces) in shortened {
+                    let line = &mut display.lines[line_i];
+                    let (text, attrs) =
+                        shortened_line(line.text(), line.attrs_list(), &pieces, &overflow.ellipsis);
+                    line.set_text(text, attrs);
+                }
+                display.shape_until_scroll(&mut font_system.0, false);
+                Some(display)
+            }
+        };
+        buffer.set_redraw(true);
+    }
+}
+
+/// Focused widgets are edited and drawn with their full text
+fn clear_overflow_on_focus(mut q: Query<&mut TextOverflow, Added<CosmicEditor>>) {
+    for mut overflow in q.iter_mut() {
+        let overflow = overflow.bypass_change_detection();
+        overflow.tooltip = None;
+        overflow.measured = None;
+        overflow.display = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shorten_modes() {
+        let line = "/home/user/file.txt";
+        let glyphs: Vec<GlyphWidth> = line.char_indices().map(|(i, _)| (i, i + 1, 1.)).collect();
+        let short = |available, mode| {
+            shorten(line, &glyphs, available, 1., mode).map(|pieces| {
+                let attrs = AttrsList::new(Attrs::new());
+                shortened_line(line, &attrs, &pieces, "…").0
+            })
+        };
+
+        assert_eq!(short(19., OverflowMode::Ellipsis), None);
+        assert_eq!(
+            short(10., OverflowMode::Ellipsis),
+            Some("/home/use…".into())
+        );
+        assert_eq!(
+            short(11., OverflowMode::MiddleEllipsis),
+            Some("/home…e.txt".into())
+        );
+        assert_eq!(short(10., OverflowMode::Clip), Some(line.into()));
+    }
+
+    #[test]
+    fn shortened_lines_keep_their_spans() {
+        let red = Attrs::new().color(CosmicColor::rgb(255, 0, 0));
+        let blue = Attrs::new().color(CosmicColor::rgb(0, 0, 255));
+        let mut attrs = AttrsList::new(Attrs::new());
+        attrs.add_span(0..4, red);
+        attrs.add_span(6..10, blue);
+
+        let pieces = [Piece::Kept(0..2), Piece::Ellipsis, Piece::Kept(7..10)];
+        let (text, list) = shortened_line("redd, blue", &attrs, &pieces, "…");
+        assert_eq!(text, "re…lue");
+        let spans: Vec<_> = list
+            .spans()
+            .into_iter()
+            .map(|(range, attrs)| (range.clone(), attrs.color_opt))
+            .collect();
+        assert_eq!(spans, [(0..5, red.color_opt), (5..8, blue.color_opt)]);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::*;
 use bevy::prelude::*;
-use cosmic_text::{Attrs, Edit};
+use cosmic_text::{Attrs, Buffer, Edit, Shaping};
 
 /// Component to be added to an entity with a [`CosmicEditBundle`] add placeholder text
 ///
@@ -49,6 +49,19 @@ impl Placeholder {
     pub fn is_active(&self) -> bool {
         self.active
     }
+
+    /// Empties `buffer` if it shows the placeholder, before text is inserted into it
+    pub(crate) fn clear(
+        &mut self,
+        buffer: &mut Buffer,
+        attrs: &DefaultAttrs,
+        font_system: &mut FontSystem,
+    ) {
+        if self.active {
+            buffer.set_text(font_system, "", attrs.0.as_attrs(), Shaping::Advanced);
+            self.active = false;
+        }
+    }
 }
 
 pub(crate) struct PlaceholderPlugin;
@@ -220,8 +233,8 @@ fn remove_placeholder_on_input(
         };
 
         editor.set_cursor(cosmic_text::Cursor::new(
-            (lines - 1).max(0),
-            last_line.bytes().len(),
+            lines.saturating_sub(1),
+            last_line.len(),
         ));
 
         placeholder.active = false;
@@ -0,0 +1,314 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use crate::*;
+use bevy::{prelude::*, window::PrimaryWindow};
+
+pub(crate) struct PrimarySelectionPlugin;
+
+impl Plugin for PrimarySelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PrimarySelectionBackend>()
+            .add_systems(
+                PreUpdate,
+                paste_primary_selection.in_set(InputSet).before(input_mouse),
+            )
+            .add_systems(Update, update_primary_selection.after(InputSet));
+    }
+}
+
+/// Reads and writes the text of the primary selection, see [`PrimarySelectionBackend`]
+pub trait SelectionBackend: Send + Sync + 'static {
+    fn get_text(&mut self) -> Option<String>;
+    fn set_text(&mut self, text: &str);
+}
+
+/// The PRIMARY selection of the X server, or of the Wayland compositor.
+///
+/// Connects on first use and then holds on to its connection, as the selection is only offered
+/// while it is open.
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+#[derive(Default)]
+pub struct X11Selection(Option<arboard::Clipboard>);
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+impl X11Selection {
+    fn clipboard(&mut self) -> Option<&mut arboard::Clipboard> {
+        if self.0.is_none() {
+            self.0 = arboard::Clipboard::new().ok();
+        }
+        self.0.as_mut()
+    }
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+impl SelectionBackend for X11Selection {
+    fn get_text(&mut self) -> Option<String> {
+        use arboard::{GetExtLinux, LinuxClipboardKind};
+        self.clipboard()?
+            .get()
+            .clipboard(LinuxClipboardKind::Primary)
+            .text()
+            .ok()
+    }
+
+    fn set_text(&mut self, text: &str) {
+        use arboard::{LinuxClipboardKind, SetExtLinux};
+        if let Some(clipboard) = self.clipboard() {
+            let _ = clipboard
+                .set()
+                .clipboard(LinuxClipboardKind::Primary)
+                .text(text.to_string());
+        }
+    }
+}
+
+/// A selection kept inside the app, shared by its widgets only
+#[derive(Clone, Debug, Default)]
+pub struct MemorySelection(pub Option<String>);
+
+impl SelectionBackend for MemorySelection {
+    fn get_text(&mut self) -> Option<String> {
+        self.0.clone()
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.0 = Some(text.to_string());
+    }
+}
+
+/// Where [`PrimarySelection`] widgets publish their selection and middle click pastes from.
+///
+/// Uses [`X11Selection`] on Linux and the BSDs and a [`MemorySelection`] elsewhere. Insert
+/// another one to replace it, such as a [`MemorySelection`] in tests without an X server.
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use bevy_cosmic_edit::*;
+/// # fn setup(mut commands: Commands) {
+/// commands.insert_resource(PrimarySelectionBackend::new(MemorySelection::default()));
+/// # }
+/// ```
+#[derive(Resource)]
+pub struct PrimarySelectionBackend(Box<dyn SelectionBackend>);
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+type PlatformSelection = X11Selection;
+
+#[cfg(not(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+)))]
+type PlatformSelection = MemorySelection;
+
+impl Default for PrimarySelectionBackend {
+    fn default() -> Self {
+        Self::new(PlatformSelection::default())
+    }
+}
+
+impl PrimarySelectionBackend {
+    pub fn new(backend: impl SelectionBackend) -> Self {
+        Self(Box::new(backend))
+    }
+
+    pub fn get_text(&mut self) -> Option<String> {
+        self.0.get_text()
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.0.set_text(text);
+    }
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] to follow the Linux
+/// primary selection convention.
+///
+/// Selecting text in the focused widget makes it the primary selection, and a middle click
+/// pastes the primary selection where it is clicked, focused or not. [`Password`] widgets take
+/// no part in it.
+#[derive(Component, Default)]
+pub struct PrimarySelection {
+    /// Text last published, so it is only written again once it changes
+    published: Option<String>,
+}
+
+/// Selected text to publish, when it differs from what was published last
+fn selection_update(published: &Option<String>, selected: Option<String>) -> Option<String> {
+    selected.filter(|text| !text.is_empty() && published.as_ref() != Some(text))
+}
+
+/// Publishes the selection of the focused widget once the pointer lets go of it
+fn update_primary_selection(
+    active_editor: Res<FocusedWidget>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut backend: ResMut<PrimarySelectionBackend>,
+    mut q: Query<(&mut PrimarySelection, &CosmicEditor), Without<Password>>,
+) {
+    // Dragging out a selection would write it every frame
+    if buttons.pressed(MouseButton::Left) {
+        return;
+    }
+    let Some(entity) = active_editor.0 else {
+        return;
+    };
+    let Ok((mut primary, editor)) = q.get_mut(entity) else {
+        return;
+    };
+
+    let selected = editor.copy_all_selections();
+    if selected.is_none() {
+        // Selecting the same text again publishes it again
+        primary.bypass_change_detection().published = None;
+        return;
+    }
+    if let Some(text) = selection_update(&primary.published, selected) {
+        backend.set_text(&text);
+        primary.bypass_change_detection().published = Some(text);
+    }
+}
+
+/// Pastes the primary selection at the pointer on middle click
+fn paste_primary_selection(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mut backend: ResMut<PrimarySelectionBackend>,
+    mut q: Query<
+        (
+            Entity,
+            &mut CosmicBuffer,
+            Option<&mut CosmicEditor>,
+            (&GlobalTransform, &Sprite, &Visibility),
+            (&CosmicTextAlign, &CosmicWrap),
+            &XOffset,
+            Option<&LineNumbers>,
+            Option<&SmoothScroll>,
+            (&MaxChars, &MaxLines),
+            Option<&mut EditHistory>,
+            (
+                Option<&InputFilter>,
+                Option<&mut Placeholder>,
+                &DefaultAttrs,
+            ),
+        ),
+        (With<PrimarySelection>, Without<ReadOnly>, Without<Password>),
+    >,
+    node_q: Query<(&Node, &GlobalTransform, &CosmicSource)>,
+    mut font_system: ResMut<CosmicFontSystem>,
+    mut evw_rejected: EventWriter<CosmicInputRejected>,
+    mut evw_changed: EventWriter<CosmicTextChanged>,
+) {
+    if !buttons.just_pressed(MouseButton::Middle) {
+        return;
+    }
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Some(pointer) = window.cursor_position() else {
+        return;
+    };
+    let Some(camera) = camera_q.iter().find(|(c, _)| c.is_active) else {
+        return;
+    };
+    let scale_factor = window.scale_factor();
+
+    for (
+        entity,
+        mut buffer,
+        mut editor_opt,
+        (sprite_transform, sprite, visibility),
+        (position, mode),
+        x_offset,
+        numbers_opt,
+        smooth_opt,
+        (max_chars, max_lines),
+        mut history_opt,
+        (filter_opt, mut placeholder_opt, attrs),
+    ) in q.iter_mut()
+    {
+        if visibility == Visibility::Hidden {
+            continue;
+        }
+        let node = widget_node(entity, (sprite_transform, sprite), &node_q);
+        let hit = |b: &Buffer| {
+            pointer_hit(
+                pointer,
+                b,
+                node,
+                camera,
+                scale_factor,
+                position,
+                mode,
+                x_offset,
+                numbers_opt,
+                smooth_opt,
+            )
+            .map(|(_, cursor)| cursor)
+        };
+        let found = match editor_opt.as_ref() {
+            Some(editor) => editor.with_buffer(hit),
+            None => hit(&buffer),
+        };
+        let Some(at) = found else {
+            continue;
+        };
+
+        let Some(text) = backend.get_text() else {
+            return;
+        };
+        let _ = insert_text(
+            entity,
+            &text,
+            Some(at),
+            &mut buffer,
+            editor_opt.as_deref_mut(),
+            history_opt.as_deref_mut(),
+            filter_opt,
+            placeholder_opt.as_deref_mut(),
+            attrs,
+            max_chars,
+            max_lines,
+            &mut font_system.0,
+            &mut evw_rejected,
+            &mut evw_changed,
+        );
+        return;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publishes_changed_selections() {
+        let mut backend = PrimarySelectionBackend::new(MemorySelection::default());
+        assert_eq!(backend.get_text(), None);
+        backend.set_text("hello");
+        assert_eq!(backend.get_text(), Some("hello".into()));
+
+        let published = Some("hello".to_string());
+        assert_eq!(selection_update(&published, Some("hello".into())), None);
+        assert_eq!(selection_update(&published, Some(String::new())), None);
+        assert_eq!(
+            selection_update(&published, Some("world".into())),
+            Some("world".into())
+        );
+        assert_eq!(
+            selection_update(&None, Some("hello".into())),
+            Some("hello".into())
+        );
+    }
+}
@@ -37,7 +37,45 @@ pub(crate) fn blink_cursor(mut q: Query<&mut CosmicEditor, Without<ReadOnly>>, t
     }
 }
 
-fn draw_pixel(buffer: &mut [u8], width: i32, height: i32, x: i32, y: i32, color: Color) {
+/// Highlights the matches of a [`CosmicSearch`], to be drawn under the text
+fn draw_search_matches(
+    buffer: &cosmic_text::Buffer,
+    search: &CosmicSearch,
+    tabs_opt: Option<&ExpandedTabs>,
+    draw: &mut impl FnMut(i32, i32, u32, u32, Color),
+) {
+    let display = |c| tabs_opt.map_or(c, |t: &ExpandedTabs| t.display_cursor(c));
+    let to_color = |c: bevy::prelude::Color| {
+        Color::rgba(
+            (c.r() * 255.) as u8,
+            (c.g() * 255.) as u8,
+            (c.b() * 255.) as u8,
+            (c.a() * 255.) as u8,
+        )
+    };
+
+    let line_height = buffer.metrics().line_height;
+    for (i, (start, end)) in search.matches().iter().enumerate() {
+        let color = if search.current() == Some(i) {
+            to_color(search.current_color)
+        } else {
+            to_color(search.highlight_color)
+        };
+        for run in buffer.layout_runs() {
+            if let Some((x, w)) = run.highlight(display(*start), display(*end)) {
+                draw(
+                    x as i32,
+                    run.line_top as i32,
+                    w as u32,
+                    line_height as u32,
+                    color,
+                );
+            }
+        }
+    }
+}
+
+pub(crate) fn draw_pixel(buffer: &mut [u8], width: i32, height: i32, x: i32, y: i32, color: Color) {
     let a_a = color.a() as u32;
     if a_a == 0 {
         // Do not draw if alpha is zero
@@ -90,12 +128,24 @@ fn render_texture(
         &CosmicWidgetSize,
         &CosmicPadding,
         &XOffset,
-        Option<&ReadOnly>,
-        &CosmicTextAlign,
+        (
+            Option<&ReadOnly>,
+            Option<&ImePreedit>,
+            Option<&VimState>,
+            Option<&CosmicSearch>,
+            Option<&mut LineNumbers>,
+            Option<&TextOverflow>,
+            Option<&Scrollbars>,
+            Option<&SmoothScroll>,
+            Option<&SelectionHandles>,
+            Option<&TextDrag>,
+            Option<&Indentation>,
+        ),
     )>,
     mut font_system: ResMut<CosmicFontSystem>,
     mut images: ResMut<Assets<Image>>,
     mut swash_cache_state: ResMut<SwashCacheState>,
+    touch_settings: Res<TouchSettings>,
 ) {
     for (
         editor,
@@ -109,8 +159,19 @@ fn render_texture(
         size,
         padding,
         x_offset,
-        readonly_opt,
-        position,
+        (
+            readonly_opt,
+            preedit_opt,
+            vim_state_opt,
+            search_opt,
+            mut numbers_opt,
+            overflow_opt,
+            scrollbars_opt,
+            smooth_opt,
+            handles_opt,
+            drag_opt,
+            indentation_opt,
+        ),
     ) in query.iter_mut()
     {
         // Draw background
@@ -148,22 +209,25 @@ fn render_texture(
             .0
             .color_opt
             .unwrap_or(cosmic_text::Color::rgb(0, 0, 0));
+        let drop_caret = drag_opt.and_then(|d| d.drop_caret());
+        let drop_color = cursor_color.0.to_cosmic();
 
-        let min_pad = match position {
-            CosmicTextAlign::Center { padding } => *padding as f32,
-            CosmicTextAlign::TopLeft { padding } => *padding as f32,
-            CosmicTextAlign::Left { padding } => *padding as f32,
+        // Text scrolled by part of a line is drawn that much higher
+        let line_height = match editor.as_ref() {
+            Some(editor) => editor.with_buffer(|b| b.metrics().line_height),
+            None => buffer.metrics().line_height,
         };
+        let padding_y = padding.y - smooth_opt.map_or(0., |s| s.partial_line(line_height));
 
-        let draw_closure = |x, y, w, h, color| {
+        let mut draw_closure = |x: i32, y: i32, w: u32, h: u32, color: Color| {
             for row in 0..h as i32 {
                 for col in 0..w as i32 {
                     draw_pixel(
                         &mut pixels,
                         size.0.x as i32,
                         size.0.y as i32,
-                        x + col + padding.x.max(min_pad) as i32 - x_offset.left as i32,
-                        y + row + padding.y as i32,
+                        x + col + padding.x as i32 - x_offset.left as i32,
+                        y + row + padding_y as i32,
                         color,
                     );
                 }
@@ -171,16 +235,31 @@ fn render_texture(
         };
 
         // Draw glyphs
-        if let Some(mut editor) = editor {
-            if !editor.redraw() {
+        if let Some(mut live) = editor {
+            if !live.redraw() {
                 continue;
             }
 
-            let cursor_opacity = if editor.cursor_visible && readonly_opt.is_none() {
-                (cursor_color.0.a() * 255.) as u8
-            } else {
-                0
+            // Tabs are drawn at their width from a copy, the edited text keeps them
+            let (mut expanded, tabs_opt) = match indentation_opt
+                .and_then(|i| expand_editor_tabs(&live, i.tab_width, &mut font_system.0))
+            {
+                Some((shown, tabs)) => (Some(shown), Some(tabs)),
+                None => (None, None),
             };
+            let display = |c| tabs_opt.as_ref().map_or(c, |t| t.display_cursor(c));
+            let editor = expanded.as_mut().unwrap_or(&mut live);
+
+            // While composing, the input method decides whether the cursor is shown
+            let preedit_hides_cursor =
+                preedit_opt.is_some_and(|p| p.range().is_some() && p.cursor.is_none());
+
+            let cursor_opacity =
+                if editor.cursor_visible && readonly_opt.is_none() && !preedit_hides_cursor {
+                    (cursor_color.0.a() * 255.) as u8
+                } else {
+                    0
+                };
 
             let cursor_color = Color::rgba(
                 (cursor_color.r() * 255.) as u8,
@@ -189,6 +268,45 @@ fn render_texture(
                 cursor_opacity,
             );
 
+            // Vim shows a block over the character under the cursor outside of insert mode
+            let block_cursor = vim_state_opt.is_some_and(|s| !s.is_insert());
+            if block_cursor {
+                let cursor = editor.cursor();
+                editor.with_buffer(|b| {
+                    let line_height = b.metrics().line_height;
+                    for run in b.layout_runs() {
+                        let Some(x) = cursor_x_in_run(&cursor, &run) else {
+                            continue;
+                        };
+                        let next = run.text[cursor.index..].chars().next().map(|c| {
+                            cosmic_text::Cursor::new(cursor.line, cursor.index + c.len_utf8())
+                        });
+                        let (x, w) = next
+                            .and_then(|next| run.highlight(cursor, next))
+                            .unwrap_or((x, b.metrics().font_size / 2.));
+                        draw_closure(
+                            x as i32,
+                            run.line_top as i32,
+                            w.max(1.) as u32,
+                            line_height as u32,
+                            // Translucent, so the character stays readable
+                            Color::rgba(
+                                cursor_color.r(),
+                                cursor_color.g(),
+                                cursor_color.b(),
+                                cursor_color.a() / 2,
+                            ),
+                        );
+                        break;
+                    }
+                });
+            }
+            let cursor_color = if block_cursor {
+                Color::rgba(0, 0, 0, 0)
+            } else {
+                cursor_color
+            };
+
             let selection_color = Color::rgba(
                 (selection_color.r() * 255.) as u8,
                 (selection_color.g() * 255.) as u8,
@@ -196,28 +314,179 @@ fn render_texture(
                 (selection_color.a() * 255.) as u8,
             );
 
+            if let Some(search) = search_opt {
+                editor.with_buffer(|b| {
+                    draw_search_matches(b, search, tabs_opt.as_ref(), &mut draw_closure)
+                });
+            }
+
+            // Selections of extra cursors go under the text, like the primary selection
+            let secondary_cursors = editor.secondary_cursors.clone();
+            editor.with_buffer(|b| {
+                let line_height = b.metrics().line_height;
+                for (start, end) in secondary_cursors
+                    .iter()
+                    .filter_map(|c| c.selection_bounds())
+                {
+                    for run in b.layout_runs() {
+                        if let Some((x, w)) = run.highlight(start, end) {
+                            draw_closure(
+                                x as i32,
+                                run.line_top as i32,
+                                w as u32,
+                                line_height as u32,
+                                selection_color,
+                            );
+                        }
+                    }
+                }
+            });
+
             editor.draw(
                 &mut font_system.0,
                 &mut swash_cache_state.swash_cache,
                 font_color,
                 cursor_color,
                 selection_color,
-                draw_closure,
+                &mut draw_closure,
             );
-            editor.set_redraw(false);
+            if padding_y < padding.y {
+                editor.with_buffer_mut(|b| {
+                    draw_partial_line(
+                        b,
+                        &mut font_system.0,
+                        &mut swash_cache_state.swash_cache,
+                        font_color,
+                        &mut draw_closure,
+                    )
+                });
+            }
+
+            if let (Some(_), Some(bounds)) = (handles_opt, editor.selection_bounds()) {
+                editor.with_buffer(|b| {
+                    draw_selection_handles(b, bounds, &touch_settings, &mut draw_closure)
+                });
+            }
+
+            editor.with_buffer(|b| {
+                let line_height = b.metrics().line_height;
+                for secondary in secondary_cursors.iter() {
+                    if let Some((x, top)) = get_cursor_position(b, secondary.cursor) {
+                        draw_closure(x as i32, top as i32, 1, line_height as u32, cursor_color);
+                    }
+                }
+                // Where dragged text would be dropped
+                if let Some((x, top)) = drop_caret.and_then(|c| get_cursor_position(b, display(c)))
+                {
+                    draw_closure(x as i32, top as i32, 2, line_height as u32, drop_color);
+                }
+            });
+
+            // Underline the preedit text, with a thicker line under the input method's cursor
+            if let Some(preedit) = preedit_opt {
+                if let Some((start, end)) = preedit.range() {
+                    let (start, end) = (display(start), display(end));
+                    let clause = preedit
+                        .cursor_range()
+                        .filter(|(s, e)| s != e)
+                        .map(|(s, e)| (display(s), display(e)));
+                    editor.with_buffer(|b| {
+                        let line_height = b.metrics().line_height;
+                        for run in b.layout_runs() {
+                            let bottom = (run.line_top + line_height) as i32;
+                            if let Some((x, w)) = run.highlight(start, end) {
+                                draw_closure(x as i32, bottom - 2, w as u32, 1, font_color);
+                            }
+                            if let Some((clause_start, clause_end)) = clause {
+                                if let Some((x, w)) = run.highlight(clause_start, clause_end) {
+                                    draw_closure(x as i32, bottom - 3, w as u32, 2, font_color);
+                                }
+                            }
+                        }
+                    });
+                }
+            }
+
+            if let Some(numbers) = numbers_opt.as_mut() {
+                let current_line = editor.cursor().line;
+                editor.with_buffer(|b| {
+                    draw_line_numbers(
+                        numbers.bypass_change_detection(),
+                        b,
+                        Some(current_line),
+                        &attrs.0,
+                        &mut font_system.0,
+                        &mut swash_cache_state.swash_cache,
+                        &mut pixels,
+                        size.0,
+                        padding_y,
+                    )
+                });
+            }
+
+            live.set_redraw(false);
         } else {
             if !buffer.redraw() {
                 continue;
             }
-            buffer.draw(
+            // Text that does not fit is drawn shortened
+            let shortened = overflow_opt.and_then(|o| o.display());
+            // Tabs are drawn at their width from a copy, the text keeps them
+            let (mut expanded, tabs_opt) = match (shortened, indentation_opt) {
+                (None, Some(indentation)) => {
+                    match expand_tabs(&buffer, indentation.tab_width, &mut font_system.0) {
+                        Some((display, tabs)) => (Some(display), Some(tabs)),
+                        None => (None, None),
+                    }
+                }
+                _ => (None, None),
+            };
+            if shortened.is_none() && padding_y < padding.y {
+                draw_partial_line(
+                    expanded.as_mut().unwrap_or(&mut buffer.0),
+                    &mut font_system.0,
+                    &mut swash_cache_state.swash_cache,
+                    font_color,
+                    &mut draw_closure,
+                );
+            }
+            let shown = expanded.as_ref().or(shortened).unwrap_or(&buffer.0);
+            if let (Some(search), None) = (search_opt, shortened) {
+                draw_search_matches(shown, search, tabs_opt.as_ref(), &mut draw_closure);
+            }
+            shown.draw(
                 &mut font_system.0,
                 &mut swash_cache_state.swash_cache,
                 font_color,
-                draw_closure,
+                &mut draw_closure,
             );
+            if let (Some(caret), None) = (drop_caret, shortened) {
+                let caret = tabs_opt.as_ref().map_or(caret, |t| t.display_cursor(caret));
+                if let Some((x, top)) = get_cursor_position(shown, caret) {
+                    draw_closure(x as i32, top as i32, 2, line_height as u32, drop_color);
+                }
+            }
+            if let Some(numbers) = numbers_opt.as_mut() {
+                draw_line_numbers(
+                    numbers.bypass_change_detection(),
+                    shown,
+                    None,
+                    &attrs.0,
+                    &mut font_system.0,
+                    &mut swash_cache_state.swash_cache,
+                    &mut pixels,
+                    size.0,
+                    padding_y,
+                );
+            }
             buffer.set_redraw(false);
         }
 
+        if let Some(scrollbars) = scrollbars_opt {
+            let gutter = numbers_opt.as_deref().map_or(0., |n| n.width());
+            draw_scrollbars(scrollbars, &mut pixels, size.0, gutter);
+        }
+
         if let Some(prev_image) = images.get_mut(canvas) {
             prev_image.data.clear();
             prev_image.data.extend_from_slice(pixels.as_slice());
@@ -0,0 +1,315 @@
+#![allow(clippy::too_many_arguments)]
+
+use crate::*;
+use bevy::prelude::*;
+use cosmic_text::AttrsList;
+#[cfg(not(target_arch = "wasm32"))]
+use cosmic_text::{Action, Change, Edit};
+
+pub(crate) struct RichClipboardPlugin;
+
+impl Plugin for RichClipboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RichClipboard>();
+    }
+}
+
+/// Lines of styled spans, as returned by [`CosmicBuffer::get_text_spans`]
+pub type TextSpans = Vec<Vec<(String, AttrsOwned)>>;
+
+/// Styled spans of the text last copied or cut from a widget.
+///
+/// The system clipboard only keeps plain text and HTML, so the spans are kept here and used
+/// when the clipboard still holds the same text on paste.
+#[derive(Resource, Default)]
+pub struct RichClipboard {
+    text: String,
+    spans: TextSpans,
+}
+
+impl RichClipboard {
+    /// Spans copied with `text`, if it is still what was copied last
+    pub fn spans_for(&self, text: &str) -> Option<&TextSpans> {
+        (!self.spans.is_empty() && self.text == text).then_some(&self.spans)
+    }
+
+    pub(crate) fn store(&mut self, text: String, spans: TextSpans) {
+        self.text = text;
+        self.spans = spans;
+    }
+
+    /// Writes `text` to the system clipboard, with HTML of its spans when it is a single
+    /// selection
+    #[cfg(not(target_arch = "wasm32"))]
+    fn copy(
+        &mut self,
+        clipboard: &mut arboard::Clipboard,
+        editor: &CosmicEditor,
+        text: String,
+        attrs: &DefaultAttrs,
+    ) {
+        let spans = selection_spans(editor, attrs).filter(|spans| spans_text(spans) == text);
+        match spans {
+            Some(spans) => {
+                let html = spans_to_html(&spans);
+                self.store(text.clone(), spans);
+                if clipboard.set_html(html, Some(text.clone())).is_err() {
+                    let _ = clipboard.set_text(text);
+                }
+            }
+            None => {
+                self.store(text.clone(), Vec::new());
+                let _ = clipboard.set_text(text);
+            }
+        }
+    }
+
+    /// Runs the copy, cut and paste `commands` on the focused widget through the system
+    /// clipboard.
+    ///
+    /// Copies keep the spans of the selection, which [`RichPaste`] widgets paste back. Returns
+    /// whether the text was cut or pasted, and the change that made.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn run_commands(
+        &mut self,
+        commands: &[EditorCommand],
+        editor: &mut CosmicEditor,
+        buffer: &CosmicBuffer,
+        entity: Entity,
+        attrs: &DefaultAttrs,
+        rich_paste_opt: Option<&RichPaste>,
+        filter_opt: Option<&InputFilter>,
+        max_chars: &MaxChars,
+        max_lines: &MaxLines,
+        editable: bool,
+        font_system: &mut FontSystem,
+        evw_rejected: &mut EventWriter<CosmicInputRejected>,
+    ) -> (bool, Option<Change>) {
+        let Ok(mut clipboard) = arboard::Clipboard::new() else {
+            return (false, None);
+        };
+
+        if commands.contains(&EditorCommand::Copy) {
+            if let Some(text) = editor.copy_all_selections() {
+                self.copy(&mut clipboard, editor, text, attrs);
+                return (false, None);
+            }
+        }
+        let mut is_clipboard = false;
+        let mut change = None;
+        if commands.contains(&EditorCommand::Cut) && editable {
+            if let Some(text) = editor.copy_all_selections() {
+                self.copy(&mut clipboard, editor, text, attrs);
+                change = editor.edit_all_cursors(font_system, |editor, _| {
+                    editor.delete_selection();
+                });
+            }
+            is_clipboard = true;
+        }
+        if commands.contains(&EditorCommand::Paste) && editable {
+            // Spans copied from a widget keep their attributes
+            let rich = clipboard.get_text().ok().and_then(|text| {
+                self.spans_for(&text)
+                    .filter(|_| rich_paste_opt.is_some() && filter_opt.is_none())
+                    .filter(|_| !editor.has_secondary_cursors())
+                    .cloned()
+            });
+            if let Some(spans) = rich {
+                let text = spans_text(&spans);
+                let current = editor.with_buffer(|b| b.get_text());
+                if fits_limits(&current, &text, max_chars, max_lines) {
+                    let attrs_list = spans_attrs_list(&spans, &attrs.0);
+                    change = editor.edit_all_cursors(font_system, |editor, _| {
+                        editor.delete_selection();
+                        let cursor = editor.cursor();
+                        let end = editor.insert_at(cursor, &text, Some(attrs_list.clone()));
+                        editor.set_cursor(end);
+                    });
+                }
+            } else if let Ok(text) = clipboard.get_text() {
+                let text = filter_input(filter_opt, entity, &text, evw_rejected);
+                change = editor.edit_all_cursors(font_system, |editor, font_system| {
+                    for c in text.chars() {
+                        if max_chars.0 == 0 || buffer.get_text().len() < max_chars.0 {
+                            if c == 0xA as char {
+                                if max_lines.0 == 0 || buffer.lines.len() < max_lines.0 {
+                                    editor.action(font_system, Action::Insert(c));
+                                }
+                            } else {
+                                editor.action(font_system, Action::Insert(c));
+                            }
+                        }
+                    }
+                });
+            }
+            is_clipboard = true;
+        }
+        (is_clipboard, change)
+    }
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] to keep the colors, weights
+/// and families of text pasted from another widget.
+///
+/// Widgets without it paste plain text in their [`DefaultAttrs`].
+#[derive(Component, Default)]
+pub struct RichPaste;
+
+/// Spans of the selection of `editor`, when it has a single one
+pub(crate) fn selection_spans(editor: &CosmicEditor, attrs: &DefaultAttrs) -> Option<TextSpans> {
+    if editor.has_secondary_cursors() {
+        return None;
+    }
+    let (start, end) = editor.selection_bounds()?;
+    let lines = editor.with_buffer(|b| CosmicBuffer(b.clone()).get_text_spans(attrs.0.clone()));
+    Some(spans_in_range(&lines, start, end))
+}
+
+/// Parts of `lines` between `start` and `end`
+fn spans_in_range(lines: &[Vec<(String, AttrsOwned)>], start: Cursor, end: Cursor) -> TextSpans {
+    (start.line..=end.line)
+        .filter_map(|i| lines.get(i).map(|spans| (i, spans)))
+        .map(|(i, spans)| {
+            let from = if i == start.line { start.index } else { 0 };
+            let to = if i == end.line { end.index } else { usize::MAX };
+            let mut offset = 0;
+            let mut line = Vec::new();
+            for (text, attrs) in spans {
+                let (span_start, span_end) = (offset, offset + text.len());
+                offset = span_end;
+                let (a, b) = (from.max(span_start), to.min(span_end));
+                if a < b {
+                    line.push((
+                        text[a - span_start..b - span_start].to_string(),
+                        attrs.clone(),
+                    ));
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+/// Plain text of `spans`
+pub(crate) fn spans_text(spans: &TextSpans) -> String {
+    spans
+        .iter()
+        .map(|line| {
+            line.iter()
+                .map(|(text, _)| text.as_str())
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Attributes of `spans` as a list over their plain text, for [`Edit::insert_at`]
+pub(crate) fn spans_attrs_list(spans: &TextSpans, default_attrs: &AttrsOwned) -> AttrsList {
+    let mut list = AttrsList::new(default_attrs.as_attrs());
+    let mut offset = 0;
+    for (i, line) in spans.iter().enumerate() {
+        if i > 0 {
+            // The newline between lines
+            offset += 1;
+        }
+        for (text, attrs) in line {
+            if !text.is_empty() {
+                list.add_span(offset..offset + text.len(), attrs.as_attrs());
+            }
+            offset += text.len();
+        }
+    }
+    list
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// CSS of a span's attributes
+fn span_style(attrs: &AttrsOwned) -> String {
+    let family = match &attrs.family_owned {
+        FamilyOwned::Name(name) => format!("'{}'", escape_html(name).replace('\'', "\\'")),
+        FamilyOwned::Serif => "serif".into(),
+        FamilyOwned::SansSerif => "sans-serif".into(),
+        FamilyOwned::Cursive => "cursive".into(),
+        FamilyOwned::Fantasy => "fantasy".into(),
+        FamilyOwned::Monospace => "monospace".into(),
+    };
+    let mut style = format!("font-family:{family};font-weight:{}", attrs.weight.0);
+    match attrs.style {
+        FontStyle::Italic => style.push_str(";font-style:italic"),
+        FontStyle::Oblique => style.push_str(";font-style:oblique"),
+        FontStyle::Normal => {}
+    }
+    if let Some(color) = attrs.color_opt {
+        style.push_str(&format!(
+            ";color:rgba({},{},{},{:.3})",
+            color.r(),
+            color.g(),
+            color.b(),
+            color.a() as f32 / 255.
+        ));
+    }
+    style
+}
+
+/// HTML of `spans` for other apps, one styled `<span>` per span
+pub(crate) fn spans_to_html(spans: &TextSpans) -> String {
+    let lines: Vec<String> = spans
+        .iter()
+        .map(|line| {
+            line.iter()
+                .map(|(text, attrs)| {
+                    format!(
+                        "<span style=\"{}\">{}</span>",
+                        span_style(attrs),
+                        escape_html(text)
+                    )
+                })
+                .collect()
+        })
+        .collect();
+    format!(
+        "<div style=\"white-space:pre-wrap\">{}</div>",
+        lines.join("<br>")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copied_spans() {
+        let plain = AttrsOwned::new(Attrs::new().family(Family::Monospace));
+        let red = AttrsOwned::new(
+            Attrs::new()
+                .color(CosmicColor::rgb(255, 0, 0))
+                .weight(FontWeight::BOLD),
+        );
+        let lines = vec![
+            vec![("one ".to_string(), plain.clone()), ("<two>".into(), red)],
+            vec![("three".into(), plain.clone())],
+        ];
+
+        let spans = spans_in_range(&lines, Cursor::new(0, 2), Cursor::new(1, 3));
+        assert_eq!(spans_text(&spans), "e <two>\nthr");
+        assert_eq!(
+            spans_to_html(&spans),
+            "<div style=\"white-space:pre-wrap\">\
+             <span style=\"font-family:monospace;font-weight:400\">e </span>\
+             <span style=\"font-family:sans-serif;font-weight:700;color:rgba(255,0,0,1.000)\">\
+             &lt;two&gt;</span><br>\
+             <span style=\"font-family:monospace;font-weight:400\">thr</span></div>"
+        );
+
+        let mut clipboard = RichClipboard::default();
+        clipboard.store(spans_text(&spans), spans);
+        assert!(clipboard.spans_for("e <two>\nthr").is_some());
+        assert!(clipboard.spans_for("other").is_none());
+    }
+}
@@ -0,0 +1,508 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use crate::*;
+use bevy::{prelude::*, window::PrimaryWindow};
+use cosmic_text::{BufferLine, Scroll};
+
+pub(crate) struct ScrollbarPlugin;
+
+impl Plugin for ScrollbarPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PreUpdate,
+            scrollbar_mouse.in_set(InputSet).before(input_mouse),
+        )
+        .add_systems(
+            PostUpdate,
+            update_scrollbars.after(WidgetSet).before(RenderSet),
+        );
+    }
+}
+
+/// Scroll state along one axis, in lines vertically and buffer pixels horizontally
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct BarMetrics {
+    total: f32,
+    visible: f32,
+    offset: f32,
+}
+
+impl BarMetrics {
+    /// Start and length of the thumb along a track `track` long
+    fn thumb(&self, track: f32, min_thumb: f32) -> Option<(f32, f32)> {
+        if self.total <= self.visible || track <= 0. {
+            return None;
+        }
+        let len = (track * self.visible / self.total).clamp(min_thumb.min(track), track);
+        let progress = (self.offset / (self.total - self.visible)).clamp(0., 1.);
+        Some(((track - len) * progress, len))
+    }
+
+    /// Offset that puts the thumb at `start` along the track
+    fn offset_at(&self, start: f32, track: f32, min_thumb: f32) -> f32 {
+        let Some((_, len)) = self.thumb(track, min_thumb) else {
+            return 0.;
+        };
+        let progress = (start / (track - len).max(1.)).clamp(0., 1.);
+        progress * (self.total - self.visible)
+    }
+}
+
+/// Thumb drag in progress
+#[derive(Clone, Copy, Debug)]
+struct ScrollDrag {
+    vertical: bool,
+    /// Pointer position inside the thumb when the drag started
+    grab: f32,
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] to draw scrollbars over the
+/// right and bottom edges of the widget.
+///
+/// The vertical bar follows the scroll of the [`CosmicBuffer`], the horizontal bar the
+/// [`XOffset`] of [`CosmicWrap::InfiniteLine`] widgets. Bars are only shown when the text does
+/// not fit. Dragging the thumb scrolls, clicking the track scrolls by a page.
+#[derive(Component, Clone, Debug)]
+pub struct Scrollbars {
+    pub vertical: bool,
+    pub horizontal: bool,
+    /// Width of the bars in buffer pixels
+    pub thickness: f32,
+    /// Shortest thumb length in buffer pixels
+    pub min_thumb: f32,
+    pub track_color: Color,
+    pub thumb_color: Color,
+    /// Color of the thumb while dragged
+    pub active_color: Color,
+    /// Seconds without scrolling after which the bars are hidden, [`None`] to always show them
+    pub auto_hide: Option<f32>,
+    vertical_metrics: Option<BarMetrics>,
+    horizontal_metrics: Option<BarMetrics>,
+    /// Seconds since the last scroll or pointer activity on the bars
+    idle: f32,
+    shown: bool,
+    drag: Option<ScrollDrag>,
+    /// Whether the pointer press of this frame belongs to the bars
+    captured: bool,
+}
+
+impl Default for Scrollbars {
+    fn default() -> Self {
+        Self {
+            vertical: true,
+            horizontal: true,
+            thickness: 8.,
+            min_thumb: 16.,
+            track_color: Color::rgba(0., 0., 0., 0.05),
+            thumb_color: Color::rgba(0., 0., 0., 0.35),
+            active_color: Color::rgba(0., 0., 0., 0.55),
+            auto_hide: Some(1.5),
+            vertical_metrics: None,
+            horizontal_metrics: None,
+            idle: 0.,
+            shown: false,
+            drag: None,
+            captured: false,
+        }
+    }
+}
+
+impl Scrollbars {
+    pub fn vertical() -> Self {
+        Self {
+            horizontal: false,
+            ..default()
+        }
+    }
+
+    pub fn horizontal() -> Self {
+        Self {
+            vertical: false,
+            ..default()
+        }
+    }
+
+    /// Whether the bars take the pointer press of this frame, so it does not move the cursor
+    pub(crate) fn captures_pointer(&self) -> bool {
+        self.captured || self.drag.is_some()
+    }
+
+    fn overflows(&self, metrics: Option<BarMetrics>) -> bool {
+        metrics.is_some_and(|m| m.total > m.visible)
+    }
+
+    /// Vertical and horizontal tracks in buffer pixels, for the bars that are needed
+    fn tracks(&self, size: Vec2, gutter: f32) -> (Option<Rect>, Option<Rect>) {
+        let vertical = self.vertical && self.overflows(self.vertical_metrics);
+        let horizontal = self.horizontal && self.overflows(self.horizontal_metrics);
+        let t = self.thickness;
+        let corner = |other: bool| if other { t } else { 0. };
+        (
+            vertical.then(|| Rect::new(size.x - t, 0., size.x, size.y - corner(horizontal))),
+            horizontal.then(|| Rect::new(gutter, size.y - t, size.x - corner(vertical), size.y)),
+        )
+    }
+
+    /// Thumb inside `track`
+    fn thumb(&self, track: Rect, vertical: bool) -> Option<Rect> {
+        if vertical {
+            let (start, len) = self
+                .vertical_metrics?
+                .thumb(track.height(), self.min_thumb)?;
+            Some(Rect::new(
+                track.min.x,
+                track.min.y + start,
+                track.max.x,
+                track.min.y + start + len,
+            ))
+        } else {
+            let (start, len) = self
+                .horizontal_metrics?
+                .thumb(track.width(), self.min_thumb)?;
+            Some(Rect::new(
+                track.min.x + start,
+                track.min.y,
+                track.min.x + start + len,
+                track.max.y,
+            ))
+        }
+    }
+}
+
+/// Layout lines of `line`, one if it is not laid out yet
+fn layout_len(line: &BufferLine) -> usize {
+    line.layout_opt().as_ref().map_or(1, |layout| layout.len())
+}
+
+/// Layout lines of `buffer`, counting lines that are not laid out yet as one
+pub(crate) fn layout_line_count(buffer: &Buffer) -> usize {
+    buffer.lines.iter().map(layout_len).sum()
+}
+
+/// Layout lines above the top of the view of `buffer`
+pub(crate) fn scroll_offset(buffer: &Buffer) -> i32 {
+    let scroll = buffer.scroll();
+    let above: usize = buffer.lines[..scroll.line.min(buffer.lines.len())]
+        .iter()
+        .map(layout_len)
+        .sum();
+    above as i32 + scroll.layout
+}
+
+/// Scrolls `buffer` so `offset` layout lines are above the top of the view
+pub(crate) fn set_scroll_offset(buffer: &mut Buffer, offset: i32) {
+    let mut layout = offset.max(0);
+    let mut line = 0;
+    while line + 1 < buffer.lines.len() {
+        let len = layout_len(&buffer.lines[line]) as i32;
+        if layout < len {
+            break;
+        }
+        layout -= len;
+        line += 1;
+    }
+    buffer.set_scroll(Scroll::new(line, layout));
+}
+
+/// Vertical scroll of `buffer`, in layout lines
+fn vertical_metrics(buffer: &Buffer) -> BarMetrics {
+    BarMetrics {
+        total: layout_line_count(buffer) as f32,
+        visible: buffer.visible_lines() as f32,
+        offset: scroll_offset(buffer) as f32,
+    }
+}
+
+fn horizontal_metrics(buffer: &Buffer, x_offset: &XOffset) -> BarMetrics {
+    let width = buffer
+        .layout_runs()
+        .map(|run| run.line_w)
+        .fold(0., f32::max);
+    BarMetrics {
+        // Room for the caret after the last glyph
+        total: width + 1.,
+        visible: x_offset.width,
+        offset: x_offset.left,
+    }
+}
+
+/// Follows the scroll state, and hides idle bars
+fn update_scrollbars(
+    mut q: Query<(
+        &mut Scrollbars,
+        &mut CosmicBuffer,
+        Option<&mut CosmicEditor>,
+        &XOffset,
+        &CosmicWrap,
+    )>,
+    time: Res<Time>,
+) {
+    for (mut bars, mut buffer, editor_opt, x_offset, mode) in q.iter_mut() {
+        let (vertical, horizontal) = {
+            let metrics = |b: &Buffer| {
+                (
+                    vertical_metrics(b),
+                    // The offset is only measured once the widget was focused
+                    (*mode == CosmicWrap::InfiniteLine && x_offset.width > 0.)
+                        .then(|| horizontal_metrics(b, x_offset)),
+                )
+            };
+            match editor_opt.as_ref() {
+                Some(editor) => editor.with_buffer(metrics),
+                None => metrics(&buffer),
+            }
+        };
+
+        // Only state kept for drawing, keeps `Changed<Scrollbars>` for user changes
+        let bars = bars.bypass_change_detection();
+        let mut redraw = false;
+        if bars.vertical_metrics != Some(vertical) || bars.horizontal_metrics != horizontal {
+            bars.vertical_metrics = Some(vertical);
+            bars.horizontal_metrics = horizontal;
+            bars.idle = 0.;
+            redraw = true;
+        } else if bars.drag.is_none() {
+            bars.idle += time.delta_seconds();
+        }
+
+        let shown = match bars.auto_hide {
+            Some(delay) => bars.idle < delay,
+            None => true,
+        };
+        if bars.shown != shown {
+            bars.shown = shown;
+            redraw = true;
+        }
+
+        if redraw {
+            match editor_opt {
+                Some(mut editor) => editor.set_redraw(true),
+                None => buffer.set_redraw(true),
+            }
+        }
+    }
+}
+
+/// Drags thumbs and pages on clicks on the track
+fn scrollbar_mouse(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mut q: Query<(
+        Entity,
+        &mut Scrollbars,
+        &mut CosmicBuffer,
+        Option<&mut CosmicEditor>,
+        &mut XOffset,
+        &CosmicWidgetSize,
+        &GlobalTransform,
+        &Sprite,
+        Option<&LineNumbers>,
+    )>,
+    node_q: Query<(&Node, &GlobalTransform, &CosmicSource)>,
+    mut font_system: ResMut<CosmicFontSystem>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Some((camera, camera_transform)) = camera_q.iter().find(|(c, _)| c.is_active) else {
+        return;
+    };
+    let scale_factor = window.scale_factor();
+
+    for (
+        entity,
+        mut bars,
+        mut buffer,
+        mut editor_opt,
+        mut x_offset,
+        size,
+        sprite_transform,
+        sprite,
+        numbers_opt,
+    ) in q.iter_mut()
+    {
+        // Pointer state only, keeps `Changed<Scrollbars>` for user changes
+        let bars = bars.bypass_change_detection();
+        bars.captured = false;
+        if !buttons.pressed(MouseButton::Left) {
+            bars.drag = None;
+        }
+
+        let mut transform = sprite_transform;
+        let mut node_size = sprite.custom_size.unwrap_or(Vec2::ONE);
+        let mut is_ui_node = false;
+        for (node, node_transform, source) in node_q.iter() {
+            if source.0 == entity {
+                is_ui_node = true;
+                transform = node_transform;
+                node_size = node.size();
+            }
+        }
+        let Some(pointer) = get_node_cursor_pos(
+            window,
+            transform,
+            (node_size.x, node_size.y),
+            is_ui_node,
+            camera,
+            camera_transform,
+        ) else {
+            continue;
+        };
+        let pointer = Vec2::new(pointer.0, pointer.1) * scale_factor;
+
+        let (vertical_track, horizontal_track) = bars.tracks(size.0, gutter_width(numbers_opt));
+        let on_track = |track: Option<Rect>| track.filter(|t| t.contains(pointer));
+
+        // Hovering the bars shows them
+        if on_track(vertical_track).is_some() || on_track(horizontal_track).is_some() {
+            bars.idle = 0.;
+        }
+        if !bars.shown && bars.drag.is_none() {
+            continue;
+        }
+
+        let mut vertical_target = None;
+        let mut horizontal_target = None;
+
+        if buttons.just_pressed(MouseButton::Left) {
+            for (track, vertical) in [(vertical_track, true), (horizontal_track, false)] {
+                let Some(track) = on_track(track) else {
+                    continue;
+                };
+                let Some(thumb) = bars.thumb(track, vertical) else {
+                    continue;
+                };
+                bars.captured = true;
+                let (pos, thumb_start, thumb_end) = if vertical {
+                    (pointer.y, thumb.min.y, thumb.max.y)
+                } else {
+                    (pointer.x, thumb.min.x, thumb.max.x)
+                };
+
+                if pos < thumb_start || pos > thumb_end {
+                    // A page towards the click
+                    let page = if pos < thumb_start { -1. } else { 1. };
+                    let metrics = if vertical {
+                        bars.vertical_metrics
+                    } else {
+                        bars.horizontal_metrics
+                    };
+                    if let Some(m) = metrics {
+                        let target = m.offset + page * m.visible;
+                        if vertical {
+                            vertical_target = Some(target);
+                        } else {
+                            horizontal_target = Some(target);
+                        }
+                    }
+                } else {
+                    bars.drag = Some(ScrollDrag {
+                        vertical,
+                        grab: pos - thumb_start,
+                    });
+                }
+            }
+        } else if let Some(drag) = bars.drag {
+            let track = if drag.vertical {
+                vertical_track
+            } else {
+                horizontal_track
+            };
+            let metrics = if drag.vertical {
+                bars.vertical_metrics
+            } else {
+                bars.horizontal_metrics
+            };
+            if let (Some(track), Some(m)) = (track, metrics) {
+                if drag.vertical {
+                    let start = pointer.y - drag.grab - track.min.y;
+                    vertical_target = Some(m.offset_at(start, track.height(), bars.min_thumb));
+                } else {
+                    let start = pointer.x - drag.grab - track.min.x;
+                    horizontal_target = Some(m.offset_at(start, track.width(), bars.min_thumb));
+                }
+            }
+        }
+
+        if let (Some(target), Some(m)) = (vertical_target, bars.vertical_metrics) {
+            let scroll = target.clamp(0., (m.total - m.visible).max(0.)).round() as i32;
+            match editor_opt.as_mut() {
+                Some(editor) => {
+                    editor.with_buffer_mut(|b| set_scroll_offset(b, scroll));
+                    editor.set_redraw(true);
+                }
+                None => {
+                    set_scroll_offset(&mut buffer, scroll);
+                    buffer.shape_until_scroll(&mut font_system.0, false);
+                    buffer.set_redraw(true);
+                }
+            }
+        }
+        if let (Some(target), Some(m)) = (horizontal_target, bars.horizontal_metrics) {
+            x_offset.left = target.clamp(0., (m.total - m.visible).max(0.));
+            match editor_opt.as_mut() {
+                Some(editor) => editor.set_redraw(true),
+                None => buffer.set_redraw(true),
+            }
+        }
+    }
+}
+
+/// Draws the bars over the edges of the widget
+pub(crate) fn draw_scrollbars(bars: &Scrollbars, pixels: &mut [u8], size: Vec2, gutter: f32) {
+    if !bars.shown {
+        return;
+    }
+
+    let (width, height) = (size.x as i32, size.y as i32);
+    let mut fill = |rect: Rect, color: Color| {
+        let color = color.to_cosmic();
+        for y in rect.min.y as i32..rect.max.y as i32 {
+            for x in rect.min.x as i32..rect.max.x as i32 {
+                draw_pixel(pixels, width, height, x, y, color);
+            }
+        }
+    };
+
+    let (vertical_track, horizontal_track) = bars.tracks(size, gutter);
+    for (track, vertical) in [(vertical_track, true), (horizontal_track, false)] {
+        let Some(track) = track else {
+            continue;
+        };
+        fill(track, bars.track_color);
+        if let Some(thumb) = bars.thumb(track, vertical) {
+            let dragged = bars.drag.is_some_and(|d| d.vertical == vertical);
+            let color = if dragged {
+                bars.active_color
+            } else {
+                bars.thumb_color
+            };
+            fill(thumb, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thumb_geometry() {
+        let metrics = BarMetrics {
+            total: 100.,
+            visible: 25.,
+            offset: 75.,
+        };
+        assert_eq!(metrics.thumb(200., 10.), Some((150., 50.)));
+        assert_eq!(metrics.offset_at(75., 200., 10.), 37.5);
+        assert_eq!(metrics.thumb(20., 10.), Some((10., 10.)));
+
+        let fits = BarMetrics {
+            total: 10.,
+            visible: 25.,
+            offset: 0.,
+        };
+        assert_eq!(fits.thumb(200., 10.), None);
+    }
+}
@@ -0,0 +1,611 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use crate::*;
+use bevy::prelude::*;
+use cosmic_text::{Buffer, Cursor, Edit, Editor, Selection};
+use regex::{Regex, RegexBuilder};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// System set for find and replace. Runs in [`Update`]
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SearchSet;
+
+pub(crate) struct SearchPlugin;
+
+impl Plugin for SearchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (kb_search, update_search_matches, apply_search_actions)
+                .chain()
+                .in_set(SearchSet)
+                .after(InputSet)
+                .before(HistorySet),
+        );
+    }
+}
+
+/// What to search for with [`CosmicSearch`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    pub text: String,
+    pub case_sensitive: bool,
+    /// Only match whole words
+    pub whole_word: bool,
+    /// Treat `text` as a regular expression. Replacements can use `$1`-style captures.
+    pub regex: bool,
+}
+
+impl SearchQuery {
+    /// Case sensitive search for plain `text`
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            case_sensitive: true,
+            ..default()
+        }
+    }
+
+    /// Regular expression search
+    pub fn regex(pattern: impl Into<String>) -> Self {
+        Self {
+            regex: true,
+            ..Self::new(pattern)
+        }
+    }
+
+    pub fn case_insensitive(mut self) -> Self {
+        self.case_sensitive = false;
+        self
+    }
+
+    pub fn whole_word(mut self) -> Self {
+        self.whole_word = true;
+        self
+    }
+
+    /// Compiles the query, failing on invalid regular expressions
+    pub fn build(&self) -> Result<Regex, regex::Error> {
+        let pattern = if self.regex {
+            self.text.clone()
+        } else {
+            regex::escape(&self.text)
+        };
+        let pattern = if self.whole_word {
+            format!(r"\b(?:{pattern})\b")
+        } else {
+            pattern
+        };
+
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .multi_line(true)
+            .build()
+    }
+
+    /// Byte ranges of all non-empty matches in `text`
+    pub fn find_in(&self, text: &str) -> Result<Vec<(usize, usize)>, regex::Error> {
+        if self.text.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(self
+            .build()?
+            .find_iter(text)
+            .filter(|m| !m.is_empty())
+            .map(|m| (m.start(), m.end()))
+            .collect())
+    }
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] to search its text.
+///
+/// Matches of `query` are highlighted, F3 and Shift+F3 select the next and previous match, and
+/// [`CosmicSearchAction`] events drive a search bar. Every change of the matches is reported
+/// with a [`CosmicSearchMatches`] event.
+///
+/// Works on unfocused widgets too, so the search bar can hold focus.
+#[derive(Component, Debug)]
+pub struct CosmicSearch {
+    /// Current query, [`None`] to stop searching
+    pub query: Option<SearchQuery>,
+    /// Text matches are replaced with
+    pub replacement: String,
+    /// Highlight of all matches
+    pub highlight_color: Color,
+    /// Highlight of the current match
+    pub current_color: Color,
+    matches: Vec<(Cursor, Cursor)>,
+    current: Option<usize>,
+    error: Option<String>,
+    /// Query and hash of the text the matches were found for
+    searched: Option<(SearchQuery, u64)>,
+}
+
+impl Default for CosmicSearch {
+    fn default() -> Self {
+        Self {
+            query: None,
+            replacement: String::new(),
+            highlight_color: Color::rgba(1., 0.85, 0., 0.35),
+            current_color: Color::rgba(1., 0.55, 0., 0.6),
+            matches: Vec::new(),
+            current: None,
+            error: None,
+            searched: None,
+        }
+    }
+}
+
+impl CosmicSearch {
+    pub fn new(query: SearchQuery) -> Self {
+        Self {
+            query: Some(query),
+            ..default()
+        }
+    }
+
+    /// Start and end of every match, in buffer order
+    pub fn matches(&self) -> &[(Cursor, Cursor)] {
+        &self.matches
+    }
+
+    /// Index of the current match into [`CosmicSearch::matches`]
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Error message for an invalid regular expression
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    fn step(&mut self, forward: bool, from: Option<Cursor>) {
+        if self.matches.is_empty() {
+            self.current = None;
+            return;
+        }
+
+        let len = self.matches.len();
+        self.current = Some(match (self.current, from) {
+            (Some(i), _) if forward => (i + 1) % len,
+            (Some(i), _) => (i + len - 1) % len,
+            (None, Some(from)) if forward => self
+                .matches
+                .iter()
+                .position(|(start, _)| (start.line, start.index) >= (from.line, from.index))
+                .unwrap_or(0),
+            (None, Some(from)) => self
+                .matches
+                .iter()
+                .rposition(|(_, end)| (end.line, end.index) <= (from.line, from.index))
+                .unwrap_or(len - 1),
+            (None, None) if forward => 0,
+            (None, None) => len - 1,
+        });
+    }
+}
+
+/// Something to do with the matches of a [`CosmicSearch`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchAction {
+    /// Select the next match, scrolling it into view
+    Next,
+    /// Select the previous match, scrolling it into view
+    Previous,
+    /// Replace the current match and move on to the next
+    ReplaceOne,
+    /// Replace every match, as a single undo step
+    ReplaceAll,
+}
+
+impl CosmicBuffer {
+    /// Start and end of every match of `query`
+    pub fn find(&self, query: &SearchQuery) -> Result<Vec<(Cursor, Cursor)>, regex::Error> {
+        find_in_buffer(self, query)
+    }
+}
+
+impl CosmicEditor {
+    /// Start and end of every match of `query`
+    pub fn find(&self, query: &SearchQuery) -> Result<Vec<(Cursor, Cursor)>, regex::Error> {
+        self.with_buffer(|b| find_in_buffer(b, query))
+    }
+}
+
+fn find_in_buffer(
+    buffer: &Buffer,
+    query: &SearchQuery,
+) -> Result<Vec<(Cursor, Cursor)>, regex::Error> {
+    let text = buffer.get_text();
+    Ok(query
+        .find_in(&text)?
+        .into_iter()
+        .map(|(start, end)| {
+            (
+                offset_to_cursor(buffer, start),
+                offset_to_cursor(buffer, end),
+            )
+        })
+        .collect())
+}
+
+/// Replaces `ranges`, given in buffer order, with their replacement text
+fn replace_ranges(editor: &mut Editor<'_>, ranges: &[(Cursor, Cursor, String)]) {
+    // Back to front, so earlier cursors stay valid
+    for (start, end, text) in ranges.iter().rev() {
+        editor.delete_range(*start, *end);
+        editor.insert_at(*start, text, None);
+    }
+}
+
+/// Text each match is replaced with, expanding captures for regular expressions
+fn replacements(
+    text: &str,
+    buffer: &Buffer,
+    query: &SearchQuery,
+    replacement: &str,
+    matches: &[(Cursor, Cursor)],
+) -> Vec<(Cursor, Cursor, String)> {
+    let regex = query.build().ok();
+    matches
+        .iter()
+        .map(|(start, end)| {
+            let replaced = match (&regex, query.regex) {
+                (Some(regex), true) => {
+                    expand_match(regex, text, cursor_to_offset(buffer, *start), replacement)
+                }
+                _ => replacement.to_string(),
+            };
+            (*start, *end, replaced)
+        })
+        .collect()
+}
+
+/// `replacement` with the captures of the match of `regex` starting at `start` in `text`.
+///
+/// Captures come from the whole text, so anchors and word boundaries still see what surrounds
+/// the match.
+fn expand_match(regex: &Regex, text: &str, start: usize, replacement: &str) -> String {
+    let mut expanded = String::new();
+    match regex.captures_at(text, start) {
+        Some(captures) => captures.expand(replacement, &mut expanded),
+        None => expanded.push_str(replacement),
+    }
+    expanded
+}
+
+fn kb_search(
+    active_editor: Res<FocusedWidget>,
+    keys: Res<ButtonInput<KeyCode>>,
+    editor_q: Query<Option<&CosmicKeymap>, With<CosmicEditor>>,
+    keymap: Res<CosmicKeymap>,
+    mut evw_action: EventWriter<CosmicSearchAction>,
+    mut evw_opened: EventWriter<CosmicSearchOpened>,
+) {
+    let Some(entity) = active_editor.0 else {
+        return;
+    };
+    let Ok(keymap_opt) = editor_q.get(entity) else {
+        return;
+    };
+
+    for command in keymap_opt.unwrap_or(&*keymap).just_pressed(&keys) {
+        match command {
+            EditorCommand::Find => {
+                evw_opened.send(CosmicSearchOpened {
+                    entity,
+                    replace: false,
+                });
+            }
+            EditorCommand::Replace => {
+                evw_opened.send(CosmicSearchOpened {
+                    entity,
+                    replace: true,
+                });
+            }
+            EditorCommand::FindNext => {
+                evw_action.send(CosmicSearchAction {
+                    entity,
+                    action: SearchAction::Next,
+                });
+            }
+            EditorCommand::FindPrevious => {
+                evw_action.send(CosmicSearchAction {
+                    entity,
+                    action: SearchAction::Previous,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+fn apply_search_actions(
+    mut evr_action: EventReader<CosmicSearchAction>,
+    mut q: Query<(
+        &mut CosmicSearch,
+        &mut CosmicBuffer,
+        Option<&mut CosmicEditor>,
+        Option<&mut EditHistory>,
+        Option<&ReadOnly>,
+    )>,
+    mut evw_changed: EventWriter<CosmicTextChanged>,
+    mut evw_matches: EventWriter<CosmicSearchMatches>,
+    mut font_system: ResMut<CosmicFontSystem>,
+) {
+    for ev in evr_action.read() {
+        let Ok((mut search, mut buffer, mut editor_opt, history_opt, readonly_opt)) =
+            q.get_mut(ev.entity)
+        else {
+            continue;
+        };
+        let Some(query) = search.query.clone() else {
+            continue;
+        };
+
+        match ev.action {
+            SearchAction::Next | SearchAction::Previous => {
+                let from = editor_opt.as_ref().map(|e| e.cursor());
+                search.step(ev.action == SearchAction::Next, from);
+
+                let Some((start, end)) = search.current.map(|i| search.matches[i]) else {
+                    continue;
+                };
+                if let Some(editor) = editor_opt.as_mut() {
+                    editor.set_selection(Selection::Normal(start));
+                    editor.set_cursor(end);
+                    // Shaping scrolls the buffer to the cursor
+                    editor.shape_as_needed(&mut font_system.0, false);
+                    editor.set_redraw(true);
+                } else {
+                    buffer.set_redraw(true);
+                }
+            }
+            SearchAction::ReplaceOne | SearchAction::ReplaceAll => {
+                if readonly_opt.is_some() || search.matches.is_empty() {
+                    continue;
+                }
+
+                let targets: Vec<(Cursor, Cursor)> = match ev.action {
+                    SearchAction::ReplaceAll => search.matches.clone(),
+                    _ => {
+                        let from = editor_opt.as_ref().map(|e| e.cursor());
+                        if search.current.is_none() {
+                            search.step(true, from);
+                        }
+                        search
+                            .current
+                            .map(|i| vec![search.matches[i]])
+                            .unwrap_or_default()
+                    }
+                };
+                if targets.is_empty() {
+                    continue;
+                }
+
+                let (text, change) = match editor_opt.as_mut() {
+                    Some(editor) => {
+                        let before = (editor.cursor(), editor.selection());
+                        let ranges = editor.with_buffer(|b| {
+                            replacements(&b.get_text(), b, &query, &search.replacement, &targets)
+                        });
+
+                        editor.clear_secondary_cursors();
+                        editor.start_change();
+                        replace_ranges(&mut editor.editor, &ranges);
+                        let change = editor.finish_change();
+
+                        let first = ranges[0].0;
+                        editor.set_selection(Selection::None);
+                        editor.set_cursor(first);
+                        editor.shape_as_needed(&mut font_system.0, false);
+                        editor.set_redraw(true);
+
+                        if let (Some(change), Some(mut history)) = (change.clone(), history_opt) {
+                            history.seal();
+                            history.record(change, before, editor);
+                            history.seal();
+                        }
+                        (editor.with_buffer(|b| b.get_text()), change)
+                    }
+                    None => {
+                        let ranges = replacements(
+                            &buffer.get_text(),
+                            &buffer,
+                            &query,
+                            &search.replacement,
+                            &targets,
+                        );
+
+                        let change = {
+                            let mut editor = Editor::new(&mut buffer.0);
+                            editor.start_change();
+                            replace_ranges(&mut editor, &ranges);
+                            editor.finish_change()
+                        };
+                        buffer.shape_until_scroll(&mut font_system.0, false);
+                        buffer.set_redraw(true);
+
+                        let text = buffer.get_text();
+                        if let (Some(change), Some(mut history)) = (change.clone(), history_opt) {
+                            let cursor = ranges[0].0;
+                            history.seal();
+                            history.record_with(
+                                change,
+                                (cursor, Selection::None),
+                                (cursor, Selection::None),
+                                text.clone(),
+                            );
+                            history.seal();
+                        }
+                        (text, change)
+                    }
+                };
+
+                if change.is_some() {
+                    // The current index now points at the match after the replaced one
+                    let (matches, hash) = match editor_opt.as_deref() {
+                        Some(editor) => (editor.find(&query), editor.with_buffer(text_hash)),
+                        None => (buffer.find(&query), text_hash(&buffer)),
+                    };
+                    let search = search.bypass_change_detection();
+                    set_matches(ev.entity, search, matches, hash, &mut evw_matches);
+                    evw_changed.send(CosmicTextChanged((ev.entity, text)));
+                }
+            }
+        }
+    }
+}
+
+/// Finds matches again when the query or the text changed
+fn update_search_matches(
+    mut q: Query<
+        (
+            Entity,
+            &mut CosmicSearch,
+            &mut CosmicBuffer,
+            Option<&mut CosmicEditor>,
+            Option<&Placeholder>,
+        ),
+        Or<(
+            Changed<CosmicSearch>,
+            Changed<CosmicBuffer>,
+            Changed<CosmicEditor>,
+            Changed<Placeholder>,
+        )>,
+    >,
+    mut evw_matches: EventWriter<CosmicSearchMatches>,
+) {
+    for (entity, mut search, mut buffer, mut editor_opt, placeholder_opt) in q.iter_mut() {
+        let placeholder_active = placeholder_opt.is_some_and(|p| p.is_active());
+        let hash = if placeholder_active {
+            0
+        } else if let Some(editor) = editor_opt.as_ref() {
+            editor.with_buffer(text_hash)
+        } else {
+            text_hash(&buffer)
+        };
+
+        let unchanged = match (&search.query, &search.searched) {
+            (Some(query), Some(searched)) => *query == searched.0 && hash == searched.1,
+            (None, None) => true,
+            _ => false,
+        };
+        if unchanged {
+            continue;
+        }
+
+        let matches = match (&search.query, editor_opt.as_deref()) {
+            (None, _) => Ok(Vec::new()),
+            _ if placeholder_active => Ok(Vec::new()),
+            (Some(query), Some(editor)) => editor.find(query),
+            (Some(query), None) => buffer.find(query),
+        };
+        // Only a cache update, keeps `Changed<CosmicSearch>` for user changes
+        set_matches(
+            entity,
+            search.bypass_change_detection(),
+            matches,
+            hash,
+            &mut evw_matches,
+        );
+
+        match editor_opt.as_mut() {
+            Some(editor) => editor.bypass_change_detection().set_redraw(true),
+            None => buffer.bypass_change_detection().set_redraw(true),
+        }
+    }
+}
+
+/// Hash of the text of `buffer`, to notice changes without copying it
+fn text_hash(buffer: &Buffer) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for line in buffer.lines.iter() {
+        line.text().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Stores the matches found in the text hashed to `hash`, reporting when they changed
+fn set_matches(
+    entity: Entity,
+    search: &mut CosmicSearch,
+    result: Result<Vec<(Cursor, Cursor)>, regex::Error>,
+    hash: u64,
+    evw_matches: &mut EventWriter<CosmicSearchMatches>,
+) {
+    let previous = (search.matches.len(), search.current);
+    match result {
+        Ok(matches) => {
+            search.matches = matches;
+            search.error = None;
+        }
+        Err(e) => {
+            search.matches.clear();
+            search.error = Some(e.to_string());
+        }
+    }
+
+    let query_changed = search.searched.as_ref().map(|(q, _)| q) != search.query.as_ref();
+    search.current = match search.current {
+        _ if search.matches.is_empty() => None,
+        Some(_) if query_changed => None,
+        Some(i) => Some(i.min(search.matches.len() - 1)),
+        None => None,
+    };
+    search.searched = search.query.clone().map(|query| (query, hash));
+
+    if previous != (search.matches.len(), search.current) || query_changed {
+        evw_matches.send(CosmicSearchMatches {
+            entity,
+            count: search.matches.len(),
+            current: search.current,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_modes() {
+        let text = "Cat cat concat cat.";
+        assert_eq!(SearchQuery::new("cat").find_in(text).unwrap().len(), 3);
+        assert_eq!(
+            SearchQuery::new("cat")
+                .case_insensitive()
+                .find_in(text)
+                .unwrap()
+                .len(),
+            4
+        );
+        assert_eq!(
+            SearchQuery::new("cat").whole_word().find_in(text).unwrap(),
+            vec![(4, 7), (15, 18)]
+        );
+        assert_eq!(
+            SearchQuery::new("t.").find_in(text).unwrap(),
+            vec![(17, 19)]
+        );
+        assert_eq!(
+            SearchQuery::regex("c.t$").find_in(text).unwrap(),
+            Vec::new()
+        );
+        assert!(SearchQuery::regex("(").find_in(text).is_err());
+    }
+
+    #[test]
+    fn replacements_see_the_whole_text() {
+        let text = "foo bar\nfoo";
+        let query = SearchQuery::regex(r"\B(o)|^(f)");
+        let regex = query.build().unwrap();
+        let expanded: Vec<_> = query
+            .find_in(text)
+            .unwrap()
+            .into_iter()
+            .map(|(start, _)| expand_match(&regex, text, start, "[$1$2]"))
+            .collect();
+        assert_eq!(expanded, ["[f]", "[o]", "[o]", "[f]", "[o]", "[o]"]);
+    }
+}
@@ -0,0 +1,294 @@
+#![allow(clippy::type_complexity)]
+
+use crate::*;
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+use cosmic_text::{Color, SwashCache};
+
+pub(crate) struct SmoothScrollPlugin;
+
+impl Plugin for SmoothScrollPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PreUpdate,
+            smooth_scroll_input.in_set(InputSet).before(input_mouse),
+        )
+        .add_systems(
+            PostUpdate,
+            animate_smooth_scroll.after(WidgetSet).before(RenderSet),
+        );
+    }
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] to scroll it by pixels instead
+/// of whole lines.
+///
+/// Wheel and touchpad deltas are added up, so small movements are not lost, and the view eases
+/// towards where they lead. The [`CosmicBuffer`] keeps scrolling by whole lines, the rest is
+/// drawn as an offset of part of a line.
+#[derive(Component, Clone, Debug)]
+pub struct SmoothScroll {
+    /// How fast the view catches up with the wheel, [`None`] to jump there. Higher is faster,
+    /// about two thirds of the distance left is covered in `1 / easing` seconds.
+    pub easing: Option<f32>,
+    /// Pixels from the top of the text the view is heading to
+    target: f32,
+    /// Pixels from the top of the text the view is at
+    position: f32,
+    /// Buffer scroll `position` was last applied as, to notice scrolling by other means
+    synced: Option<i32>,
+}
+
+impl Default for SmoothScroll {
+    fn default() -> Self {
+        Self {
+            easing: Some(12.),
+            target: 0.,
+            position: 0.,
+            synced: None,
+        }
+    }
+}
+
+impl SmoothScroll {
+    /// Pixel scrolling without animation
+    pub fn instant() -> Self {
+        Self {
+            easing: None,
+            ..default()
+        }
+    }
+
+    /// Pixels from the top of the text to the top of the view
+    pub fn offset(&self) -> f32 {
+        self.position
+    }
+
+    /// Moves the target down by `pixels`, staying between the top and `bottom`
+    pub(crate) fn scroll_by(&mut self, pixels: f32, bottom: f32) {
+        self.target = (self.target + pixels).clamp(0., bottom);
+    }
+
+    /// Part of a line the view is scrolled past the buffer's scroll, in buffer pixels
+    pub(crate) fn partial_line(&self, line_height: f32) -> f32 {
+        match self.synced {
+            Some(scroll) => (self.position - scroll as f32 * line_height).max(0.),
+            None => 0.,
+        }
+    }
+}
+
+/// Furthest the top of the view can scroll down in a widget `height` buffer pixels high
+pub(crate) fn scroll_bottom(buffer: &Buffer, height: f32) -> f32 {
+    (layout_line_count(buffer) as f32 * buffer.metrics().line_height - height).max(0.)
+}
+
+/// Draws the layout line below the last whole line in view, which scrolling by part of a line
+/// brings into view. The buffer is only as high as the widget, so it is not a layout run.
+pub(crate) fn draw_partial_line(
+    buffer: &mut Buffer,
+    font_system: &mut FontSystem,
+    cache: &mut SwashCache,
+    color: Color,
+    mut f: impl FnMut(i32, i32, u32, u32, Color),
+) {
+    let line_height = buffer.metrics().line_height;
+    let runs = buffer.layout_runs().count();
+    if line_height <= 0. || runs < (buffer.size().1 / line_height) as usize {
+        // The text ends in view
+        return;
+    }
+
+    // Layout line `runs` lines below the top of the view
+    let scroll = buffer.scroll();
+    let mut line_i = scroll.line;
+    let mut skip = scroll.layout.max(0) as usize + runs;
+    let layout_line = loop {
+        let Some(layout) = buffer.line_layout(font_system, line_i) else {
+            return;
+        };
+        if let Some(layout_line) = layout.get(skip) {
+            break layout_line.clone();
+        }
+        skip -= layout.len();
+        line_i += 1;
+    };
+
+    // Placed like a layout run
+    let line_top = runs as f32 * line_height;
+    let glyph_height = layout_line.max_ascent + layout_line.max_descent;
+    let line_y = line_top + (line_height - glyph_height) / 2. + layout_line.max_ascent;
+    for glyph in layout_line.glyphs.iter() {
+        let physical = glyph.physical((0., 0.), 1.);
+        let glyph_color = glyph.color_opt.unwrap_or(color);
+        cache.with_pixels(
+            font_system,
+            physical.cache_key,
+            glyph_color,
+            |x, y, color| {
+                f(physical.x + x, line_y as i32 + physical.y + y, 1, 1, color);
+            },
+        );
+    }
+}
+
+/// Next position easing from `position` to `target` over `delta` seconds
+fn ease(position: f32, target: f32, easing: Option<f32>, delta: f32) -> f32 {
+    let Some(speed) = easing else {
+        return target;
+    };
+    let next = position + (target - position) * (1. - (-speed * delta).exp());
+    // Close enough to stop animating
+    if (target - next).abs() < 0.5 {
+        target
+    } else {
+        next
+    }
+}
+
+/// Adds wheel movement to the target of the focused widget
+fn smooth_scroll_input(
+    active_editor: Res<FocusedWidget>,
+    mut scroll_evr: EventReader<MouseWheel>,
+    mut q: Query<(&mut SmoothScroll, &CosmicEditor, &CosmicWidgetSize), Without<ScrollDisabled>>,
+) {
+    let Some(entity) = active_editor.0 else {
+        scroll_evr.clear();
+        return;
+    };
+    let Ok((mut smooth, editor, size)) = q.get_mut(entity) else {
+        scroll_evr.clear();
+        return;
+    };
+
+    let (line_height, bottom) =
+        editor.with_buffer(|b| (b.metrics().line_height, scroll_bottom(b, size.y)));
+    // Only animation state, keeps `Changed<SmoothScroll>` for user changes
+    let smooth = smooth.bypass_change_detection();
+    for ev in scroll_evr.read() {
+        let delta = match ev.unit {
+            MouseScrollUnit::Line => ev.y * line_height,
+            MouseScrollUnit::Pixel => ev.y,
+        };
+        smooth.scroll_by(-delta, bottom);
+    }
+}
+
+/// Moves the view towards its target, scrolling the buffer by the whole lines of it
+fn animate_smooth_scroll(
+    mut q: Query<(
+        &mut SmoothScroll,
+        &mut CosmicBuffer,
+        Option<&mut CosmicEditor>,
+    )>,
+    mut font_system: ResMut<CosmicFontSystem>,
+    time: Res<Time>,
+) {
+    for (mut smooth, mut buffer, editor_opt) in q.iter_mut() {
+        let (scroll, line_height) = match editor_opt.as_ref() {
+            Some(editor) => editor.with_buffer(|b| (scroll_offset(b), b.metrics().line_height)),
+            None => (scroll_offset(&buffer), buffer.metrics().line_height),
+        };
+
+        // Only animation state, keeps `Changed<SmoothScroll>` for user changes
+        let smooth = smooth.bypass_change_detection();
+        if smooth.synced != Some(scroll) {
+            // Scrolled by the cursor or the scrollbars, continue from there
+            smooth.position = scroll as f32 * line_height;
+            smooth.target = smooth.position;
+            smooth.synced = Some(scroll);
+        }
+        if smooth.position == smooth.target {
+            continue;
+        }
+
+        smooth.position = ease(
+            smooth.position,
+            smooth.target,
+            smooth.easing,
+            time.delta_seconds(),
+        );
+        let lines = (smooth.position / line_height).floor() as i32;
+
+        let scroll = match editor_opt {
+            Some(mut editor) => {
+                if lines != scroll {
+                    editor.with_buffer_mut(|b| set_scroll_offset(b, lines));
+                    editor.shape_as_needed(&mut font_system.0, false);
+                }
+                editor.set_redraw(true);
+                editor.with_buffer(scroll_offset)
+            }
+            None => {
+                if lines != scroll {
+                    set_scroll_offset(&mut buffer, lines);
+                    buffer.shape_until_scroll(&mut font_system.0, false);
+                }
+                buffer.set_redraw(true);
+                scroll_offset(&buffer)
+            }
+        };
+        if scroll != lines {
+            // The buffer stopped at the end of the text
+            smooth.position = scroll as f32 * line_height;
+            smooth.target = smooth.position;
+        }
+        smooth.synced = Some(scroll);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmic_text::{Attrs, Metrics, Shaping};
+
+    #[test]
+    fn easing() {
+        assert_eq!(ease(0., 100., None, 0.016), 100.);
+
+        let next = ease(0., 100., Some(12.), 0.1);
+        assert!(next > 60. && next < 75.);
+        assert_eq!(ease(99.8, 100., Some(12.), 0.016), 100.);
+
+        let smooth = SmoothScroll {
+            position: 50.,
+            synced: Some(2),
+            ..default()
+        };
+        assert_eq!(smooth.partial_line(20.), 10.);
+    }
+
+    #[test]
+    fn line_below_the_view_is_drawn() {
+        let mut font_system = create_cosmic_font_system(CosmicFontConfig {
+            load_system_fonts: false,
+            ..Default::default()
+        });
+        let mut cache = SwashCache::new();
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(20., 20.));
+        buffer.set_size(&mut font_system, 200., 40.);
+
+        let mut drawn_rows = |buffer: &mut Buffer, text: &str| {
+            buffer.set_text(&mut font_system, text, Attrs::new(), Shaping::Advanced);
+            buffer.shape_until_scroll(&mut font_system, false);
+            let mut rows = Vec::new();
+            draw_partial_line(
+                buffer,
+                &mut font_system,
+                &mut cache,
+                Color::rgb(0, 0, 0),
+                |_, y, _, _, _| rows.push(y),
+            );
+            rows
+        };
+
+        // Two lines fit, the third is below them
+        let rows = drawn_rows(&mut buffer, "a\nb\nc");
+        assert!(!rows.is_empty());
+        assert!(rows.iter().all(|y| *y >= 40 && *y < 60), "{rows:?}");
+
+        assert!(drawn_rows(&mut buffer, "a\nb").is_empty());
+    }
+}
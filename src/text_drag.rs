@@ -0,0 +1,430 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use crate::*;
+use bevy::{prelude::*, window::PrimaryWindow};
+use cosmic_text::{Action, Selection};
+
+pub(crate) struct TextDragPlugin;
+
+impl Plugin for TextDragPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, drag_text.in_set(InputSet).before(input_mouse));
+    }
+}
+
+/// Component to be added to entities with a [`CosmicEditBundle`] to drag selected text out of
+/// them and drop text into them.
+///
+/// Pressing inside the selection of the focused widget and dragging moves the selection to where
+/// it is dropped, in the same widget or in another one with a [`TextDrag`], focused or not.
+/// Holding Ctrl when dropping copies instead. A caret shows where the text will go, and
+/// [`CosmicTextDropped`] is sent after the drop.
+#[derive(Component, Default)]
+pub struct TextDrag {
+    /// Where dragged text would be dropped into this widget
+    drop_caret: Option<Cursor>,
+    /// A drag started from this widget's selection, so the press does not move its cursor
+    captured: bool,
+}
+
+impl TextDrag {
+    /// Where dragged text would be dropped into this widget, while it is dragged over it
+    pub fn drop_caret(&self) -> Option<Cursor> {
+        self.drop_caret
+    }
+
+    /// Whether the pointer is used for a drag from this widget
+    pub(crate) fn captures_pointer(&self) -> bool {
+        self.captured
+    }
+}
+
+/// Logical pixels the pointer moves before a press in the selection becomes a drag
+const DRAG_THRESHOLD: f32 = 4.;
+
+/// Drag of the selection of the focused widget
+struct DragState {
+    source: Entity,
+    /// Window position of the press
+    press: Vec2,
+    /// Text coordinates of the press in the source
+    press_point: Vec2,
+    text: String,
+    bounds: (Cursor, Cursor),
+    dragging: bool,
+}
+
+/// Whether inserting `inserted` into `text` stays within a widget's [`MaxChars`] and
+/// [`MaxLines`]
+pub(crate) fn fits_limits(
+    text: &str,
+    inserted: &str,
+    max_chars: &MaxChars,
+    max_lines: &MaxLines,
+) -> bool {
+    let chars_fit = max_chars.0 == 0 || text.len() + inserted.len() <= max_chars.0;
+    let lines = text.split('\n').count() + inserted.matches('\n').count();
+    let lines_fit = max_lines.0 == 0 || lines <= max_lines.0;
+    chars_fit && lines_fit
+}
+
+/// Byte offsets of the moved text after moving `start..end` to `drop`, which is outside of it
+fn moved_start(start: usize, end: usize, drop: usize, copy: bool) -> usize {
+    if copy || drop <= start {
+        drop
+    } else {
+        drop - (end - start)
+    }
+}
+
+/// Starts drags in the selection, previews and performs drops
+fn drag_text(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    active_editor: Res<FocusedWidget>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mut q: Query<(
+        Entity,
+        &mut TextDrag,
+        &mut CosmicBuffer,
+        Option<&mut CosmicEditor>,
+        (&GlobalTransform, &Sprite, &Visibility),
+        (&CosmicTextAlign, &CosmicWrap),
+        &XOffset,
+        Option<&LineNumbers>,
+        Option<&SmoothScroll>,
+        Option<&ReadOnly>,
+        (&MaxChars, &MaxLines),
+        Option<&mut EditHistory>,
+        (
+            Option<&InputFilter>,
+            Option<&mut Placeholder>,
+            &DefaultAttrs,
+        ),
+    )>,
+    node_q: Query<(&Node, &GlobalTransform, &CosmicSource)>,
+    mut font_system: ResMut<CosmicFontSystem>,
+    mut evw_dropped: EventWriter<CosmicTextDropped>,
+    mut evw_changed: EventWriter<CosmicTextChanged>,
+    mut evw_rejected: EventWriter<CosmicInputRejected>,
+    mut state: Local<Option<DragState>>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Some(camera) = camera_q.iter().find(|(c, _)| c.is_active) else {
+        return;
+    };
+    let scale_factor = window.scale_factor();
+    let pointer = window.cursor_position();
+
+    if state.is_none() && !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    // Text coordinates and cursor under the pointer in every widget it is over
+    let mut hits: Vec<(Entity, Vec2, Cursor)> = Vec::new();
+    if let Some(pointer) = pointer {
+        for (
+            entity,
+            _,
+            buffer,
+            editor_opt,
+            (sprite_transform, sprite, visibility),
+            (position, mode),
+            x_offset,
+            numbers_opt,
+            smooth_opt,
+            ..,
+        ) in q.iter()
+        {
+            if visibility == Visibility::Hidden {
+                continue;
+            }
+            let node = widget_node(entity, (sprite_transform, sprite), &node_q);
+            let hit = |b: &Buffer| {
+                pointer_hit(
+                    pointer,
+                    b,
+                    node,
+                    camera,
+                    scale_factor,
+                    position,
+                    mode,
+                    x_offset,
+                    numbers_opt,
+                    smooth_opt,
+                )
+            };
+            let found = match editor_opt {
+                Some(editor) => editor.with_buffer(hit),
+                None => hit(buffer),
+            };
+            if let Some((point, cursor)) = found {
+                hits.push((entity, point, cursor));
+            }
+        }
+    }
+
+    // A press in the selection of the focused widget
+    if state.is_none() {
+        let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        let alt = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+        let (Some(focused), Some(pointer)) = (active_editor.0, pointer) else {
+            return;
+        };
+        let Some((_, point, cursor)) = hits.iter().find(|(e, ..)| *e == focused).copied() else {
+            return;
+        };
+        let Ok((_, mut drag, _, Some(editor), ..)) = q.get_mut(focused) else {
+            return;
+        };
+        if shift || alt {
+            return;
+        }
+        let Some((start, end)) = editor.selection_bounds() else {
+            return;
+        };
+        let key = |c: Cursor| (c.line, c.index);
+        if start == end || key(cursor) < key(start) || key(cursor) >= key(end) {
+            return;
+        }
+        let Some(text) = editor.copy_selection() else {
+            return;
+        };
+        drag.captured = true;
+        *state = Some(DragState {
+            source: focused,
+            press: pointer,
+            press_point: point,
+            text,
+            bounds: (start, end),
+            dragging: false,
+        });
+        return;
+    }
+
+    let Some(drag_state) = state.as_mut() else {
+        return;
+    };
+
+    if buttons.pressed(MouseButton::Left) {
+        if !drag_state.dragging {
+            let moved = pointer.map_or(0., |p| p.distance(drag_state.press));
+            drag_state.dragging = moved > DRAG_THRESHOLD;
+        }
+        if !drag_state.dragging {
+            return;
+        }
+
+        // Drop caret preview
+        let target = hits.first().map(|(entity, _, cursor)| (*entity, *cursor));
+        for (entity, mut drag, mut buffer, editor_opt, ..) in q.iter_mut() {
+            let caret = target.filter(|(e, _)| *e == entity).map(|(_, c)| c);
+            if drag.drop_caret != caret {
+                drag.drop_caret = caret;
+                match editor_opt {
+                    Some(mut editor) => editor.set_redraw(true),
+                    None => buffer.set_redraw(true),
+                }
+            }
+        }
+        return;
+    }
+
+    // Released
+    let Some(drag_state) = state.take() else {
+        return;
+    };
+    for (_, mut drag, mut buffer, editor_opt, ..) in q.iter_mut() {
+        drag.captured = false;
+        if drag.drop_caret.take().is_some() {
+            match editor_opt {
+                Some(mut editor) => editor.set_redraw(true),
+                None => buffer.set_redraw(true),
+            }
+        }
+    }
+
+    if !drag_state.dragging {
+        // Only a click in the selection, which places the cursor
+        if let Ok((_, _, _, Some(mut editor), ..)) = q.get_mut(drag_state.source) {
+            editor.action(
+                &mut font_system.0,
+                Action::Click {
+                    x: drag_state.press_point.x as i32,
+                    y: drag_state.press_point.y as i32,
+                },
+            );
+        }
+        return;
+    }
+
+    let Some((target, _, drop)) = hits.first().copied() else {
+        return;
+    };
+    let copy = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let text = drag_state.text;
+    let (start, end) = drag_state.bounds;
+
+    if target == drag_state.source {
+        let Ok((_, _, _, Some(mut editor), _, _, _, _, _, readonly_opt, limits, history_opt, _)) =
+            q.get_mut(target)
+        else {
+            return;
+        };
+        let (start_off, end_off, drop_off, current) = editor.with_buffer(|b| {
+            (
+                cursor_to_offset(b, start),
+                cursor_to_offset(b, end),
+                cursor_to_offset(b, drop),
+                b.get_text(),
+            )
+        });
+        let inside = start_off < drop_off && drop_off < end_off;
+        if readonly_opt.is_some()
+            || inside
+            || (copy && !fits_limits(&current, &text, limits.0, limits.1))
+        {
+            return;
+        }
+
+        let before = (editor.cursor(), editor.selection());
+        editor.clear_secondary_cursors();
+        editor.start_change();
+        // The later position first, so the earlier one stays where it is
+        if drop_off >= end_off {
+            editor.insert_at(drop, &text, None);
+            if !copy {
+                editor.delete_range(start, end);
+            }
+        } else {
+            if !copy {
+                editor.delete_range(start, end);
+            }
+            editor.insert_at(drop, &text, None);
+        }
+        let change = editor.finish_change();
+
+        // The dropped text is selected
+        let moved = moved_start(start_off, end_off, drop_off, copy);
+        let (moved_start, moved_end) = editor.with_buffer(|b| {
+            (
+                offset_to_cursor(b, moved),
+                offset_to_cursor(b, moved + text.len()),
+            )
+        });
+        editor.set_selection(Selection::Normal(moved_start));
+        editor.set_cursor(moved_end);
+        editor.shape_as_needed(&mut font_system.0, false);
+        editor.set_redraw(true);
+
+        if let (Some(change), Some(mut history)) = (change, history_opt) {
+            history.seal();
+            history.record(change, before, &editor);
+            history.seal();
+        }
+        evw_changed.send(CosmicTextChanged((
+            target,
+            editor.with_buffer(|b| b.get_text()),
+        )));
+        evw_dropped.send(CosmicTextDropped {
+            source: drag_state.source,
+            target,
+            text,
+            moved: !copy,
+        });
+        return;
+    }
+
+    let Ok([source_item, target_item]) = q.get_many_mut([drag_state.source, target]) else {
+        return;
+    };
+    let (
+        _,
+        _,
+        mut target_buffer,
+        target_editor,
+        _,
+        _,
+        _,
+        _,
+        _,
+        readonly_opt,
+        limits,
+        mut target_history,
+        (filter_opt, mut placeholder_opt, attrs),
+    ) = target_item;
+    if readonly_opt.is_some() || target_editor.is_some() {
+        return;
+    }
+    let inserted = insert_text(
+        target,
+        &text,
+        Some(drop),
+        &mut target_buffer,
+        None,
+        target_history.as_deref_mut(),
+        filter_opt,
+        placeholder_opt.as_deref_mut(),
+        attrs,
+        limits.0,
+        limits.1,
+        &mut font_system.0,
+        &mut evw_rejected,
+        &mut evw_changed,
+    );
+    if inserted.is_err() {
+        return;
+    }
+
+    let (_, _, _, source_editor, _, _, _, _, _, source_readonly, _, source_history, _) =
+        source_item;
+    let moved = !copy && source_readonly.is_none();
+    if let (true, Some(mut editor)) = (moved, source_editor) {
+        let before = (editor.cursor(), editor.selection());
+        editor.clear_secondary_cursors();
+        editor.start_change();
+        editor.delete_range(start, end);
+        editor.set_selection(Selection::None);
+        editor.set_cursor(start);
+        let change = editor.finish_change();
+        editor.shape_as_needed(&mut font_system.0, false);
+        editor.set_redraw(true);
+        if let (Some(change), Some(mut history)) = (change, source_history) {
+            history.seal();
+            history.record(change, before, &editor);
+            history.seal();
+        }
+        evw_changed.send(CosmicTextChanged((
+            drag_state.source,
+            editor.with_buffer(|b| b.get_text()),
+        )));
+    }
+
+    evw_dropped.send(CosmicTextDropped {
+        source: drag_state.source,
+        target,
+        text,
+        moved,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drop_offsets_and_limits() {
+        // "hello world", moving "hello" after "world"
+        assert_eq!(moved_start(0, 5, 11, false), 6);
+        assert_eq!(moved_start(0, 5, 11, true), 11);
+        // Moving "world" to the start
+        assert_eq!(moved_start(6, 11, 0, false), 0);
+
+        assert!(fits_limits("ab", "cd", &MaxChars(4), &MaxLines(1)));
+        assert!(!fits_limits("ab", "cde", &MaxChars(4), &MaxLines(0)));
+        assert!(!fits_limits("ab", "c\nd", &MaxChars(0), &MaxLines(1)));
+    }
+}
@@ -0,0 +1,440 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use crate::*;
+use bevy::{prelude::*, window::PrimaryWindow};
+use cosmic_text::{Action, Motion, Selection};
+
+pub(crate) struct TouchPlugin;
+
+impl Plugin for TouchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchSettings>()
+            .add_systems(PreUpdate, input_touch.in_set(InputSet).after(input_mouse));
+    }
+}
+
+/// Resource to tune touch input of the focused widget
+#[derive(Resource, Clone, Debug)]
+pub struct TouchSettings {
+    /// Seconds a finger rests before it selects the word under it
+    pub long_press: f32,
+    /// Buffer pixels a finger moves before a touch scrolls instead of tapping
+    pub slop: f32,
+    /// Radius of the selection handles in buffer pixels
+    pub handle_radius: f32,
+    pub handle_color: Color,
+    /// How fast a flung view slows down, higher stops sooner
+    pub friction: f32,
+}
+
+impl Default for TouchSettings {
+    fn default() -> Self {
+        Self {
+            long_press: 0.5,
+            slop: 12.,
+            handle_radius: 10.,
+            handle_color: Color::rgb(0.2, 0.45, 0.95),
+            friction: 4.,
+        }
+    }
+}
+
+/// Marks a widget whose selection was made by touch, handles are drawn at the ends of the
+/// selection and can be dragged to change it
+#[derive(Component, Default)]
+pub struct SelectionHandles;
+
+/// What a finger on the focused widget does
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Gesture {
+    /// Resting for `held` seconds, could still become any of the others
+    Pending {
+        held: f32,
+    },
+    Scroll,
+    /// Moving the cursor end of the selection with a handle
+    Handle,
+    /// A long press selected a word, nothing else happens until the finger lifts
+    Selected,
+}
+
+#[derive(Default)]
+struct TouchState {
+    /// Id of the first finger on the widget, where it went down and what it does
+    touch: Option<(u64, Vec2, Gesture)>,
+    /// Scroll speed left after a fling, in buffer pixels per second
+    velocity: f32,
+    /// Part of a line scrolled without [`SmoothScroll`] that was not applied yet
+    remainder: f32,
+}
+
+/// Below this speed in buffer pixels per second a fling stops
+const MIN_VELOCITY: f32 = 20.;
+
+/// Speed of a fling after `delta` seconds of `friction`
+fn decay(velocity: f32, friction: f32, delta: f32) -> f32 {
+    let velocity = velocity * (-friction * delta).exp();
+    if velocity.abs() < MIN_VELOCITY {
+        0.
+    } else {
+        velocity
+    }
+}
+
+/// Centers of the handles under the start and end of a selection, in text coordinates
+pub(crate) fn selection_handle_centers(
+    buffer: &Buffer,
+    bounds: (Cursor, Cursor),
+    radius: f32,
+) -> [Option<Vec2>; 2] {
+    let line_height = buffer.metrics().line_height;
+    let center = |cursor: Cursor| {
+        get_cursor_position(buffer, cursor).map(|(x, top)| Vec2::new(x, top + line_height + radius))
+    };
+    [center(bounds.0), center(bounds.1)]
+}
+
+/// Draws the handles of a selection made by touch
+pub(crate) fn draw_selection_handles(
+    buffer: &Buffer,
+    bounds: (Cursor, Cursor),
+    settings: &TouchSettings,
+    draw: &mut impl FnMut(i32, i32, u32, u32, CosmicColor),
+) {
+    let radius = settings.handle_radius;
+    let color = settings.handle_color.to_cosmic();
+    for center in selection_handle_centers(buffer, bounds, radius)
+        .into_iter()
+        .flatten()
+    {
+        for dy in -radius as i32..=radius as i32 {
+            let half = (radius * radius - (dy * dy) as f32).max(0.).sqrt();
+            draw(
+                (center.x - half) as i32,
+                center.y as i32 + dy,
+                (half * 2.).max(1.) as u32,
+                1,
+                color,
+            );
+        }
+    }
+}
+
+/// Scrolls by `pixels`, through the [`SmoothScroll`] if there is one
+fn scroll_pixels(
+    editor: &mut CosmicEditor,
+    smooth_opt: Option<&mut SmoothScroll>,
+    pixels: f32,
+    height: f32,
+    remainder: &mut f32,
+    font_system: &mut FontSystem,
+) {
+    match smooth_opt {
+        Some(smooth) => {
+            let bottom = editor.with_buffer(|b| scroll_bottom(b, height));
+            smooth.scroll_by(pixels, bottom);
+        }
+        None => {
+            let line_height = editor.with_buffer(|b| b.metrics().line_height);
+            let lines = *remainder + pixels / line_height;
+            *remainder = lines.fract();
+            if lines.trunc() != 0. {
+                editor.action(
+                    font_system,
+                    Action::Scroll {
+                        lines: lines.trunc() as i32,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Taps place the cursor, long presses select a word, handles move the selection and dragging
+/// with one or two fingers scrolls, with momentum after the fingers lift
+fn input_touch(
+    mut commands: Commands,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    active_editor: Res<FocusedWidget>,
+    touches: Res<Touches>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    settings: Res<TouchSettings>,
+    mut editor_q: Query<(
+        Entity,
+        &mut CosmicEditor,
+        &GlobalTransform,
+        &Sprite,
+        (&CosmicTextAlign, &CosmicWrap),
+        &XOffset,
+        &CosmicWidgetSize,
+        Option<&LineNumbers>,
+        Option<&mut SmoothScroll>,
+        Option<&SelectionHandles>,
+        Option<&ScrollDisabled>,
+    )>,
+    node_q: Query<(&Node, &GlobalTransform, &CosmicSource)>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mut font_system: ResMut<CosmicFontSystem>,
+    time: Res<Time>,
+    mut state: Local<TouchState>,
+) {
+    let Some(active_editor_entity) = active_editor.0 else {
+        *state = default();
+        return;
+    };
+    let Ok((
+        entity,
+        mut editor,
+        sprite_transform,
+        sprite,
+        (text_position, mode),
+        x_offset,
+        size,
+        numbers_opt,
+        mut smooth_opt,
+        handles_opt,
+        scroll_disabled,
+    )) = editor_q.get_mut(active_editor_entity)
+    else {
+        *state = default();
+        return;
+    };
+
+    // Handles belong to touch selections only
+    if handles_opt.is_some()
+        && (buttons.just_pressed(MouseButton::Left) || editor.selection_bounds().is_none())
+    {
+        commands.entity(entity).remove::<SelectionHandles>();
+    }
+
+    let idle = touches.iter().next().is_none() && touches.iter_just_released().next().is_none();
+    if idle && state.touch.is_none() && state.velocity == 0. {
+        return;
+    }
+
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Some((camera, camera_transform)) = camera_q.iter().find(|(c, _)| c.is_active) else {
+        return;
+    };
+    let scale_factor = window.scale_factor();
+    let delta = time.delta_seconds();
+
+    let mut is_ui_node = false;
+    let mut transform = sprite_transform;
+    let mut node_size = sprite.custom_size.unwrap_or(Vec2::ONE);
+    for (node, node_transform, source) in node_q.iter() {
+        if source.0 == entity {
+            is_ui_node = true;
+            transform = node_transform;
+            node_size = node.size();
+        }
+    }
+
+    // Same hit-testing as the mouse, from window positions to text coordinates
+    let (origin, line_height) = editor.with_buffer(|b| {
+        let origin = text_origin(
+            text_position,
+            mode,
+            node_size * scale_factor,
+            b,
+            gutter_width(numbers_opt),
+        );
+        (origin, b.metrics().line_height)
+    });
+    let partial = smooth_opt
+        .as_ref()
+        .map_or(0., |s| s.partial_line(line_height));
+    let to_text = |pos: Vec2| {
+        get_node_pos_at(
+            pos,
+            transform,
+            (node_size.x, node_size.y),
+            is_ui_node,
+            camera,
+            camera_transform,
+        )
+        .map(|(x, y)| {
+            Vec2::new(
+                x * scale_factor - origin.x + x_offset.left,
+                y * scale_factor - origin.y + partial,
+            )
+        })
+    };
+
+    // The finger of a tap that focused the widget was down before it had an editor
+    if active_editor.is_changed() && state.touch.is_none() {
+        if let Some(touch) = touches.iter().chain(touches.iter_just_released()).next() {
+            state.velocity = 0.;
+            state.remainder = 0.;
+            let gesture = Gesture::Pending { held: 0. };
+            state.touch = Some((touch.id(), touch.start_position(), gesture));
+        }
+    }
+
+    // Lifted fingers
+    let lifted = touches
+        .iter_just_released()
+        .chain(touches.iter_just_canceled());
+    for touch in lifted {
+        let Some((id, _, gesture)) = state.touch else {
+            break;
+        };
+        if touch.id() != id {
+            continue;
+        }
+        if let (Gesture::Pending { .. }, Some(point)) = (gesture, to_text(touch.position())) {
+            // Tap
+            editor.clear_secondary_cursors();
+            editor.mark_active = false;
+            editor.cursor_visible = true;
+            editor.cursor_timer.reset();
+            editor.action(
+                &mut font_system.0,
+                Action::Click {
+                    x: point.x as i32,
+                    y: point.y as i32,
+                },
+            );
+            commands.entity(entity).remove::<SelectionHandles>();
+        }
+        if gesture != Gesture::Scroll {
+            state.velocity = 0.;
+        }
+        state.touch = None;
+    }
+
+    // New fingers
+    for touch in touches.iter_just_pressed() {
+        if let Some((_, _, gesture)) = state.touch.as_mut() {
+            // A second finger scrolls together with the first
+            if matches!(gesture, Gesture::Pending { .. }) {
+                *gesture = Gesture::Scroll;
+            }
+            continue;
+        }
+        let Some(point) = to_text(touch.position()) else {
+            continue;
+        };
+        state.velocity = 0.;
+        state.remainder = 0.;
+
+        let mut gesture = Gesture::Pending { held: 0. };
+        if let (Some(_), Some(bounds)) = (handles_opt, editor.selection_bounds()) {
+            let centers =
+                editor.with_buffer(|b| selection_handle_centers(b, bounds, settings.handle_radius));
+            let grabbed = centers.iter().position(|center| {
+                center.is_some_and(|c| c.distance(point) <= settings.handle_radius * 2.)
+            });
+            if let Some(end) = grabbed {
+                // The other end stays put while the grabbed one follows the finger
+                let (moved, anchor) = if end == 0 {
+                    (bounds.0, bounds.1)
+                } else {
+                    (bounds.1, bounds.0)
+                };
+                editor.set_selection(Selection::Normal(anchor));
+                editor.set_cursor(moved);
+                gesture = Gesture::Handle;
+            }
+        }
+        state.touch = Some((touch.id(), touch.position(), gesture));
+    }
+
+    // Fingers down
+    if let Some((id, start, mut gesture)) = state.touch {
+        let Some(touch) = touches.get_pressed(id) else {
+            state.touch = None;
+            return;
+        };
+        let fingers = touches.iter().count().max(1);
+
+        match gesture {
+            Gesture::Pending { held } => {
+                let moved = (touch.position() - start).length() * scale_factor;
+                if moved > settings.slop || fingers > 1 {
+                    gesture = Gesture::Scroll;
+                } else if held + delta >= settings.long_press {
+                    if let Some(point) = to_text(start) {
+                        // Select the word under the finger, like a double click
+                        editor.clear_secondary_cursors();
+                        editor.action(
+                            &mut font_system.0,
+                            Action::Click {
+                                x: point.x as i32,
+                                y: point.y as i32,
+                            },
+                        );
+                        editor.action(&mut font_system.0, Action::Motion(Motion::LeftWord));
+                        let cursor = editor.cursor();
+                        editor.set_selection(Selection::Normal(cursor));
+                        editor.action(&mut font_system.0, Action::Motion(Motion::RightWord));
+                        commands.entity(entity).insert(SelectionHandles);
+                    }
+                    gesture = Gesture::Selected;
+                } else {
+                    gesture = Gesture::Pending { held: held + delta };
+                }
+            }
+            Gesture::Handle => {
+                if let Some(point) = to_text(touch.position()) {
+                    editor.action(
+                        &mut font_system.0,
+                        Action::Drag {
+                            x: point.x as i32,
+                            y: point.y as i32,
+                        },
+                    );
+                }
+            }
+            Gesture::Scroll | Gesture::Selected => {}
+        }
+
+        if gesture == Gesture::Scroll && scroll_disabled.is_none() {
+            // The text follows the average movement of the fingers
+            let moved = touches.iter().map(|t| t.delta()).sum::<Vec2>() / fingers as f32;
+            let pixels = -moved.y * scale_factor;
+            scroll_pixels(
+                &mut editor,
+                smooth_opt.as_mut().map(|s| s.bypass_change_detection()),
+                pixels,
+                size.y,
+                &mut state.remainder,
+                &mut font_system.0,
+            );
+            if delta > 0. {
+                state.velocity = pixels / delta;
+            }
+        }
+        state.touch = Some((id, start, gesture));
+    } else if state.velocity != 0. {
+        if scroll_disabled.is_some() {
+            state.velocity = 0.;
+            return;
+        }
+        let pixels = state.velocity * delta;
+        scroll_pixels(
+            &mut editor,
+            smooth_opt.as_mut().map(|s| s.bypass_change_detection()),
+            pixels,
+            size.y,
+            &mut state.remainder,
+            &mut font_system.0,
+        );
+        state.velocity = decay(state.velocity, settings.friction, delta);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fling_decay() {
+        let velocity = decay(1000., 4., 0.25);
+        assert!((velocity - 1000. * (-1f32).exp()).abs() < 0.01);
+        assert_eq!(decay(-1000., 4., 0.), -1000.);
+        assert_eq!(decay(25., 4., 0.1), 0.);
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::too_many_arguments)]
+
 // Common functions for examples
 use crate::*;
 use bevy::{prelude::*, window::PrimaryWindow};
@@ -33,6 +35,27 @@ pub fn get_node_cursor_pos(
     is_ui_node: bool,
     camera: &Camera,
     camera_transform: &GlobalTransform,
+) -> Option<(f32, f32)> {
+    window.cursor_position().and_then(|pos| {
+        get_node_pos_at(
+            pos,
+            node_transform,
+            size,
+            is_ui_node,
+            camera,
+            camera_transform,
+        )
+    })
+}
+
+/// Function to find the location of a window position, such as a touch, in a cosmic widget
+pub fn get_node_pos_at(
+    pos: Vec2,
+    node_transform: &GlobalTransform,
+    size: (f32, f32),
+    is_ui_node: bool,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
 ) -> Option<(f32, f32)> {
     let (x_min, y_min, x_max, y_max) = (
         node_transform.affine().translation.x - size.0 / 2.,
@@ -41,32 +64,138 @@ pub fn get_node_cursor_pos(
         node_transform.affine().translation.y + size.1 / 2.,
     );
 
-    window.cursor_position().and_then(|pos| {
-        if is_ui_node {
-            if x_min < pos.x && pos.x < x_max && y_min < pos.y && pos.y < y_max {
-                Some((pos.x - x_min, pos.y - y_min))
-            } else {
-                None
-            }
+    if is_ui_node {
+        if x_min < pos.x && pos.x < x_max && y_min < pos.y && pos.y < y_max {
+            Some((pos.x - x_min, pos.y - y_min))
         } else {
-            camera
-                .viewport_to_world_2d(camera_transform, pos)
-                .and_then(|pos| {
-                    if x_min < pos.x && pos.x < x_max && y_min < pos.y && pos.y < y_max {
-                        Some((pos.x - x_min, y_max - pos.y))
-                    } else {
-                        None
-                    }
-                })
+            None
         }
-    })
+    } else {
+        camera
+            .viewport_to_world_2d(camera_transform, pos)
+            .and_then(|pos| {
+                if x_min < pos.x && pos.x < x_max && y_min < pos.y && pos.y < y_max {
+                    Some((pos.x - x_min, y_max - pos.y))
+                } else {
+                    None
+                }
+            })
+    }
+}
+
+/// Where a widget is drawn: its transform, logical size and whether it is a UI node
+pub(crate) fn widget_node<'a>(
+    entity: Entity,
+    sprite: (&'a GlobalTransform, &Sprite),
+    node_q: &'a Query<(&Node, &GlobalTransform, &CosmicSource)>,
+) -> (&'a GlobalTransform, Vec2, bool) {
+    let mut node = (sprite.0, sprite.1.custom_size.unwrap_or(Vec2::ONE), false);
+    for (ui_node, node_transform, source) in node_q.iter() {
+        if source.0 == entity {
+            node = (node_transform, ui_node.size(), true);
+        }
+    }
+    node
+}
+
+/// Text coordinates and cursor under the window position `pointer` in a widget's `buffer`
+pub(crate) fn pointer_hit(
+    pointer: Vec2,
+    buffer: &Buffer,
+    node: (&GlobalTransform, Vec2, bool),
+    camera: (&Camera, &GlobalTransform),
+    scale_factor: f32,
+    position: &CosmicTextAlign,
+    mode: &CosmicWrap,
+    x_offset: &XOffset,
+    numbers_opt: Option<&LineNumbers>,
+    smooth_opt: Option<&SmoothScroll>,
+) -> Option<(Vec2, Cursor)> {
+    let (transform, size, is_ui_node) = node;
+    let (x, y) = get_node_pos_at(
+        pointer,
+        transform,
+        (size.x, size.y),
+        is_ui_node,
+        camera.0,
+        camera.1,
+    )?;
+    let origin = text_origin(
+        position,
+        mode,
+        size * scale_factor,
+        buffer,
+        gutter_width(numbers_opt),
+    );
+    let partial = smooth_opt.map_or(0., |s| s.partial_line(buffer.metrics().line_height));
+    let point = Vec2::new(x, y) * scale_factor - origin + Vec2::new(x_offset.left, partial);
+    buffer.hit(point.x, point.y).map(|cursor| (point, cursor))
 }
 
-/// System to allow focus on click for sprite widgets
+/// Function to find the window position of a point in a cosmic widget, the inverse of
+/// [`get_node_cursor_pos`]. `point` is in logical pixels from the widget's top left corner.
+pub fn get_node_point_window_pos(
+    node_transform: &GlobalTransform,
+    size: (f32, f32),
+    is_ui_node: bool,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    point: (f32, f32),
+) -> Option<Vec2> {
+    let (x_min, y_min, y_max) = (
+        node_transform.affine().translation.x - size.0 / 2.,
+        node_transform.affine().translation.y - size.1 / 2.,
+        node_transform.affine().translation.y + size.1 / 2.,
+    );
+
+    if is_ui_node {
+        Some(Vec2::new(x_min + point.0, y_min + point.1))
+    } else {
+        camera.world_to_viewport(
+            camera_transform,
+            Vec3::new(x_min + point.0, y_max - point.1, 0.),
+        )
+    }
+}
+
+/// Window position of the bottom of the caret of `editor`, for anchoring popups and the input
+/// method's candidate window. `node` is the widget's transform, logical size and whether it is a
+/// UI node.
+pub(crate) fn get_caret_window_pos(
+    editor: &CosmicEditor,
+    node: (&GlobalTransform, Vec2, bool),
+    padding: &CosmicPadding,
+    x_offset: &XOffset,
+    camera: (&Camera, &GlobalTransform),
+    scale_factor: f32,
+) -> Option<Vec2> {
+    let (transform, size, is_ui_node) = node;
+    let (caret_x, caret_top, line_height) = editor.with_buffer(|b| {
+        get_cursor_position(b, editor.cursor()).map(|(x, top)| (x, top, b.metrics().line_height))
+    })?;
+
+    // Buffer pixels are physical, the window position is logical
+    let point = (
+        (caret_x + padding.x - x_offset.left) / scale_factor,
+        (caret_top + line_height + padding.y) / scale_factor,
+    );
+
+    get_node_point_window_pos(
+        transform,
+        (size.x, size.y),
+        is_ui_node,
+        camera.0,
+        camera.1,
+        point,
+    )
+}
+
+/// System to allow focus on click or tap for sprite widgets
 pub fn change_active_editor_sprite(
     mut commands: Commands,
     windows: Query<&Window, With<PrimaryWindow>>,
     buttons: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
     mut cosmic_edit_query: Query<
         (&mut Sprite, &GlobalTransform, &Visibility, Entity),
         (With<CosmicBuffer>, Without<ReadOnly>),
@@ -75,7 +204,11 @@ pub fn change_active_editor_sprite(
 ) {
     let window = windows.single();
     let (camera, camera_transform) = camera_q.single();
+    let mut presses: Vec<Vec2> = touches.iter_just_pressed().map(|t| t.position()).collect();
     if buttons.just_pressed(MouseButton::Left) {
+        presses.extend(window.cursor_position());
+    }
+    for pos in presses {
         for (sprite, node_transform, visibility, entity) in &mut cosmic_edit_query.iter_mut() {
             if visibility == Visibility::Hidden {
                 continue;
@@ -85,13 +218,11 @@ pub fn change_active_editor_sprite(
             let y_min = node_transform.affine().translation.y - size.y / 2.;
             let x_max = node_transform.affine().translation.x + size.x / 2.;
             let y_max = node_transform.affine().translation.y + size.y / 2.;
-            if let Some(pos) = window.cursor_position() {
-                if let Some(pos) = camera.viewport_to_world_2d(camera_transform, pos) {
-                    if x_min < pos.x && pos.x < x_max && y_min < pos.y && pos.y < y_max {
-                        commands.insert_resource(FocusedWidget(Some(entity)))
-                    };
-                }
-            };
+            if let Some(pos) = camera.viewport_to_world_2d(camera_transform, pos) {
+                if x_min < pos.x && pos.x < x_max && y_min < pos.y && pos.y < y_max {
+                    commands.insert_resource(FocusedWidget(Some(entity)))
+                };
+            }
         }
     }
 }
@@ -0,0 +1,1441 @@
+#![allow(clippy::type_complexity)]
+
+use crate::*;
+use bevy::{prelude::*, utils::HashMap};
+use cosmic_text::{Action, Edit, FontSystem, Motion, Selection};
+
+/// System set for vim modal editing. Runs in [`Update`]
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VimSet;
+
+pub(crate) struct VimPlugin;
+
+impl Plugin for VimPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                add_vim_state,
+                vim_input.in_set(InputSet).after(kb_undo_redo),
+            )
+                .chain()
+                .in_set(VimSet),
+        );
+    }
+}
+
+/// Component to be added to an entity with a [`CosmicEditBundle`] to edit it like vim.
+///
+/// Supports normal, insert and visual modes, counts, the `w` `b` `e` `0` `^` `$` `gg` `G`
+/// motions, the `d` `c` `y` operators with motions and text objects (`iw`, `a(`, `i"`, ...),
+/// `x` `X` `D` `C` `Y` `s` `S` `p` `P` `u` `Ctrl+R`, registers and `.` repeat.
+///
+/// The current mode is kept in the [`VimState`] component, added alongside this one.
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use bevy_cosmic_edit::*;
+/// #
+/// # fn setup(mut commands: Commands) {
+/// commands.spawn((CosmicEditBundle::default(), VimMode::default()));
+/// # }
+/// #
+/// # fn main() {
+/// #     App::new()
+/// #         .add_plugins(MinimalPlugins)
+/// #         .add_plugins(CosmicEditPlugin::default())
+/// #         .add_systems(Startup, setup);
+/// # }
+/// ```
+#[derive(Component, Debug, Default)]
+pub struct VimMode {
+    /// Keys typed so far of an unfinished command
+    pending: String,
+    registers: HashMap<char, VimRegister>,
+    /// Last change made, with the text typed in insert mode afterwards, for `.` repeat
+    last_change: Option<(VimCommand, String)>,
+    /// Set while insert mode text is appended to `last_change`
+    recording: bool,
+}
+
+impl VimMode {
+    /// Keys of the command being typed, e.g. `"2d"` while waiting for a motion
+    pub fn pending_keys(&self) -> &str {
+        &self.pending
+    }
+
+    /// Contents of register `name`
+    pub fn register(&self, name: char) -> Option<&VimRegister> {
+        self.registers.get(&name)
+    }
+
+    /// Stores yanked or deleted text in `register`, and in the unnamed register
+    fn store(&mut self, register: Option<char>, text: String, linewise: bool, yank: bool) {
+        let name = register.unwrap_or('"');
+        if name == '_' {
+            return;
+        }
+
+        let value = VimRegister { text, linewise };
+        if name.is_ascii_uppercase() {
+            let entry = self.registers.entry(name.to_ascii_lowercase()).or_default();
+            if entry.linewise || value.linewise {
+                entry.text.push('\n');
+            }
+            entry.text.push_str(&value.text);
+            entry.linewise |= value.linewise;
+        } else {
+            self.registers.insert(name, value.clone());
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if name == '+' {
+            if let Ok(mut clipboard) = arboard::Clipboard::new() {
+                let _ = clipboard.set_text(value.text.clone());
+            }
+        }
+
+        if yank {
+            self.registers.insert('0', value.clone());
+        }
+        self.registers.insert('"', value);
+    }
+
+    fn load(&self, register: Option<char>) -> Option<VimRegister> {
+        let name = register.unwrap_or('"').to_ascii_lowercase();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if name == '+' {
+            return arboard::Clipboard::new()
+                .and_then(|mut c| c.get_text())
+                .ok()
+                .map(|text| VimRegister {
+                    text,
+                    linewise: false,
+                });
+        }
+
+        self.registers.get(&name).cloned()
+    }
+}
+
+/// Text held in a vim register
+#[derive(Clone, Debug, Default)]
+pub struct VimRegister {
+    pub text: String,
+    /// Whether the text was yanked as whole lines, which are put on their own lines
+    pub linewise: bool,
+}
+
+/// Current mode of an editor with [`VimMode`]
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VimState {
+    #[default]
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+}
+
+impl VimState {
+    /// Whether keys are typed into the buffer as text
+    pub fn is_insert(&self) -> bool {
+        *self == VimState::Insert
+    }
+
+    pub fn is_visual(&self) -> bool {
+        matches!(self, VimState::Visual | VimState::VisualLine)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum VimMotion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordStart,
+    WordEnd,
+    WordBack,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FirstLine,
+    LastLine,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TextObject {
+    Word,
+    Quote(char),
+    Pair(char, char),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Target {
+    Motion(VimMotion),
+    Object {
+        object: TextObject,
+        around: bool,
+    },
+    /// The operator key doubled, as in `dd`
+    Line,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum InsertAt {
+    Cursor,
+    After,
+    FirstNonBlank,
+    LineEnd,
+    LineBelow,
+    LineAbove,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum VimAction {
+    Move(VimMotion),
+    Operate(Operator, Target),
+    OperateSelection(Operator),
+    Insert(InsertAt),
+    DeleteChar { before: bool },
+    Put { before: bool },
+    Visual { line: bool },
+    Undo,
+    Redo,
+    Repeat,
+}
+
+impl VimAction {
+    /// Whether the action changes the buffer and is remembered for `.`
+    fn is_change(&self) -> bool {
+        matches!(
+            self,
+            VimAction::Operate(Operator::Delete | Operator::Change, _)
+                | VimAction::Insert(_)
+                | VimAction::DeleteChar { .. }
+                | VimAction::Put { .. }
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct VimCommand {
+    count: Option<usize>,
+    register: Option<char>,
+    action: VimAction,
+}
+
+impl VimCommand {
+    fn count(&self) -> usize {
+        self.count.unwrap_or(1).max(1)
+    }
+}
+
+enum Parse {
+    Incomplete,
+    Invalid,
+    Done(VimCommand),
+}
+
+/// Parses the keys typed in normal or visual mode into a command
+fn parse(keys: &str, visual: bool) -> Parse {
+    let mut chars = keys.chars().peekable();
+
+    let mut register = None;
+    if chars.peek() == Some(&'"') {
+        chars.next();
+        match chars.next() {
+            Some(c) => register = Some(c),
+            None => return Parse::Incomplete,
+        }
+    }
+
+    let count = read_count(&mut chars);
+    let Some(c) = chars.next() else {
+        return Parse::Incomplete;
+    };
+
+    let done = |count, action| {
+        Parse::Done(VimCommand {
+            count,
+            register,
+            action,
+        })
+    };
+
+    if visual {
+        let operator = match c {
+            'd' | 'x' => Some(Operator::Delete),
+            'c' | 's' => Some(Operator::Change),
+            'y' => Some(Operator::Yank),
+            _ => None,
+        };
+        if let Some(operator) = operator {
+            return done(count, VimAction::OperateSelection(operator));
+        }
+    }
+
+    let operator = match c {
+        'd' => Some(Operator::Delete),
+        'c' => Some(Operator::Change),
+        'y' => Some(Operator::Yank),
+        _ => None,
+    };
+
+    if let Some(operator) = operator {
+        let inner_count = read_count(&mut chars);
+        let count = match (count, inner_count) {
+            (Some(a), Some(b)) => Some(a.saturating_mul(b).min(MAX_COUNT)),
+            (a, b) => a.or(b),
+        };
+
+        let Some(t) = chars.next() else {
+            return Parse::Incomplete;
+        };
+        if t == c {
+            return done(count, VimAction::Operate(operator, Target::Line));
+        }
+        if t == 'i' || t == 'a' {
+            let Some(o) = chars.next() else {
+                return Parse::Incomplete;
+            };
+            return match text_object(o) {
+                Some(object) => done(
+                    count,
+                    VimAction::Operate(
+                        operator,
+                        Target::Object {
+                            object,
+                            around: t == 'a',
+                        },
+                    ),
+                ),
+                None => Parse::Invalid,
+            };
+        }
+        return match parse_motion(t, &mut chars) {
+            Ok(motion) => done(count, VimAction::Operate(operator, Target::Motion(motion))),
+            Err(parse) => parse,
+        };
+    }
+
+    let action = match c {
+        'i' => VimAction::Insert(InsertAt::Cursor),
+        'a' => VimAction::Insert(InsertAt::After),
+        'I' => VimAction::Insert(InsertAt::FirstNonBlank),
+        'A' => VimAction::Insert(InsertAt::LineEnd),
+        'o' => VimAction::Insert(InsertAt::LineBelow),
+        'O' => VimAction::Insert(InsertAt::LineAbove),
+        'x' => VimAction::DeleteChar { before: false },
+        'X' => VimAction::DeleteChar { before: true },
+        'p' => VimAction::Put { before: false },
+        'P' => VimAction::Put { before: true },
+        'D' => VimAction::Operate(Operator::Delete, Target::Motion(VimMotion::LineEnd)),
+        'C' => VimAction::Operate(Operator::Change, Target::Motion(VimMotion::LineEnd)),
+        'Y' => VimAction::Operate(Operator::Yank, Target::Line),
+        's' => VimAction::Operate(Operator::Change, Target::Motion(VimMotion::Right)),
+        'S' => VimAction::Operate(Operator::Change, Target::Line),
+        'v' => VimAction::Visual { line: false },
+        'V' => VimAction::Visual { line: true },
+        'u' => VimAction::Undo,
+        '.' => VimAction::Repeat,
+        _ => {
+            return match parse_motion(c, &mut chars) {
+                Ok(motion) => done(count, VimAction::Move(motion)),
+                Err(parse) => parse,
+            }
+        }
+    };
+
+    // Visual mode only moves the cursor or acts on the selection
+    if visual && !matches!(action, VimAction::Visual { .. }) {
+        return Parse::Invalid;
+    }
+
+    done(count, action)
+}
+
+/// Largest count, as in vim
+const MAX_COUNT: usize = 999_999_999;
+
+fn read_count(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<usize> {
+    let mut count: Option<usize> = None;
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        // A leading 0 is the line start motion, not a count
+        if digit == 0 && count.is_none() {
+            break;
+        }
+        let count_so_far = count.unwrap_or(0).saturating_mul(10);
+        count = Some(count_so_far.saturating_add(digit as usize).min(MAX_COUNT));
+        chars.next();
+    }
+    count
+}
+
+fn parse_motion(c: char, chars: &mut impl Iterator<Item = char>) -> Result<VimMotion, Parse> {
+    match c {
+        'h' => Ok(VimMotion::Left),
+        'l' | ' ' => Ok(VimMotion::Right),
+        'k' => Ok(VimMotion::Up),
+        'j' => Ok(VimMotion::Down),
+        'w' => Ok(VimMotion::WordStart),
+        'e' => Ok(VimMotion::WordEnd),
+        'b' => Ok(VimMotion::WordBack),
+        '0' => Ok(VimMotion::LineStart),
+        '^' => Ok(VimMotion::FirstNonBlank),
+        '$' => Ok(VimMotion::LineEnd),
+        'G' => Ok(VimMotion::LastLine),
+        'g' => match chars.next() {
+            Some('g') => Ok(VimMotion::FirstLine),
+            Some(_) => Err(Parse::Invalid),
+            None => Err(Parse::Incomplete),
+        },
+        _ => Err(Parse::Invalid),
+    }
+}
+
+fn text_object(c: char) -> Option<TextObject> {
+    match c {
+        'w' => Some(TextObject::Word),
+        '"' | '\'' | '`' => Some(TextObject::Quote(c)),
+        '(' | ')' | 'b' => Some(TextObject::Pair('(', ')')),
+        '[' | ']' => Some(TextObject::Pair('[', ']')),
+        '{' | '}' | 'B' => Some(TextObject::Pair('{', '}')),
+        '<' | '>' => Some(TextObject::Pair('<', '>')),
+        _ => None,
+    }
+}
+
+/// Adds [`VimState`] to new [`VimMode`] editors
+fn add_vim_state(mut commands: Commands, q: Query<Entity, (Added<VimMode>, Without<VimState>)>) {
+    for entity in q.iter() {
+        commands.entity(entity).insert(VimState::default());
+    }
+}
+
+/// Reads keys for the focused [`VimMode`] editor outside of insert mode.
+///
+/// Runs after the regular input systems, which leave non-insert modes alone.
+fn vim_input(
+    active_editor: Res<FocusedWidget>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut char_evr: EventReader<ReceivedCharacter>,
+    mut editor_q: Query<(
+        &mut CosmicEditor,
+        &mut VimMode,
+        &mut VimState,
+        Option<&mut EditHistory>,
+        Option<&ReadOnly>,
+    )>,
+    mut evw_changed: EventWriter<CosmicTextChanged>,
+    mut font_system: ResMut<CosmicFontSystem>,
+) {
+    let Some(active_editor_entity) = active_editor.0 else {
+        char_evr.clear();
+        return;
+    };
+    let Ok((mut editor, mut vim, mut state, mut history_opt, readonly_opt)) =
+        editor_q.get_mut(active_editor_entity)
+    else {
+        char_evr.clear();
+        return;
+    };
+
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let command_key = ctrl || keys.any_pressed([KeyCode::SuperLeft, KeyCode::SuperRight]);
+
+    let mut typed: Vec<char> = Vec::new();
+    for ev in char_evr.read() {
+        if !command_key {
+            typed.extend(ev.char.chars().filter(|c| !c.is_control()));
+        }
+    }
+
+    if state.is_insert() {
+        if keys.just_pressed(KeyCode::Escape) {
+            let mut cx = VimContext {
+                editor: &mut editor,
+                vim: &mut vim,
+                state: &mut state,
+                font_system: &mut font_system.0,
+                readonly: readonly_opt.is_some(),
+            };
+            cx.leave_insert(history_opt.as_deref_mut());
+            return;
+        }
+
+        // Remember typed text, so `.` can repeat it
+        if vim.recording {
+            if let Some((_, text)) = vim.last_change.as_mut() {
+                if keys.just_pressed(KeyCode::Backspace) {
+                    text.pop();
+                }
+                if keys.just_pressed(KeyCode::Enter) {
+                    text.push('\n');
+                }
+                text.extend(typed.iter());
+            }
+        }
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        vim.pending.clear();
+        if state.is_visual() {
+            *state = VimState::Normal;
+            editor.set_selection(Selection::None);
+            editor.set_redraw(true);
+        }
+        return;
+    }
+
+    for (key, c) in [
+        (KeyCode::ArrowLeft, 'h'),
+        (KeyCode::ArrowDown, 'j'),
+        (KeyCode::ArrowUp, 'k'),
+        (KeyCode::ArrowRight, 'l'),
+    ] {
+        if keys.just_pressed(key) {
+            typed.push(c);
+        }
+    }
+
+    let mut cx = VimContext {
+        editor: &mut editor,
+        vim: &mut vim,
+        state: &mut state,
+        font_system: &mut font_system.0,
+        readonly: readonly_opt.is_some(),
+    };
+
+    let mut changed = false;
+
+    if ctrl && keys.just_pressed(KeyCode::KeyR) {
+        let redo = VimCommand {
+            count: None,
+            register: None,
+            action: VimAction::Redo,
+        };
+        changed |= cx.undo_redo(history_opt.as_deref_mut(), redo);
+    }
+
+    for (i, c) in typed.iter().enumerate() {
+        // Keys typed after a command that entered insert mode are text
+        if cx.state.is_insert() {
+            let rest: String = typed[i..].iter().collect();
+            changed |= cx.type_text(&rest, history_opt.as_deref_mut());
+            break;
+        }
+
+        cx.vim.pending.push(*c);
+        let command = match parse(&cx.vim.pending, cx.state.is_visual()) {
+            Parse::Incomplete => continue,
+            Parse::Invalid => {
+                cx.vim.pending.clear();
+                continue;
+            }
+            Parse::Done(command) => command,
+        };
+        cx.vim.pending.clear();
+
+        match command.action {
+            VimAction::Undo | VimAction::Redo => {
+                changed |= cx.undo_redo(history_opt.as_deref_mut(), command);
+                continue;
+            }
+            _ if cx.readonly && !cx.is_read_only_safe(&command.action) => continue,
+            _ => {}
+        }
+
+        changed |= cx.run(command, history_opt.as_deref_mut());
+    }
+
+    if changed {
+        evw_changed.send(CosmicTextChanged((
+            active_editor_entity,
+            editor.with_buffer(|b| b.get_text()),
+        )));
+    }
+}
+
+/// Everything a vim command needs to run on the focused editor
+struct VimContext<'a> {
+    editor: &'a mut CosmicEditor,
+    vim: &'a mut VimMode,
+    state: &'a mut VimState,
+    font_system: &'a mut FontSystem,
+    readonly: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MotionKind {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+impl<'a> VimContext<'a> {
+    fn text(&self) -> String {
+        self.editor.with_buffer(|b| b.get_text())
+    }
+
+    fn offset(&self) -> usize {
+        let cursor = self.editor.cursor();
+        self.editor.with_buffer(|b| cursor_to_offset(b, cursor))
+    }
+
+    fn set_offset(&mut self, offset: usize) {
+        let cursor = self.editor.with_buffer(|b| offset_to_cursor(b, offset));
+        self.editor.set_cursor(cursor);
+    }
+
+    /// Keeps the cursor on a character, as normal mode has no position past the end of a line
+    fn clamp_cursor(&mut self) {
+        let text = self.text();
+        let offset = clamp_normal(&text, self.offset().min(text.len()));
+        self.set_offset(offset);
+    }
+
+    fn delete(&mut self, start: usize, end: usize) {
+        if start < end {
+            let (start_cursor, end_cursor) = self
+                .editor
+                .with_buffer(|b| (offset_to_cursor(b, start), offset_to_cursor(b, end)));
+            self.editor.delete_range(start_cursor, end_cursor);
+        }
+        self.editor.set_selection(Selection::None);
+        self.set_offset(start);
+    }
+
+    fn insert(&mut self, offset: usize, text: &str) {
+        let cursor = self.editor.with_buffer(|b| offset_to_cursor(b, offset));
+        self.editor.insert_at(cursor, text, None);
+    }
+
+    fn enter_insert(&mut self) {
+        *self.state = VimState::Insert;
+        self.vim.recording = true;
+    }
+
+    /// Back to normal mode, with the cursor on the last inserted character
+    fn leave_insert(&mut self, history: Option<&mut EditHistory>) {
+        self.vim.recording = false;
+        *self.state = VimState::Normal;
+        if self.editor.cursor().index > 0 {
+            self.editor
+                .action(self.font_system, Action::Motion(Motion::Left));
+        }
+        self.editor.set_redraw(true);
+        if let Some(history) = history {
+            history.end_group();
+        }
+    }
+
+    /// Executes `command` as one undo step. Returns whether the text changed.
+    ///
+    /// A command entering insert mode starts a group, so everything typed until insert mode is
+    /// left is undone together with it.
+    fn run(&mut self, command: VimCommand, history: Option<&mut EditHistory>) -> bool {
+        let before = (self.editor.cursor(), self.editor.selection());
+        self.editor.start_change();
+        self.execute(command);
+        self.editor.set_redraw(true);
+
+        let change = self.editor.finish_change();
+        let changed = change.is_some();
+        if let Some(history) = history {
+            history.seal();
+            if self.state.is_insert() {
+                history.begin_group();
+            }
+            if let Some(change) = change {
+                history.record(change, before, self.editor);
+            }
+            if !self.state.is_insert() {
+                history.seal();
+            }
+        }
+        changed
+    }
+
+    /// Inserts `text` typed in insert mode. Returns whether the text changed.
+    fn type_text(&mut self, text: &str, history: Option<&mut EditHistory>) -> bool {
+        let before = (self.editor.cursor(), self.editor.selection());
+        self.editor.start_change();
+        self.editor.insert_string(text, None);
+        if let Some((_, typed)) = self.vim.last_change.as_mut() {
+            typed.push_str(text);
+        }
+
+        let Some(change) = self.editor.finish_change() else {
+            return false;
+        };
+        if let Some(history) = history {
+            history.record(change, before, self.editor);
+        }
+        true
+    }
+
+    /// Runs `u` or `Ctrl+R` against the editor's [`EditHistory`]
+    fn undo_redo(&mut self, history: Option<&mut EditHistory>, command: VimCommand) -> bool {
+        let Some(history) = history else {
+            return false;
+        };
+
+        let mut changed = false;
+        for _ in 0..command.count() {
+            let stepped = match command.action {
+                VimAction::Undo => history.undo(self.editor),
+                _ => history.redo(self.editor),
+            };
+            if !stepped {
+                break;
+            }
+            changed = true;
+        }
+        self.clamp_cursor();
+        changed
+    }
+
+    /// Whether `action` leaves the buffer untouched
+    fn is_read_only_safe(&self, action: &VimAction) -> bool {
+        matches!(
+            action,
+            VimAction::Move(_)
+                | VimAction::Visual { .. }
+                | VimAction::Operate(Operator::Yank, _)
+                | VimAction::OperateSelection(Operator::Yank)
+        )
+    }
+
+    fn execute(&mut self, command: VimCommand) {
+        if command.action.is_change() {
+            self.vim.last_change = Some((command, String::new()));
+        }
+
+        let text = self.text();
+        let from = self.offset().min(text.len());
+        let count = command.count();
+
+        match command.action {
+            VimAction::Move(motion) => {
+                let (to, _) = self.motion_target(&text, from, motion, command.count, false);
+                self.set_offset(to);
+                if !self.state.is_visual() {
+                    self.clamp_cursor();
+                }
+            }
+            VimAction::Operate(operator, target) => {
+                let range = match target {
+                    Target::Motion(motion) => {
+                        // `cw` on a word changes to its end, like `ce`
+                        let motion = match (operator, motion) {
+                            (Operator::Change, VimMotion::WordStart)
+                                if char_at(&text, from).is_some_and(|c| !c.is_whitespace()) =>
+                            {
+                                VimMotion::WordEnd
+                            }
+                            _ => motion,
+                        };
+                        let (to, kind) =
+                            self.motion_target(&text, from, motion, command.count, true);
+                        Some(motion_range(&text, from, to, kind))
+                    }
+                    Target::Object { object, around } => {
+                        object_range(&text, from, object, around).map(|(s, e)| (s, e, false))
+                    }
+                    Target::Line => {
+                        let end = repeat_motion(from, count - 1, |end| {
+                            next_line_start(&text, end).unwrap_or(end)
+                        });
+                        Some((line_start(&text, from), line_end(&text, end), true))
+                    }
+                };
+
+                match range {
+                    Some((start, end, linewise)) => {
+                        self.operate(&text, operator, command.register, start, end, linewise)
+                    }
+                    None => self.set_offset(from),
+                }
+            }
+            VimAction::OperateSelection(operator) => {
+                let anchor = match self.editor.selection() {
+                    Selection::Normal(c) | Selection::Line(c) | Selection::Word(c) => {
+                        self.editor.with_buffer(|b| cursor_to_offset(b, c))
+                    }
+                    Selection::None => from,
+                };
+                let (start, end) = (anchor.min(from), anchor.max(from));
+                let linewise = *self.state == VimState::VisualLine;
+                let range = if linewise {
+                    (line_start(&text, start), line_end(&text, end))
+                } else {
+                    (start, next_char(&text, end).min(text.len()))
+                };
+
+                *self.state = VimState::Normal;
+                self.editor.set_selection(Selection::None);
+                self.operate(
+                    &text,
+                    operator,
+                    command.register,
+                    range.0,
+                    range.1,
+                    linewise,
+                );
+            }
+            VimAction::Insert(at) => {
+                let offset = match at {
+                    InsertAt::Cursor => from,
+                    InsertAt::After => next_char(&text, from).min(line_end(&text, from)),
+                    InsertAt::FirstNonBlank => first_non_blank(&text, from),
+                    InsertAt::LineEnd => line_end(&text, from),
+                    InsertAt::LineBelow => {
+                        let end = line_end(&text, from);
+                        self.insert(end, "\n");
+                        end + 1
+                    }
+                    InsertAt::LineAbove => {
+                        let start = line_start(&text, from);
+                        self.insert(start, "\n");
+                        start
+                    }
+                };
+                self.set_offset(offset);
+                self.enter_insert();
+            }
+            VimAction::DeleteChar { before } => {
+                let (start, end) = if before {
+                    let line = line_start(&text, from);
+                    let start = repeat_motion(from, count, |start| {
+                        if start > line {
+                            prev_char(&text, start)
+                        } else {
+                            start
+                        }
+                    });
+                    (start, from)
+                } else {
+                    let line = line_end(&text, from);
+                    let end = repeat_motion(from, count, |end| {
+                        if end < line {
+                            next_char(&text, end)
+                        } else {
+                            end
+                        }
+                    });
+                    (from, end)
+                };
+                if start < end {
+                    self.operate(&text, Operator::Delete, command.register, start, end, false);
+                }
+            }
+            VimAction::Put { before } => {
+                let Some(register) = self.vim.load(command.register) else {
+                    return;
+                };
+                let content = vec![register.text.as_str(); count].join(if register.linewise {
+                    "\n"
+                } else {
+                    ""
+                });
+
+                if register.linewise {
+                    if before {
+                        let at = line_start(&text, from);
+                        self.insert(at, &format!("{content}\n"));
+                        self.set_offset(at);
+                    } else {
+                        let at = line_end(&text, from);
+                        self.insert(at, &format!("\n{content}"));
+                        self.set_offset(at + 1);
+                    }
+                } else {
+                    let at = if before {
+                        from
+                    } else {
+                        next_char(&text, from).min(line_end(&text, from))
+                    };
+                    self.insert(at, &content);
+                    let end = at + content.len();
+                    self.set_offset(if end > at {
+                        prev_char(&self.text(), end)
+                    } else {
+                        at
+                    });
+                }
+                self.clamp_cursor();
+            }
+            VimAction::Visual { line } => {
+                let next = if line {
+                    VimState::VisualLine
+                } else {
+                    VimState::Visual
+                };
+                let cursor = self.editor.cursor();
+                if *self.state == next {
+                    *self.state = VimState::Normal;
+                    self.editor.set_selection(Selection::None);
+                } else {
+                    *self.state = next;
+                    self.editor.set_selection(if line {
+                        Selection::Line(cursor)
+                    } else {
+                        Selection::Normal(cursor)
+                    });
+                }
+            }
+            VimAction::Repeat => {
+                let Some((mut last, typed)) = self.vim.last_change.clone() else {
+                    return;
+                };
+                if command.count.is_some() {
+                    last.count = command.count;
+                }
+
+                self.execute(last);
+                if self.state.is_insert() {
+                    self.editor.insert_string(&typed, None);
+                    self.leave_insert(None);
+                }
+                self.vim.last_change = Some((last, typed));
+            }
+            VimAction::Undo | VimAction::Redo => {}
+        }
+    }
+
+    /// Where `motion` moves the cursor from `from`, and how an operator treats the range
+    fn motion_target(
+        &mut self,
+        text: &str,
+        from: usize,
+        motion: VimMotion,
+        count: Option<usize>,
+        operator: bool,
+    ) -> (usize, MotionKind) {
+        let n = count.unwrap_or(1).max(1);
+        match motion {
+            VimMotion::Left => {
+                let start = line_start(text, from);
+                let to = repeat_motion(
+                    from,
+                    n,
+                    |to| {
+                        if to > start {
+                            prev_char(text, to)
+                        } else {
+                            to
+                        }
+                    },
+                );
+                (to, MotionKind::Exclusive)
+            }
+            VimMotion::Right => {
+                let end = line_end(text, from);
+                let to = repeat_motion(
+                    from,
+                    n,
+                    |to| {
+                        if to < end {
+                            next_char(text, to)
+                        } else {
+                            to
+                        }
+                    },
+                );
+                (to, MotionKind::Exclusive)
+            }
+            VimMotion::Up | VimMotion::Down => {
+                let motion = if motion == VimMotion::Up {
+                    Motion::Up
+                } else {
+                    Motion::Down
+                };
+                self.set_offset(from);
+                repeat_motion(from, n, |_| {
+                    self.editor.action(self.font_system, Action::Motion(motion));
+                    self.offset()
+                });
+                (self.offset(), MotionKind::Linewise)
+            }
+            VimMotion::WordStart => {
+                let mut to = repeat_motion(from, n, |to| word_start_forward(text, to));
+                // `dw` on the last word of a line stops at the line end
+                let end = line_end(text, from);
+                if operator && to > end && from < end {
+                    to = end;
+                }
+                (to, MotionKind::Exclusive)
+            }
+            VimMotion::WordEnd => {
+                let to = repeat_motion(from, n, |to| word_end_forward(text, to));
+                (to, MotionKind::Inclusive)
+            }
+            VimMotion::WordBack => {
+                let to = repeat_motion(from, n, |to| word_back(text, to));
+                (to, MotionKind::Exclusive)
+            }
+            VimMotion::LineStart => (line_start(text, from), MotionKind::Exclusive),
+            VimMotion::FirstNonBlank => (first_non_blank(text, from), MotionKind::Exclusive),
+            VimMotion::LineEnd => {
+                let line = repeat_motion(from, n - 1, |line| {
+                    next_line_start(text, line).unwrap_or(line)
+                });
+                (line_end(text, line), MotionKind::Exclusive)
+            }
+            VimMotion::FirstLine | VimMotion::LastLine => {
+                let last = text.matches('\n').count();
+                let line = match (motion, count) {
+                    (_, Some(n)) => (n.max(1) - 1).min(last),
+                    (VimMotion::FirstLine, None) => 0,
+                    _ => last,
+                };
+                let start = match line {
+                    0 => 0,
+                    _ => text
+                        .match_indices('\n')
+                        .nth(line - 1)
+                        .map_or(0, |(i, _)| i + 1),
+                };
+                (first_non_blank(text, start), MotionKind::Linewise)
+            }
+        }
+    }
+
+    /// Applies `operator` to the text between `start` and `end`
+    fn operate(
+        &mut self,
+        text: &str,
+        operator: Operator,
+        register: Option<char>,
+        start: usize,
+        end: usize,
+        linewise: bool,
+    ) {
+        self.vim.store(
+            register,
+            text[start..end].to_string(),
+            linewise,
+            operator == Operator::Yank,
+        );
+
+        match operator {
+            Operator::Yank => {
+                self.set_offset(start);
+                self.clamp_cursor();
+            }
+            Operator::Delete => {
+                if linewise {
+                    // Take a line break along, so no empty line is left behind
+                    if end < text.len() {
+                        self.delete(start, end + 1);
+                    } else if start > 0 {
+                        self.delete(start - 1, end);
+                        let text = self.text();
+                        let line = line_start(&text, start - 1);
+                        self.set_offset(line);
+                    } else {
+                        self.delete(start, end);
+                    }
+                    let text = self.text();
+                    let offset = first_non_blank(&text, self.offset());
+                    self.set_offset(offset);
+                } else {
+                    self.delete(start, end);
+                }
+                self.clamp_cursor();
+            }
+            Operator::Change => {
+                self.delete(start, end);
+                self.enter_insert();
+            }
+        }
+    }
+}
+
+/// Start and end of what an operator acts on, and whether it is whole lines
+fn motion_range(text: &str, from: usize, to: usize, kind: MotionKind) -> (usize, usize, bool) {
+    let (start, end) = (from.min(to), from.max(to));
+    match kind {
+        MotionKind::Exclusive => (start, end, false),
+        MotionKind::Inclusive => (start, next_char(text, end).min(text.len()), false),
+        MotionKind::Linewise => (line_start(text, start), line_end(text, end), true),
+    }
+}
+
+fn object_range(
+    text: &str,
+    from: usize,
+    object: TextObject,
+    around: bool,
+) -> Option<(usize, usize)> {
+    match object {
+        TextObject::Word => {
+            let (start_limit, end_limit) = (line_start(text, from), line_end(text, from));
+            if from >= end_limit {
+                return None;
+            }
+            let class = char_class(char_at(text, from)?);
+
+            let mut start = from;
+            while start > start_limit && char_class(char_at(text, prev_char(text, start))?) == class
+            {
+                start = prev_char(text, start);
+            }
+            let mut end = next_char(text, from);
+            while end < end_limit && char_class(char_at(text, end)?) == class {
+                end = next_char(text, end);
+            }
+
+            if around {
+                if end < end_limit && char_at(text, end).is_some_and(char::is_whitespace) {
+                    while end < end_limit && char_at(text, end).is_some_and(char::is_whitespace) {
+                        end = next_char(text, end);
+                    }
+                } else {
+                    while start > start_limit
+                        && char_at(text, prev_char(text, start)).is_some_and(char::is_whitespace)
+                    {
+                        start = prev_char(text, start);
+                    }
+                }
+            }
+            Some((start, end))
+        }
+        TextObject::Quote(quote) => {
+            let start = line_start(text, from);
+            let end = line_end(text, from);
+            let quotes: Vec<usize> = text[start..end]
+                .match_indices(quote)
+                .map(|(i, _)| start + i)
+                .collect();
+            let (open, close) = quotes
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .find(|(_, close)| from <= *close)?;
+            Some(if around {
+                (open, close + quote.len_utf8())
+            } else {
+                (open + quote.len_utf8(), close)
+            })
+        }
+        TextObject::Pair(open_char, close_char) => {
+            // A closing bracket under the cursor belongs to the pair, so search before it
+            let open = if char_at(text, from) == Some(open_char) {
+                from
+            } else {
+                let mut depth = 0;
+                text[..from]
+                    .char_indices()
+                    .rev()
+                    .find(|(_, c)| {
+                        if *c == close_char {
+                            depth += 1;
+                        } else if *c == open_char {
+                            if depth == 0 {
+                                return true;
+                            }
+                            depth -= 1;
+                        }
+                        false
+                    })?
+                    .0
+            };
+
+            let mut depth = 0;
+            let inner_start = open + open_char.len_utf8();
+            let close = text[inner_start..]
+                .char_indices()
+                .find(|(_, c)| {
+                    if *c == open_char {
+                        depth += 1;
+                    } else if *c == close_char {
+                        if depth == 0 {
+                            return true;
+                        }
+                        depth -= 1;
+                    }
+                    false
+                })?
+                .0
+                + inner_start;
+
+            Some(if around {
+                (open, close + close_char.len_utf8())
+            } else {
+                (inner_start, close)
+            })
+        }
+    }
+}
+
+fn char_at(text: &str, offset: usize) -> Option<char> {
+    text.get(offset..)?.chars().next()
+}
+
+fn next_char(text: &str, offset: usize) -> usize {
+    char_at(text, offset).map_or(text.len(), |c| offset + c.len_utf8())
+}
+
+fn prev_char(text: &str, offset: usize) -> usize {
+    text[..offset]
+        .char_indices()
+        .next_back()
+        .map_or(0, |(i, _)| i)
+}
+
+fn line_start(text: &str, offset: usize) -> usize {
+    text[..offset].rfind('\n').map_or(0, |i| i + 1)
+}
+
+fn line_end(text: &str, offset: usize) -> usize {
+    text[offset..].find('\n').map_or(text.len(), |i| offset + i)
+}
+
+fn next_line_start(text: &str, offset: usize) -> Option<usize> {
+    text[offset..].find('\n').map(|i| offset + i + 1)
+}
+
+/// Takes up to `count` steps from `from`, stopping early once a step no longer moves
+fn repeat_motion(from: usize, count: usize, mut step: impl FnMut(usize) -> usize) -> usize {
+    let mut at = from;
+    for _ in 0..count {
+        let next = step(at);
+        if next == at {
+            break;
+        }
+        at = next;
+    }
+    at
+}
+
+fn first_non_blank(text: &str, offset: usize) -> usize {
+    let start = line_start(text, offset);
+    let end = line_end(text, offset);
+    text[start..end]
+        .char_indices()
+        .find(|(_, c)| !c.is_whitespace())
+        .map_or(end, |(i, _)| start + i)
+}
+
+/// Moves an offset past the end of a line back onto its last character
+fn clamp_normal(text: &str, offset: usize) -> usize {
+    if offset == line_end(text, offset) && offset > line_start(text, offset) {
+        prev_char(text, offset)
+    } else {
+        offset
+    }
+}
+
+/// 0 for whitespace, 1 for word characters, 2 for punctuation
+fn char_class(c: char) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+fn class_at(text: &str, offset: usize) -> u8 {
+    char_at(text, offset).map_or(0, char_class)
+}
+
+fn word_start_forward(text: &str, offset: usize) -> usize {
+    let mut i = offset;
+    let class = class_at(text, i);
+    if class != 0 {
+        while i < text.len() && class_at(text, i) == class {
+            i = next_char(text, i);
+        }
+    }
+    while i < text.len() && class_at(text, i) == 0 {
+        i = next_char(text, i);
+    }
+    i
+}
+
+fn word_end_forward(text: &str, offset: usize) -> usize {
+    let mut i = next_char(text, offset);
+    while i < text.len() && class_at(text, i) == 0 {
+        i = next_char(text, i);
+    }
+    if i >= text.len() {
+        return prev_char(text, text.len());
+    }
+    let class = class_at(text, i);
+    while next_char(text, i) < text.len() && class_at(text, next_char(text, i)) == class {
+        i = next_char(text, i);
+    }
+    i
+}
+
+fn word_back(text: &str, offset: usize) -> usize {
+    if offset == 0 {
+        return 0;
+    }
+    let mut i = prev_char(text, offset);
+    while i > 0 && class_at(text, i) == 0 {
+        i = prev_char(text, i);
+    }
+    let class = class_at(text, i);
+    while i > 0 && class_at(text, prev_char(text, i)) == class {
+        i = prev_char(text, i);
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmic_text::{Attrs, Buffer, Editor, Metrics, Shaping};
+
+    /// Runs `f` on a normal mode context for `text`, with the cursor at the start
+    fn with_context(text: &str, f: impl FnOnce(&mut VimContext, &mut EditHistory)) {
+        let mut font_system = create_cosmic_font_system(CosmicFontConfig {
+            load_system_fonts: false,
+            ..Default::default()
+        });
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(20., 20.));
+        buffer.set_text(&mut font_system, text, Attrs::new(), Shaping::Advanced);
+        let mut editor = CosmicEditor::new(Editor::new(buffer));
+        let mut vim = VimMode::default();
+        let mut state = VimState::default();
+        let mut history = EditHistory::default();
+        let mut cx = VimContext {
+            editor: &mut editor,
+            vim: &mut vim,
+            state: &mut state,
+            font_system: &mut font_system,
+            readonly: false,
+        };
+        f(&mut cx, &mut history);
+    }
+
+    fn command(keys: &str) -> Option<VimCommand> {
+        match parse(keys, false) {
+            Parse::Done(command) => Some(command),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn parses_counts_registers_and_objects() {
+        let c = command("\"a2d3w").unwrap();
+        assert_eq!(c.register, Some('a'));
+        assert_eq!(c.count, Some(6));
+        assert_eq!(
+            c.action,
+            VimAction::Operate(Operator::Delete, Target::Motion(VimMotion::WordStart))
+        );
+
+        assert_eq!(
+            command("ci(").unwrap().action,
+            VimAction::Operate(
+                Operator::Change,
+                Target::Object {
+                    object: TextObject::Pair('(', ')'),
+                    around: false
+                }
+            )
+        );
+        assert_eq!(
+            command("0").unwrap().action,
+            VimAction::Move(VimMotion::LineStart)
+        );
+        assert_eq!(
+            command("99999999999999999999j").unwrap().count,
+            Some(MAX_COUNT)
+        );
+        assert_eq!(command("999999d999999w").unwrap().count, Some(MAX_COUNT));
+        assert!(matches!(parse("d", false), Parse::Incomplete));
+        assert!(matches!(parse("dq", false), Parse::Invalid));
+    }
+
+    #[test]
+    fn word_motions() {
+        let text = "foo.bar  baz\nqux";
+        assert_eq!(word_start_forward(text, 0), 3);
+        assert_eq!(word_start_forward(text, 4), 9);
+        assert_eq!(word_end_forward(text, 0), 2);
+        assert_eq!(word_back(text, 9), 4);
+        assert_eq!(word_start_forward(text, 9), 13);
+        assert_eq!(
+            object_range("f(a, (b))", 3, TextObject::Pair('(', ')'), false),
+            Some((2, 8))
+        );
+    }
+
+    #[test]
+    fn operators_with_motions() {
+        with_context("foo bar baz", |cx, history| {
+            cx.run(command("dw").unwrap(), Some(history));
+            assert_eq!(cx.text(), "bar baz");
+            cx.run(command("d2e").unwrap(), Some(history));
+            assert_eq!(cx.text(), "");
+        });
+        with_context("foo bar baz", |cx, history| {
+            cx.run(command("yw").unwrap(), Some(history));
+            cx.run(command("P").unwrap(), Some(history));
+            assert_eq!(cx.text(), "foo foo bar baz");
+            // The cursor is on the last character put
+            cx.run(command("c$").unwrap(), Some(history));
+            assert_eq!(cx.text(), "foo");
+            assert!(cx.state.is_insert());
+        });
+    }
+
+    #[test]
+    fn huge_counts_stop_at_the_text_end() {
+        with_context("foo bar\nbaz", |cx, history| {
+            cx.run(command("999999999j").unwrap(), Some(history));
+            assert_eq!(cx.editor.cursor().line, 1);
+            cx.run(command("999999999w").unwrap(), Some(history));
+            cx.run(command("999999999u").unwrap(), Some(history));
+            cx.run(command("999999999x").unwrap(), Some(history));
+            assert_eq!(cx.text(), "foo bar\nba");
+            cx.run(command("999999999dd").unwrap(), Some(history));
+            assert_eq!(cx.text(), "foo bar");
+        });
+    }
+
+    #[test]
+    fn insert_session_is_one_undo_step() {
+        with_context("foo bar", |cx, history| {
+            cx.run(command("x").unwrap(), Some(history));
+            assert_eq!(cx.text(), "oo bar");
+
+            cx.run(command("ciw").unwrap(), Some(history));
+            cx.type_text("xy", Some(history));
+            cx.type_text(" z", Some(history));
+            cx.leave_insert(Some(history));
+            assert_eq!(cx.text(), "xy z bar");
+
+            let undo = command("u").unwrap();
+            cx.undo_redo(Some(history), undo);
+            assert_eq!(cx.text(), "oo bar");
+            cx.undo_redo(Some(history), undo);
+            assert_eq!(cx.text(), "foo bar");
+
+            let redo = VimCommand {
+                count: None,
+                register: None,
+                action: VimAction::Redo,
+            };
+            cx.undo_redo(Some(history), redo);
+            assert_eq!(cx.text(), "oo bar");
+            cx.undo_redo(Some(history), redo);
+            assert_eq!(cx.text(), "xy z bar");
+        });
+    }
+}
@@ -1,6 +1,6 @@
 use crate::*;
 use bevy::{prelude::*, window::PrimaryWindow};
-use cosmic_text::Affinity;
+use cosmic_text::{Align, Wrap};
 
 /// System set for cosmic text layout systems. Runs in [`PostUpdate`]
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
@@ -17,6 +17,7 @@ impl Plugin for WidgetPlugin {
                     (new_image_from_default, set_sprite_size_from_ui),
                     set_widget_size,
                     set_buffer_size,
+                    set_line_align,
                     set_padding,
                     set_x_offset,
                 )
@@ -47,15 +48,55 @@ fn reshape(mut query: Query<&mut CosmicEditor>, mut font_system: ResMut<CosmicFo
     }
 }
 
+/// Position of the top left of a widget's text in buffer pixels, for `size` in buffer pixels.
+///
+/// Used for the [`CosmicPadding`], so drawing and mouse input agree on where the text is. With
+/// [`CosmicWrap::Wrap`] lines are aligned inside the buffer width by [`set_line_align`], with
+/// [`CosmicWrap::InfiniteLine`] the text is placed as a block.
+pub(crate) fn text_origin(
+    position: &CosmicTextAlign,
+    mode: &CosmicWrap,
+    size: Vec2,
+    buffer: &Buffer,
+    gutter: f32,
+) -> Vec2 {
+    let (horizontal, vertical, padding) = position.parts();
+    let (text_width, text_height) = get_text_size(buffer);
+    let rtl = buffer.layout_runs().next().is_some_and(|run| run.rtl);
+
+    let left = gutter + padding.x;
+    let right = size.x - padding.x - text_width;
+    let x = match (mode, horizontal) {
+        (CosmicWrap::Wrap, _) => left,
+        (_, HorizontalAlign::Left) => left,
+        (_, HorizontalAlign::Right) => right,
+        (_, HorizontalAlign::Center) => gutter + (size.x - gutter - text_width) / 2.,
+        (_, HorizontalAlign::Start) if rtl => right,
+        (_, HorizontalAlign::Start) => left,
+        (_, HorizontalAlign::End) if rtl => left,
+        (_, HorizontalAlign::End) => right,
+    };
+    let y = match vertical {
+        VerticalAlign::Top => padding.y,
+        VerticalAlign::Center => (size.y - text_height) / 2.,
+        VerticalAlign::Bottom => size.y - padding.y - text_height,
+    };
+
+    // Text that does not fit starts at the padding, and scrolls from there
+    Vec2::new(x.max(left).floor(), y.max(padding.y).floor())
+}
+
 /// Programatically sets the [`CosmicPadding`] of a widget based on it's [`CosmicTextAlign`]
 fn set_padding(
     mut query: Query<
         (
             &mut CosmicPadding,
             &CosmicTextAlign,
+            &CosmicWrap,
             &CosmicBuffer,
             &CosmicWidgetSize,
             Option<&CosmicEditor>,
+            Option<&LineNumbers>,
         ),
         Or<(
             With<CosmicEditor>,
@@ -65,7 +106,7 @@ fn set_padding(
         )>,
     >,
 ) {
-    for (mut padding, position, buffer, size, editor_opt) in query.iter_mut() {
+    for (mut padding, position, mode, buffer, size, editor_opt, numbers_opt) in query.iter_mut() {
         // TODO: At least one of these clones is uneccessary
         let mut buffer = buffer.0.clone();
 
@@ -77,22 +118,62 @@ fn set_padding(
             continue;
         }
 
-        padding.0 = match position {
-            CosmicTextAlign::Center { padding: _ } => Vec2::new(
-                get_x_offset_center(size.0.x, &buffer) as f32,
-                get_y_offset_center(size.0.y, &buffer) as f32,
-            ),
-            CosmicTextAlign::TopLeft { padding } => Vec2::new(*padding as f32, *padding as f32),
-            CosmicTextAlign::Left { padding } => Vec2::new(
-                *padding as f32,
-                get_y_offset_center(size.0.y, &buffer) as f32,
-            ),
+        // Text is laid out right of the line number gutter
+        padding.0 = text_origin(position, mode, size.0, &buffer, gutter_width(numbers_opt));
+    }
+}
+
+/// Aligns each line inside the buffer width for wrapped text. Lines of single line widgets are
+/// laid out from the left and aligned as a block by [`text_origin`].
+fn set_line_align(
+    mut query: Query<(
+        &mut CosmicBuffer,
+        Option<&mut CosmicEditor>,
+        &CosmicTextAlign,
+        &CosmicWrap,
+    )>,
+    mut font_system: ResMut<CosmicFontSystem>,
+) {
+    for (mut buffer, editor_opt, position, mode) in query.iter_mut() {
+        let align = match (mode, position.parts().0) {
+            (CosmicWrap::InfiniteLine, _) => Some(Align::Left),
+            (_, HorizontalAlign::Left) => Some(Align::Left),
+            (_, HorizontalAlign::Center) => Some(Align::Center),
+            (_, HorizontalAlign::Right) => Some(Align::Right),
+            // Cosmic text aligns paragraphs to their start by default
+            (_, HorizontalAlign::Start) => None,
+            (_, HorizontalAlign::End) => Some(Align::End),
+        };
+        let realign = |b: &mut Buffer| {
+            let mut changed = false;
+            for line in b.lines.iter_mut() {
+                if line.align() != align {
+                    line.set_align(align);
+                    changed = true;
+                }
+            }
+            changed
+        };
+
+        match editor_opt {
+            Some(mut editor) => {
+                if editor.bypass_change_detection().with_buffer_mut(realign) {
+                    editor.shape_as_needed(&mut font_system.0, false);
+                    editor.set_redraw(true);
+                }
+            }
+            None => {
+                if realign(&mut buffer.bypass_change_detection().0) {
+                    buffer.shape_until_scroll(&mut font_system.0, false);
+                    buffer.set_redraw(true);
+                }
+            }
         }
     }
 }
 
 /// Programatically sets the [`CosmicWidgetSize`] of a widget based on it's [`Sprite`] properties
-fn set_widget_size(
+pub(crate) fn set_widget_size(
     mut query: Query<(&mut CosmicWidgetSize, &Sprite), Changed<Sprite>>,
     windows: Query<&Window, With<PrimaryWindow>>,
 ) {
@@ -107,35 +188,37 @@ fn set_widget_size(
 }
 
 /// Sets the internal [`Buffer`]'s size according to the [`CosmicWidgetSize`] and [`CosmicTextAlign`]
-fn set_buffer_size(
+pub(crate) fn set_buffer_size(
     mut query: Query<
         (
             &mut CosmicBuffer,
             &CosmicWrap,
             &CosmicWidgetSize,
             &CosmicTextAlign,
+            Option<&LineNumbers>,
         ),
         Or<(
             Changed<CosmicWrap>,
             Changed<CosmicWidgetSize>,
             Changed<CosmicTextAlign>,
+            Changed<LineNumbers>,
         )>,
     >,
     mut font_system: ResMut<CosmicFontSystem>,
 ) {
-    for (mut buffer, mode, size, position) in query.iter_mut() {
-        let padding_x = match position {
-            CosmicTextAlign::Center { padding: _ } => 0.,
-            CosmicTextAlign::TopLeft { padding } => *padding as f32,
-            CosmicTextAlign::Left { padding } => *padding as f32,
-        };
+    for (mut buffer, mode, size, position, numbers_opt) in query.iter_mut() {
+        let padding_x = position.padding().x;
+        let buffer_width = (size.0.x - padding_x * 2. - gutter_width(numbers_opt)).max(1.);
 
-        let (buffer_width, buffer_height) = match mode {
-            CosmicWrap::InfiniteLine => (f32::MAX, size.0.y),
-            CosmicWrap::Wrap => (size.0.x - padding_x, size.0.y),
+        // Single lines are not wrapped rather than laid out infinitely wide, which would put
+        // right-to-left glyphs at negative x once the width swallows the line width
+        let wrap = match mode {
+            CosmicWrap::InfiniteLine => Wrap::None,
+            CosmicWrap::Wrap => Wrap::WordOrGlyph,
         };
 
-        buffer.set_size(&mut font_system.0, buffer_width, buffer_height);
+        buffer.set_wrap(&mut font_system.0, wrap);
+        buffer.set_size(&mut font_system.0, buffer_width, size.0.y);
     }
 }
 
@@ -149,6 +232,7 @@ fn new_image_from_default(
     }
 }
 
+/// Scrolls single line widgets horizontally to keep the caret in view
 fn set_x_offset(
     mut query: Query<(
         &mut XOffset,
@@ -156,51 +240,50 @@ fn set_x_offset(
         &CosmicEditor,
         &CosmicWidgetSize,
         &CosmicTextAlign,
+        Option<&LineNumbers>,
+        Option<&ScrollMargin>,
     )>,
 ) {
-    for (mut x_offset, mode, editor, size, position) in query.iter_mut() {
+    for (mut x_offset, mode, editor, size, position, numbers_opt, margin_opt) in query.iter_mut() {
         if mode != &CosmicWrap::InfiniteLine {
-            return;
+            continue;
         }
 
-        let mut cursor_x = 0.;
+        // Caret x from layout, which handles multibyte and right-to-left text
         let cursor = editor.cursor();
-
-        if let Some(line) = editor.with_buffer(|b| b.clone()).layout_runs().next() {
-            for (idx, glyph) in line.glyphs.iter().enumerate() {
-                if cursor.affinity == Affinity::Before {
-                    if idx <= cursor.index {
-                        cursor_x += glyph.w;
-                    } else {
-                        break;
-                    }
-                } else if idx < cursor.index {
-                    cursor_x += glyph.w;
-                } else {
-                    break;
-                }
-            }
-        }
-
-        let padding_x = match position {
-            CosmicTextAlign::Center { padding } => *padding as f32,
-            CosmicTextAlign::TopLeft { padding } => *padding as f32,
-            CosmicTextAlign::Left { padding } => *padding as f32,
+        let Some((cursor_x, line_w)) = editor.with_buffer(|b| {
+            let (x, _) = get_cursor_position(b, cursor)?;
+            let line_w = b
+                .layout_runs()
+                .filter(|run| run.line_i == cursor.line)
+                .map(|run| run.line_w)
+                .fold(0., f32::max);
+            Some((x, line_w))
+        }) else {
+            continue;
         };
 
-        if x_offset.width == 0. {
-            x_offset.width = size.x - padding_x * 2.;
-        }
+        let width = (size.x - position.padding().x * 2. - gutter_width(numbers_opt)).max(0.);
+        let margin = margin_opt.map_or(0., |m| m.0).min(width / 2.);
 
-        let right = x_offset.width + x_offset.left;
+        let mut left = x_offset.left;
+        if x_offset.caret != Some(cursor) {
+            if cursor_x + margin > left + width {
+                left = cursor_x + margin - width;
+            }
+            if cursor_x - margin < left {
+                left = cursor_x - margin;
+            }
+            x_offset.caret = Some(cursor);
+        }
+        // No empty space past the ends of the line, leaving room for the caret
+        left = left.clamp(0., (line_w + 1. - width).max(0.));
 
-        if cursor_x > right {
-            let diff = cursor_x - right;
-            x_offset.left += diff;
+        if x_offset.left != left {
+            x_offset.left = left;
         }
-        if cursor_x < x_offset.left {
-            let diff = x_offset.left - cursor_x;
-            x_offset.left -= diff;
+        if x_offset.width != width {
+            x_offset.width = width;
         }
     }
 }
@@ -215,3 +298,56 @@ fn set_sprite_size_from_ui(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn right_to_left_single_lines_scroll_from_zero() {
+        let mut font_system = create_cosmic_font_system(CosmicFontConfig::default());
+        let text = "שלום עולם שלום עולם";
+        let mut buffer = CosmicBuffer::new(&mut font_system, Metrics::new(14., 18.));
+        buffer.set_text(&mut font_system, text, Attrs::new());
+
+        let mut app = App::new();
+        app.insert_resource(CosmicFontSystem(font_system))
+            .add_systems(Update, (set_buffer_size, set_line_align).chain());
+        let entity = app
+            .world
+            .spawn((
+                buffer,
+                CosmicWrap::InfiniteLine,
+                CosmicWidgetSize(Vec2::new(60., 20.)),
+                CosmicTextAlign::Left { padding: 0 },
+                XOffset::default(),
+            ))
+            .id();
+        app.update();
+
+        // Laid out like the widget renders it: the line runs right to left from its width to 0
+        let buffer = &app.world.get::<CosmicBuffer>(entity).unwrap().0;
+        let run = buffer.layout_runs().next().unwrap();
+        assert!(run.rtl && run.line_w > 60.);
+        assert!(run
+            .glyphs
+            .iter()
+            .all(|g| g.x >= 0. && g.x + g.w <= run.line_w + 0.5));
+
+        let start = get_cursor_position(buffer, Cursor::new(0, 0)).unwrap().0;
+        let end = get_cursor_position(buffer, Cursor::new(0, text.len()))
+            .unwrap()
+            .0;
+        assert!((start - run.line_w).abs() < 0.5 && end.abs() < 0.5);
+
+        // The caret at the start of the text is scrolled into view at the right end
+        let editor = CosmicEditor::new(Editor::new(buffer.clone()));
+        app.world.entity_mut(entity).insert(editor);
+        app.add_systems(Update, set_x_offset.after(set_line_align));
+        app.update();
+
+        let x_offset = app.world.get::<XOffset>(entity).unwrap();
+        assert!(x_offset.left > 0.);
+        assert!(start >= x_offset.left && start <= x_offset.left + x_offset.width + 1.);
+    }
+}